@@ -6,10 +6,13 @@
     mesh_bindings::mesh,
 }
 
+@group(#{MATERIAL_BIND_GROUP}) @binding(0) var<uniform> color: vec4<f32>;
+
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
+    @location(2) light: vec4<f32>,
 }
 
 struct VertexOutput {
@@ -17,6 +20,7 @@ struct VertexOutput {
     @location(0) world_position: vec4<f32>,
     @location(1) world_normal: vec3<f32>,
     @location(2) instance_index: u32,
+    @location(3) light: vec4<f32>,
 }
 
 @vertex
@@ -32,6 +36,7 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     out.world_position = world_from_local * vec4<f32>(vertex.position, 1.0);
     out.world_normal = mesh_normal_local_to_world(vertex.normal, vertex.instance_index);
     out.instance_index = vertex.instance_index;
+    out.light = vertex.light;
 
     return out;
 }
@@ -52,7 +57,10 @@ fn fragment(in: VertexOutput) -> FragmentOutput {
     );
 
     pbr_input.N = normalize(pbr_input.world_normal);
-    pbr_input.material.base_color = vec4(0.4, 0.5, 0.4, 1.0);
+    // coloured block light in rgb, sky light in w, all in 0..1
+    let voxel_light = max(in.light.rgb, vec3(in.light.w));
+    let brightness = mix(vec3(0.05), vec3(1.0), voxel_light * voxel_light);
+    pbr_input.material.base_color = vec4(color.rgb * brightness, color.a);
 
     var out: FragmentOutput;
     out.color = apply_pbr_lighting(pbr_input);
@@ -0,0 +1,478 @@
+use crate::block::{Block, Orientation};
+use crate::block_registry::BlockRegistry;
+use crate::chunk::{CHUNK_SIZE, CHUNK_SIZE3, Chunk, ChunkPos, ChunkSection};
+use crate::level::Level;
+use crate::nbt::{Nbt, NbtError};
+use crate::region::RegionStorage;
+use bevy::math::IVec2;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::sync::{Arc, RwLock};
+use std::{fmt, fs, io};
+
+/// First data version storing block states with a palette (1.13).
+const MIN_DATA_VERSION: i64 = 1451;
+/// First data version packing block states without spreading entries over two longs (1.16).
+const PADDED_DATA_VERSION: i64 = 2529;
+const SECTOR_SIZE: usize = 4096;
+
+/// Registry blocks of Minecraft block states.
+///
+/// Read from lines like `minecraft:stone = voxel:stone`. A state with properties, written like
+/// `minecraft:stone_slab[type=double]`, wins over the plain name, and `*` maps every state
+/// without a line of its own. Oriented blocks take their orientation from the `facing` or slab
+/// `type` properties.
+#[derive(Debug, Clone, Default)]
+pub struct AnvilMapping {
+    states: HashMap<String, Block>,
+    fallback: Option<Block>,
+}
+
+impl AnvilMapping {
+    pub fn parse(text: &str, registry: &BlockRegistry) -> Result<Self, AnvilError> {
+        let mut mapping = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: String| AnvilError::Mapping {
+                line: index + 1,
+                message,
+            };
+
+            // the properties of a state contain `=` themselves
+            let properties_end = line.find(']').map_or(0, |end| end + 1);
+            let (state, name) = line[properties_end..]
+                .split_once('=')
+                .map(|(state, name)| (line[..properties_end + state.len()].trim(), name.trim()))
+                .ok_or_else(|| invalid("expected `state = block`".into()))?;
+
+            let block = registry.by_name(name).ok_or_else(|| invalid(format!("unknown block `{name}`")))?;
+            if state == "*" {
+                mapping.fallback = Some(block);
+            } else {
+                mapping.states.insert(state.to_string(), block);
+            }
+        }
+
+        Ok(mapping)
+    }
+
+    /// Block of a state, `None` when it isn't mapped.
+    pub fn block(&self, name: &str, properties: &BTreeMap<String, String>, registry: &BlockRegistry) -> Option<Block> {
+        let block = self
+            .states
+            .get(&state_key(name, properties))
+            .or_else(|| self.states.get(name))
+            .copied()
+            .or_else(|| matches!(name, "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air").then_some(Block(0)))
+            .or(self.fallback)?;
+
+        if !registry.get(block).oriented {
+            return Some(block);
+        }
+
+        // Minecraft's north is towards -z, ours towards +z
+        let facing = match properties.get("facing").or(properties.get("type")).map(String::as_str) {
+            Some("north") => Orientation::South,
+            Some("south") => Orientation::North,
+            Some("east") => Orientation::East,
+            Some("west") => Orientation::West,
+            Some("up" | "top") => Orientation::Up,
+            Some("down" | "bottom") => Orientation::Down,
+            _ => return Some(block),
+        };
+        Some(block.with_facing(facing))
+    }
+}
+
+/// `name[key=value,...]` with the properties sorted by key.
+fn state_key(name: &str, properties: &BTreeMap<String, String>) -> String {
+    if properties.is_empty() {
+        return name.to_string();
+    }
+
+    let properties: Vec<_> = properties.iter().map(|(key, value)| format!("{key}={value}")).collect();
+    format!("{name}[{}]", properties.join(","))
+}
+
+/// Which part of the Minecraft world ends up in the chunks.
+#[derive(Debug, Clone)]
+pub struct AnvilImport {
+    /// Minecraft height of the bottom of the chunks.
+    pub min_y: i32,
+    /// Amount of sections per chunk.
+    pub sections: usize,
+}
+
+impl Default for AnvilImport {
+    fn default() -> Self {
+        Self { min_y: 48, sections: 2 }
+    }
+}
+
+/// Chunks converted from a region file.
+#[derive(Debug, Default)]
+pub struct AnvilRegion {
+    pub chunks: Vec<(ChunkPos, Chunk)>,
+    /// States without a mapping by how many blocks had them, these became air.
+    pub unmapped: BTreeMap<String, usize>,
+}
+
+/// Converts every chunk of a Minecraft Anvil region file (`.mca`, 1.13 or newer).
+pub fn import_region(
+    bytes: &[u8],
+    mapping: &AnvilMapping,
+    registry: &BlockRegistry,
+    options: &AnvilImport,
+) -> Result<AnvilRegion, AnvilError> {
+    if bytes.len() < SECTOR_SIZE * 2 {
+        return Err(AnvilError::Invalid("region file without a header".into()));
+    }
+
+    let mut region = AnvilRegion::default();
+    for slot in 0..1024 {
+        let location = &bytes[slot * 4..slot * 4 + 4];
+        let offset = u32::from_be_bytes([0, location[0], location[1], location[2]]) as usize * SECTOR_SIZE;
+        if offset == 0 {
+            continue;
+        }
+
+        let nbt = read_chunk_nbt(bytes, offset)?;
+        region.chunks.push(import_chunk(&nbt, mapping, registry, options, &mut region.unmapped)?);
+    }
+
+    Ok(region)
+}
+
+fn read_chunk_nbt(bytes: &[u8], offset: usize) -> Result<Nbt, AnvilError> {
+    let truncated = || AnvilError::Invalid("chunk outside of the region file".into());
+    let header = bytes.get(offset..offset + 5).ok_or_else(truncated)?;
+    let len = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let compressed = bytes.get(offset + 5..offset + 4 + len.max(1)).ok_or_else(truncated)?;
+
+    let mut raw = vec![];
+    match header[4] {
+        1 => GzDecoder::new(compressed).read_to_end(&mut raw)?,
+        2 => ZlibDecoder::new(compressed).read_to_end(&mut raw)?,
+        3 => {
+            raw.extend_from_slice(compressed);
+            raw.len()
+        }
+        compression => {
+            return Err(AnvilError::Unsupported(format!("chunk compression {compression}")));
+        }
+    };
+
+    Ok(Nbt::parse(&raw)?)
+}
+
+/// Converts the NBT of a single Minecraft chunk.
+pub fn import_chunk(
+    nbt: &Nbt,
+    mapping: &AnvilMapping,
+    registry: &BlockRegistry,
+    options: &AnvilImport,
+    unmapped: &mut BTreeMap<String, usize>,
+) -> Result<(ChunkPos, Chunk), AnvilError> {
+    let missing = |what: &str| AnvilError::Invalid(format!("chunk without {what}"));
+    let data_version = nbt.get("DataVersion").and_then(Nbt::as_i64).unwrap_or(0);
+    if data_version < MIN_DATA_VERSION {
+        return Err(AnvilError::Unsupported(format!("data version {data_version}, needs 1.13 or newer")));
+    }
+
+    // 1.18 moved everything out of `Level` and renamed it to snake case
+    let (root, sections_key) = match nbt.get("Level") {
+        Some(level) => (level, "Sections"),
+        None => (nbt, "sections"),
+    };
+    let x = root.get("xPos").and_then(Nbt::as_i64).ok_or_else(|| missing("xPos"))?;
+    let z = root.get("zPos").and_then(Nbt::as_i64).ok_or_else(|| missing("zPos"))?;
+    let sections = root.get(sections_key).and_then(Nbt::as_list).unwrap_or_default();
+
+    // Minecraft sections by their height
+    let mut decoded = HashMap::new();
+    for section in sections {
+        let Some(y) = section.get("Y").and_then(Nbt::as_i64) else {
+            continue;
+        };
+        let (palette, data) = match section.get("block_states") {
+            Some(states) => (states.get("palette"), states.get("data")),
+            None => (section.get("Palette"), section.get("BlockStates")),
+        };
+        let Some(palette) = palette.and_then(Nbt::as_list) else {
+            continue;
+        };
+
+        let states: Vec<Result<Block, String>> = palette
+            .iter()
+            .map(|state| {
+                let name = state.get("Name").and_then(Nbt::as_str).unwrap_or("minecraft:air");
+                let properties = state
+                    .get("Properties")
+                    .and_then(Nbt::as_compound)
+                    .map(|properties| {
+                        properties
+                            .iter()
+                            .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                mapping.block(name, &properties, registry).ok_or_else(|| state_key(name, &properties))
+            })
+            .collect();
+
+        let data = data.and_then(Nbt::as_long_array).unwrap_or_default();
+        let indices = unpack_indices(data, states.len(), data_version >= PADDED_DATA_VERSION)?;
+        let blocks: Vec<Block> = indices
+            .into_iter()
+            .map(|index| match &states[index] {
+                Ok(block) => *block,
+                Err(state) => {
+                    *unmapped.entry(state.clone()).or_default() += 1;
+                    Block(0)
+                }
+            })
+            .collect();
+        decoded.insert(y, blocks);
+    }
+
+    let mut chunk = Chunk::new();
+    for section_y in 0..options.sections {
+        let mut blocks = vec![Block(0); CHUNK_SIZE3 as usize];
+        for (index, block) in blocks.iter_mut().enumerate() {
+            // our sections go x, then y, then z, Minecraft's x, then z, then y
+            let pos = Chunk::coords_by_index(index as i32);
+            let y = options.min_y + section_y as i32 * CHUNK_SIZE + pos.y;
+            let Some(source) = decoded.get(&(y.div_euclid(CHUNK_SIZE) as i64)) else {
+                continue;
+            };
+            *block = source[(pos.x + pos.z * CHUNK_SIZE + y.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE * CHUNK_SIZE) as usize];
+        }
+
+        let section = ChunkSection::from_blocks(blocks).expect("sections hold 4096 blocks");
+        chunk.sections.push(Arc::new(RwLock::new(section)));
+    }
+
+    Ok((ChunkPos(IVec2::new(x as i32, z as i32)), chunk))
+}
+
+/// Palette indices of the 4096 blocks of a section, of at least 4 bits each. When `padded`,
+/// every long holds as many indices as fit into it, otherwise indices follow each other and
+/// spread over two longs where they don't fit.
+fn unpack_indices(data: &[i64], palette_len: usize, padded: bool) -> Result<Vec<usize>, AnvilError> {
+    if palette_len <= 1 || data.is_empty() {
+        return Ok(vec![0; CHUNK_SIZE3 as usize]);
+    }
+
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(4) as usize;
+    let per_long = 64 / bits;
+    let expected = if padded {
+        (CHUNK_SIZE3 as usize).div_ceil(per_long)
+    } else {
+        (CHUNK_SIZE3 as usize * bits).div_ceil(64)
+    };
+    if data.len() != expected {
+        return Err(AnvilError::Invalid(format!("{} longs for {bits} bit indices", data.len())));
+    }
+
+    let mask = (1u64 << bits) - 1;
+    (0..CHUNK_SIZE3 as usize)
+        .map(|index| {
+            let palette_index = if padded {
+                let long = data[index / per_long] as u64;
+                ((long >> ((index % per_long) * bits)) & mask) as usize
+            } else {
+                let (long, shift) = ((index * bits) / 64, (index * bits) % 64);
+                let mut value = data[long] as u64 >> shift;
+                if shift + bits > 64 {
+                    value |= (data[long + 1] as u64) << (64 - shift);
+                }
+                (value & mask) as usize
+            };
+            if palette_index < palette_len {
+                Ok(palette_index)
+            } else {
+                Err(AnvilError::Invalid(format!("palette index {palette_index} of {palette_len}")))
+            }
+        })
+        .collect()
+}
+
+/// `voxel import-mca <region.mca>... --mapping <file> [--world <dir>] [--min-y <y>]`, converts
+/// region files into the chunks of a saved world. Returns the exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let mut regions = vec![];
+    let mut mapping_path = None;
+    let mut storage = RegionStorage::default();
+    let mut options = AnvilImport::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mapping" => mapping_path = args.next(),
+            "--world" => storage = RegionStorage::new(args.next().cloned().unwrap_or_default()),
+            "--min-y" => match args.next().and_then(|y| y.parse().ok()) {
+                Some(min_y) => options.min_y = min_y,
+                None => {
+                    eprintln!("--min-y needs a number");
+                    return 2;
+                }
+            },
+            _ => regions.push(arg),
+        }
+    }
+    let (Some(mapping_path), false) = (mapping_path, regions.is_empty()) else {
+        eprintln!("usage: voxel import-mca <region.mca>... --mapping <file> [--world <dir>] [--min-y <y>]");
+        return 2;
+    };
+
+    let import = || -> Result<(), Box<dyn std::error::Error>> {
+        let registry = BlockRegistry::default();
+        Level::open(&storage, &registry)?;
+        let mapping = AnvilMapping::parse(&fs::read_to_string(mapping_path)?, &registry)?;
+
+        for path in &regions {
+            let region = import_region(&fs::read(path)?, &mapping, &registry, &options)?;
+            let chunks: Vec<_> = region.chunks.into_iter().map(|(pos, chunk)| (pos, Arc::new(chunk))).collect();
+            storage.save_chunks(&chunks)?;
+
+            println!("{path}: imported {} chunks", chunks.len());
+            for (state, count) in region.unmapped {
+                println!("  {count} blocks of unmapped `{state}` became air");
+            }
+        }
+
+        Ok(())
+    };
+
+    match import() {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("import failed: {err}");
+            1
+        }
+    }
+}
+
+/// Reason a region couldn't be imported.
+#[derive(Debug)]
+pub enum AnvilError {
+    Io(io::Error),
+    Nbt(NbtError),
+    Invalid(String),
+    Unsupported(String),
+    Mapping { line: usize, message: String },
+}
+
+impl Display for AnvilError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            AnvilError::Io(err) => write!(f, "{err}"),
+            AnvilError::Nbt(err) => write!(f, "{err}"),
+            AnvilError::Invalid(message) => write!(f, "invalid region: {message}"),
+            AnvilError::Unsupported(message) => write!(f, "unsupported region: {message}"),
+            AnvilError::Mapping { line, message } => write!(f, "invalid mapping, line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for AnvilError {}
+
+impl From<io::Error> for AnvilError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<NbtError> for AnvilError {
+    fn from(err: NbtError) -> Self {
+        Self::Nbt(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::IVec3;
+
+    #[test]
+    fn imports_the_fixture_region() {
+        let registry = BlockRegistry::default();
+        let mapping = AnvilMapping::parse(include_str!("../assets/anvil/blocks.mapping"), &registry).unwrap();
+        let bytes = include_bytes!("../assets/anvil/r.-1.0.mca");
+        let region = import_region(bytes, &mapping, &registry, &AnvilImport::default()).unwrap();
+
+        let positions: Vec<_> = region.chunks.iter().map(|(pos, _)| pos.0).collect();
+        assert_eq!(positions, [IVec2::new(-32, 0), IVec2::new(-31, 1)]);
+        assert_eq!(region.unmapped, BTreeMap::from([("minecraft:torch".to_string(), 2)]));
+
+        let stone = registry.by_name("voxel:stone").unwrap();
+        let stairs = registry.by_name("voxel:stone_stairs").unwrap();
+        for (_, chunk) in &region.chunks {
+            assert_eq!(chunk.sections.len(), 2);
+            assert_eq!(chunk.get(IVec3::new(0, 0, 0)), Some(stone));
+            assert_eq!(chunk.get(IVec3::new(0, 8, 0)), Some(Block(0)));
+            assert_eq!(chunk.get(IVec3::new(15, 31, 15)), Some(stone));
+            // Minecraft's stairs face north, towards -z
+            assert_eq!(chunk.get(IVec3::new(1, 1, 0)), Some(stairs.with_facing(Orientation::South)));
+        }
+    }
+
+    /// Indices of `bits` bits each, packed like Minecraft does before or since 1.16.
+    fn pack(indices: &[usize], bits: usize, padded: bool) -> Vec<i64> {
+        let mut data = vec![];
+        for (index, &value) in indices.iter().enumerate() {
+            let bit = if padded {
+                (index / (64 / bits)) * 64 + (index % (64 / bits)) * bits
+            } else {
+                index * bits
+            };
+            for offset in 0..bits {
+                let (long, shift) = ((bit + offset) / 64, (bit + offset) % 64);
+                if data.len() <= long {
+                    data.resize(long + 1, 0u64);
+                }
+                data[long] |= ((value as u64 >> offset) & 1) << shift;
+            }
+        }
+        data.into_iter().map(|long| long as i64).collect()
+    }
+
+    fn counting_indices(palette_len: usize) -> Vec<usize> {
+        (0..CHUNK_SIZE3 as usize).map(|index| index * 7 % palette_len).collect()
+    }
+
+    #[test]
+    fn unpacks_indices_spread_over_two_longs() {
+        // 6 bit indices, the 11th starts 4 bits before the end of the first long
+        let indices = counting_indices(40);
+        let data = pack(&indices, 6, false);
+        assert_eq!(data.len(), 384);
+        assert_eq!(unpack_indices(&data, 40, false).unwrap(), indices);
+        assert!(unpack_indices(&data, 40, true).is_err());
+    }
+
+    #[test]
+    fn unpacks_padded_indices() {
+        // 6 bit indices, 10 per long with the last 4 bits unused
+        let indices = counting_indices(40);
+        let data = pack(&indices, 6, true);
+        assert_eq!(data.len(), 410);
+        assert_eq!(unpack_indices(&data, 40, true).unwrap(), indices);
+        assert!(unpack_indices(&data, 40, false).is_err());
+    }
+
+    #[test]
+    fn small_palettes_use_four_bits() {
+        let indices = counting_indices(3);
+        for padded in [false, true] {
+            assert_eq!(unpack_indices(&pack(&indices, 4, padded), 3, padded).unwrap(), indices);
+        }
+        assert_eq!(unpack_indices(&[], 1, true).unwrap(), vec![0; CHUNK_SIZE3 as usize]);
+        assert!(unpack_indices(&pack(&counting_indices(16), 4, true), 3, true).is_err());
+    }
+}
@@ -1,8 +1,9 @@
-﻿use std::cmp::PartialEq;
+﻿use crate::quad::Direction;
+use std::cmp::PartialEq;
 
 /// Block representation
 ///
-/// msb  ``u3: orientation``
+/// msb  ``u3: orientation``, see [`Orientation`]
 ///      ``u3: variant``
 /// lsb  ``u10: id``
 #[repr(transparent)]
@@ -60,10 +61,186 @@ impl Block {
 
     pub fn set_orientation(&mut self, orientation: u16) {
         self.0 =
-            (self.0 & !Self::ORIENTATION_MASK) | ((orientation << 10) & Self::ORIENTATION_MASK);
+            (self.0 & !Self::ORIENTATION_MASK) | ((orientation << 13) & Self::ORIENTATION_MASK);
+    }
+
+    /// Orientation stored in the orientation bits, unused values read as [`Orientation::North`].
+    pub fn facing(&self) -> Orientation {
+        Orientation::from_bits(self.orientation()).unwrap_or_default()
+    }
+
+    pub fn with_facing(mut self, facing: Orientation) -> Block {
+        self.set_orientation(facing.bits() as u16);
+        self
+    }
+
+    /// Turns the block by quarter turns counter-clockwise seen from above, north towards east.
+    pub fn rotate_y(&self, quarter_turns: i32) -> Block {
+        self.with_facing(self.facing().rotate_y(quarter_turns))
+    }
+
+    /// Mirrors the block along an axis.
+    pub fn mirror(&self, axis: Axis) -> Block {
+        self.with_facing(self.facing().mirror(axis))
     }
 
     pub fn is_solid(&self) -> bool {
         self != &Block(0)
     }
 }
+
+/// Facing stored in the orientation bits of a [`Block`].
+///
+/// Horizontal facings go counter-clockwise seen from above, north being +z and east +x. Values 6
+/// and 7 are unused.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Orientation {
+    #[default]
+    North = 0,
+    East = 1,
+    South = 2,
+    West = 3,
+    Up = 4,
+    Down = 5,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 6] = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+        Orientation::Up,
+        Orientation::Down,
+    ];
+
+    const HORIZONTAL: [Orientation; 4] = [
+        Orientation::North,
+        Orientation::East,
+        Orientation::South,
+        Orientation::West,
+    ];
+
+    /// `None` for the unused values.
+    pub fn from_bits(bits: u8) -> Option<Orientation> {
+        Self::ALL.get(bits as usize).copied()
+    }
+
+    pub fn bits(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Facing looking towards a face of a block.
+    pub fn from_direction(direction: Direction) -> Orientation {
+        Self::ALL.into_iter().find(|facing| facing.direction() == direction).unwrap()
+    }
+
+    /// Face of a block looking towards the facing.
+    pub fn direction(&self) -> Direction {
+        match self {
+            Orientation::North => Direction::Back,
+            Orientation::East => Direction::Right,
+            Orientation::South => Direction::Forward,
+            Orientation::West => Direction::Left,
+            Orientation::Up => Direction::Up,
+            Orientation::Down => Direction::Down,
+        }
+    }
+
+    /// Turns horizontal facings by quarter turns counter-clockwise seen from above, vertical ones
+    /// stay.
+    pub fn rotate_y(&self, quarter_turns: i32) -> Orientation {
+        match self {
+            Orientation::Up | Orientation::Down => *self,
+            _ => Self::HORIZONTAL[(self.bits() as i32 + quarter_turns).rem_euclid(4) as usize],
+        }
+    }
+
+    /// Flips facings pointing along the axis.
+    pub fn mirror(&self, axis: Axis) -> Orientation {
+        match (self, axis) {
+            (Orientation::East, Axis::X) => Orientation::West,
+            (Orientation::West, Axis::X) => Orientation::East,
+            (Orientation::Up, Axis::Y) => Orientation::Down,
+            (Orientation::Down, Axis::Y) => Orientation::Up,
+            (Orientation::North, Axis::Z) => Orientation::South,
+            (Orientation::South, Axis::Z) => Orientation::North,
+            _ => *self,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every stored value, orientation bits included.
+    fn all_blocks() -> impl Iterator<Item = Block> {
+        (0..=u16::MAX).map(Block)
+    }
+
+    #[test]
+    fn every_orientation_round_trips_through_the_orientation_bits() {
+        for orientation in Orientation::ALL {
+            assert_eq!(Orientation::from_bits(orientation.bits()), Some(orientation));
+        }
+        assert_eq!(Orientation::from_bits(6), None);
+        assert_eq!(Orientation::from_bits(7), None);
+
+        for block in all_blocks() {
+            for orientation in Orientation::ALL {
+                let oriented = block.with_facing(orientation);
+                assert_eq!(oriented.facing(), orientation);
+                assert_eq!(oriented.orientation(), orientation.bits());
+                assert_eq!(oriented.0 & !Block::ORIENTATION_MASK, block.0 & !Block::ORIENTATION_MASK);
+            }
+        }
+    }
+
+    #[test]
+    fn a_quarter_turn_takes_north_to_east() {
+        assert_eq!(Orientation::North.rotate_y(1), Orientation::East);
+        assert_eq!(Orientation::East.rotate_y(1), Orientation::South);
+        assert_eq!(Orientation::North.rotate_y(-1), Orientation::West);
+        assert_eq!(Orientation::Up.rotate_y(1), Orientation::Up);
+    }
+
+    #[test]
+    fn four_quarter_turns_are_the_identity() {
+        for orientation in Orientation::ALL {
+            for turns in -5..=5 {
+                let turned = (0..4).fold(orientation, |facing, _| facing.rotate_y(turns));
+                assert_eq!(turned, orientation, "{orientation:?} turned 4 times by {turns}");
+            }
+        }
+
+        for block in all_blocks().filter(|block| block.orientation() < 6) {
+            let turned = (0..4).fold(block, |block, _| block.rotate_y(1));
+            assert_eq!(turned, block);
+            assert_eq!((block.id(), block.variant()), (block.rotate_y(1).id(), block.rotate_y(1).variant()));
+        }
+    }
+
+    #[test]
+    fn mirroring_twice_is_the_identity() {
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            for orientation in Orientation::ALL {
+                assert_eq!(orientation.mirror(axis).mirror(axis), orientation);
+                let along = orientation.direction().offset()[axis as usize] != 0;
+                assert_eq!(orientation.mirror(axis) != orientation, along, "{orientation:?} mirrored along {axis:?}");
+            }
+
+            for block in all_blocks().filter(|block| block.orientation() < 6) {
+                assert_eq!(block.mirror(axis).mirror(axis), block);
+                assert_eq!((block.id(), block.variant()), (block.mirror(axis).id(), block.mirror(axis).variant()));
+            }
+        }
+    }
+}
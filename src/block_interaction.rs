@@ -0,0 +1,182 @@
+use crate::block::{Block, Orientation};
+use crate::collision::Aabb;
+use crate::edit_history::EditHistory;
+use crate::world::{RaycastHit, World};
+use crate::world_edit::WorldEdit;
+use bevy::app::{App, Plugin, PreUpdate, Update};
+use bevy::camera::Camera;
+use bevy::input::ButtonInput;
+use bevy::log::warn;
+use bevy::math::{IVec3, Ray3d, Vec3};
+use bevy::picking::PickingSystems;
+use bevy::picking::backend::ray::{RayId, RayMap};
+use bevy::picking::backend::{HitData, PointerHits};
+use bevy::picking::pointer::PointerId;
+use bevy::prelude::{Component, Entity, GlobalTransform, IntoScheduleConfigs, MessageWriter, MouseButton, Query, Res, ResMut, Resource, With, not};
+use bevy::window::{CursorGrabMode, CursorOptions, PrimaryWindow};
+use bevy_inspector_egui::bevy_egui::input::egui_wants_any_pointer_input;
+
+/// Farthest distance blocks are reported to picking at.
+const PICKING_DISTANCE: f32 = 256.0;
+
+/// Box of the player relative to its translation, blocks aren't placed where they'd overlap it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PlayerCollider {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for PlayerCollider {
+    /// Box of a camera at eye height.
+    fn default() -> Self {
+        Self {
+            min: Vec3::new(-0.3, -1.6, -0.3),
+            max: Vec3::new(0.3, 0.2, 0.3),
+        }
+    }
+}
+
+impl PlayerCollider {
+    /// Box of the player at a translation.
+    pub fn aabb(&self, translation: Vec3) -> Aabb {
+        Aabb::new(translation + self.min, translation + self.max)
+    }
+
+    pub fn intersects_block(&self, translation: Vec3, block: IVec3) -> bool {
+        let block = block.as_vec3();
+        self.aabb(translation).intersects(&Aabb::new(block, block + 1.0))
+    }
+}
+
+/// Buttons and reach of [`BlockInteractionPlugin`].
+#[derive(Resource, Debug, Clone)]
+pub struct BlockInteraction {
+    /// Farthest distance from the camera blocks can be broken or placed against.
+    pub reach: f32,
+    pub break_button: MouseButton,
+    pub place_button: MouseButton,
+    /// Name of the block placed with the place button.
+    pub selected: String,
+}
+
+impl Default for BlockInteraction {
+    fn default() -> Self {
+        Self {
+            reach: 6.0,
+            break_button: MouseButton::Left,
+            place_button: MouseButton::Right,
+            selected: "voxel:stone".into(),
+        }
+    }
+}
+
+/// Block within reach under the cursor, or in the middle of the screen while the cursor is grabbed.
+#[derive(Resource, Debug, Default)]
+pub struct TargetedBlock(pub Option<RaycastHit>);
+
+/// Picking backend reporting the block section meshes under pointers, and breaking and placing
+/// blocks with the mouse for the camera with a [`PlayerCollider`].
+pub struct BlockInteractionPlugin;
+
+impl Plugin for BlockInteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlockInteraction>()
+            .init_resource::<TargetedBlock>()
+            .add_systems(PreUpdate, (Self::pick_blocks, Self::target_block).in_set(PickingSystems::Backend))
+            .add_systems(Update, Self::break_and_place.run_if(not(egui_wants_any_pointer_input)));
+    }
+}
+
+impl BlockInteractionPlugin {
+    /// Reports the section mesh of the first block along every pointer ray, with the hit point
+    /// and face normal.
+    pub fn pick_blocks(
+        ray_map: Res<RayMap>,
+        cameras: Query<&Camera>,
+        world: Res<World>,
+        mut hits: MessageWriter<PointerHits>,
+    ) {
+        for (&ray_id, ray) in ray_map.iter() {
+            let Ok(camera) = cameras.get(ray_id.camera) else {
+                continue;
+            };
+            let Some(hit) = world.raycast(ray.origin, *ray.direction, PICKING_DISTANCE) else {
+                continue;
+            };
+            let Some(entity) = world.section_entity(hit.pos) else {
+                continue;
+            };
+
+            let normal = Vec3::from(hit.normal.normals());
+            let data = HitData::new(ray_id.camera, hit.distance, Some(hit.point), Some(normal));
+            hits.write(PointerHits::new(ray_id.pointer, vec![(entity, data)], camera.order as f32));
+        }
+    }
+
+    /// Updates [`TargetedBlock`] for the player camera.
+    pub fn target_block(
+        ray_map: Res<RayMap>,
+        players: Query<(Entity, &GlobalTransform), With<PlayerCollider>>,
+        cursor: Query<&CursorOptions, With<PrimaryWindow>>,
+        settings: Res<BlockInteraction>,
+        world: Res<World>,
+        mut targeted: ResMut<TargetedBlock>,
+    ) {
+        let crosshair = cursor.single().is_ok_and(|cursor| cursor.grab_mode != CursorGrabMode::None);
+        targeted.0 = players.single().ok().and_then(|(camera, transform)| {
+            let ray = if crosshair {
+                Ray3d::new(transform.translation(), transform.forward())
+            } else {
+                *ray_map.map.get(&RayId::new(camera, PointerId::Mouse))?
+            };
+            world.raycast(ray.origin, *ray.direction, settings.reach)
+        });
+    }
+
+    /// Breaks the targeted block, or places the selected block against the targeted face unless
+    /// it would overlap a player. Oriented blocks face away from the face they're placed on. Both
+    /// are recorded in the [`EditHistory`].
+    pub fn break_and_place(
+        buttons: Res<ButtonInput<MouseButton>>,
+        settings: Res<BlockInteraction>,
+        targeted: Res<TargetedBlock>,
+        players: Query<(&GlobalTransform, &PlayerCollider)>,
+        mut world: ResMut<World>,
+        mut history: ResMut<EditHistory>,
+    ) {
+        let Some(hit) = targeted.0 else {
+            return;
+        };
+
+        if buttons.just_pressed(settings.break_button) {
+            if let Err(err) = history.apply(&mut world, WorldEdit::new().set(hit.pos, Block(0))) {
+                warn!("Failed to break {}: {err}", hit.pos);
+            }
+            return;
+        }
+        if !buttons.just_pressed(settings.place_button) {
+            return;
+        }
+
+        let Some(block) = world.registry.by_name(&settings.selected) else {
+            warn!("Unknown block {} selected", settings.selected);
+            return;
+        };
+        let pos = hit.pos + hit.normal.offset();
+        if world.get_block(pos).is_none_or(|current| current.is_solid()) {
+            return;
+        }
+        if players.iter().any(|(transform, collider)| collider.intersects_block(transform.translation(), pos)) {
+            return;
+        }
+
+        let block = if world.registry.get(block).oriented {
+            block.with_facing(Orientation::from_direction(hit.normal))
+        } else {
+            block
+        };
+        if let Err(err) = history.apply(&mut world, WorldEdit::new().set(pos, block)) {
+            warn!("Failed to place {} at {pos}: {err}", settings.selected);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::block::{Axis, Block, Orientation};
+use crate::block_shape::{BlockShape, ModelBox};
+use bevy::math::IVec3;
+use std::sync::Arc;
+
+const ALL_FACES: u8 = 0b111111;
+
+/// Properties shared by every block with the same id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockDefinition {
+    pub name: String,
+    /// Whether the block stops light and hides the faces of the blocks next to it.
+    pub opaque: bool,
+    /// Whether the block is see-through and drawn in the translucent pass.
+    pub translucent: bool,
+    /// Whether entities collide with the boxes of the block's shape.
+    pub solid: bool,
+    /// How much of an explosion's power the block absorbs, see
+    /// [`World::explode`](crate::world::World::explode).
+    pub blast_resistance: f32,
+    /// Red, green and blue block light emitted by the block, 0 to 15 each.
+    pub light_emission: [u8; 3],
+    pub shape: BlockShape,
+    /// Whether the orientation bits of the block hold an [`Orientation`].
+    pub oriented: bool,
+    /// RGBA color of the block, used where it isn't textured such as mesh exports.
+    pub color: [u8; 4],
+    /// Faces covered by the shape for every orientation.
+    covered_faces: [u8; 6],
+}
+
+impl BlockDefinition {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            opaque: true,
+            translucent: false,
+            solid: true,
+            blast_resistance: 3.0,
+            light_emission: [0; 3],
+            shape: BlockShape::Cube,
+            oriented: false,
+            color: [255; 4],
+            covered_faces: [ALL_FACES; 6],
+        }
+    }
+
+    pub fn transparent(mut self) -> Self {
+        self.opaque = false;
+        self
+    }
+
+    /// Makes the block see-through, which also lets light pass.
+    pub fn translucent(mut self) -> Self {
+        self.opaque = false;
+        self.translucent = true;
+        self
+    }
+
+    /// Lets entities move through the block.
+    pub fn passable(mut self) -> Self {
+        self.solid = false;
+        self
+    }
+
+    pub fn blast_resistance(mut self, resistance: f32) -> Self {
+        self.blast_resistance = resistance;
+        self
+    }
+
+    /// Gives the block another shape than a full cube, which lets light pass and makes it oriented.
+    pub fn shape(mut self, shape: BlockShape) -> Self {
+        if shape != BlockShape::Cube {
+            self.opaque = false;
+            self.oriented = true;
+        }
+        self.covered_faces = Orientation::ALL.map(|orientation| shape.covered_faces(orientation));
+        self.shape = shape;
+        self
+    }
+
+    pub fn emission(mut self, light: [u8; 3]) -> Self {
+        self.light_emission = light.map(|value| value.min(15));
+        self
+    }
+
+    pub fn color(mut self, color: [u8; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+static UNKNOWN_BLOCK: BlockDefinition = BlockDefinition {
+    name: String::new(),
+    opaque: true,
+    translucent: false,
+    solid: true,
+    blast_resistance: 6.0,
+    light_emission: [0; 3],
+    shape: BlockShape::Cube,
+    oriented: false,
+    color: [255, 0, 255, 255],
+    covered_faces: [ALL_FACES; 6],
+};
+
+/// Block definitions indexed by block id, cheap to clone into tasks.
+#[derive(Debug, Clone)]
+pub struct BlockRegistry {
+    definitions: Arc<Vec<BlockDefinition>>,
+}
+
+impl Default for BlockRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            definitions: Arc::new(vec![]),
+        };
+
+        registry.register(BlockDefinition::new("voxel:air").transparent().passable());
+        registry.register(BlockDefinition::new("voxel:stone").blast_resistance(6.0).color([127, 127, 127, 255]));
+        registry.register(BlockDefinition::new("voxel:glowstone").emission([15, 14, 10]).blast_resistance(0.3).color([240, 224, 144, 255]));
+        registry.register(BlockDefinition::new("voxel:lava").emission([15, 7, 1]).blast_resistance(100.0).color([224, 96, 16, 255]));
+        registry.register(BlockDefinition::new("voxel:crystal").translucent().emission([3, 8, 15]).blast_resistance(1.5).color([64, 128, 240, 160]));
+        registry.register(BlockDefinition::new("voxel:glass").translucent().blast_resistance(0.3).color([168, 216, 240, 96]));
+        registry.register(BlockDefinition::new("voxel:leaves").translucent().blast_resistance(0.2).color([64, 160, 64, 200]));
+        registry.register(BlockDefinition::new("voxel:water").translucent().passable().blast_resistance(100.0).color([48, 96, 192, 128]));
+        registry.register(BlockDefinition::new("voxel:stone_slab").shape(BlockShape::Slab).blast_resistance(6.0).color([143, 143, 143, 255]));
+        registry.register(BlockDefinition::new("voxel:stone_stairs").shape(BlockShape::Stairs).blast_resistance(6.0).color([111, 111, 111, 255]));
+        registry.register(BlockDefinition::new("voxel:tall_grass").shape(BlockShape::Cross).passable().blast_resistance(0.0).color([96, 192, 64, 255]));
+        registry.register(
+            BlockDefinition::new("voxel:post")
+                .shape(BlockShape::Custom(Arc::new([ModelBox::new(IVec3::new(6, 0, 6), IVec3::new(10, 16, 10))])))
+                .color([159, 127, 95, 255]),
+        );
+
+        registry
+    }
+}
+
+impl BlockRegistry {
+    /// Adds a definition and returns the id it got.
+    pub fn register(&mut self, definition: BlockDefinition) -> u16 {
+        let definitions = Arc::make_mut(&mut self.definitions);
+        assert!(definitions.len() <= Block::ID_MASK as usize, "block registry is full");
+        definitions.push(definition);
+
+        (definitions.len() - 1) as u16
+    }
+
+    /// Definition of a block, unknown ids get an opaque placeholder.
+    pub fn get(&self, block: Block) -> &BlockDefinition {
+        self.definitions
+            .get(block.id() as usize)
+            .unwrap_or(&UNKNOWN_BLOCK)
+    }
+
+    /// Faces of the block hiding the faces of its neighbors, one bit per
+    /// [`Direction`](crate::quad::Direction).
+    pub fn covered_faces(&self, block: Block) -> u8 {
+        let definition = self.get(block);
+        if definition.translucent {
+            return 0;
+        }
+
+        match definition.shape {
+            BlockShape::Cube if definition.opaque => ALL_FACES,
+            BlockShape::Cube => 0,
+            _ => definition.covered_faces[block.facing().bits() as usize],
+        }
+    }
+
+    /// Whether the block is meshed from its model rather than as a cube.
+    pub fn has_model(&self, block: Block) -> bool {
+        block.is_solid() && self.get(block).shape != BlockShape::Cube
+    }
+
+    /// Boxes entities collide with, turned like the block. Empty for passable blocks.
+    pub fn collision_boxes(&self, block: Block) -> Vec<ModelBox> {
+        let definition = self.get(block);
+        if !definition.solid {
+            return vec![];
+        }
+
+        let boxes = definition.shape.boxes();
+        if definition.oriented {
+            boxes.iter().map(|model_box| model_box.oriented(block.facing())).collect()
+        } else {
+            boxes
+        }
+    }
+
+    /// Turns an oriented block by quarter turns counter-clockwise seen from above, other blocks
+    /// stay as they are.
+    pub fn rotate_y(&self, block: Block, quarter_turns: i32) -> Block {
+        if self.get(block).oriented {
+            block.rotate_y(quarter_turns)
+        } else {
+            block
+        }
+    }
+
+    /// Mirrors an oriented block along an axis, other blocks stay as they are.
+    pub fn mirror(&self, block: Block, axis: Axis) -> Block {
+        if self.get(block).oriented {
+            block.mirror(axis)
+        } else {
+            block
+        }
+    }
+
+    /// Definition names by block id.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.definitions.iter().map(|definition| definition.name.as_str())
+    }
+
+    /// Block with the given definition name, `None` when nothing was registered under it.
+    pub fn by_name(&self, name: &str) -> Option<Block> {
+        self.definitions
+            .iter()
+            .position(|definition| definition.name == name)
+            .map(|id| Block::from_id(id as u16))
+    }
+}
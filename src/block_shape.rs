@@ -0,0 +1,217 @@
+use crate::block::Orientation;
+use crate::quad::Direction;
+use bevy::math::{IVec3, Vec3};
+use std::sync::Arc;
+
+/// Resolution of block models, boxes are given in sixteenths of a block.
+pub const MODEL_RESOLUTION: i32 = 16;
+
+/// Axis aligned box of a block model in sixteenths of a block, `min` inclusive and `max` exclusive.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ModelBox {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl ModelBox {
+    pub const fn new(min: IVec3, max: IVec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Turns the box from facing north to the given orientation.
+    pub fn oriented(&self, orientation: Orientation) -> Self {
+        let center = IVec3::splat(MODEL_RESOLUTION);
+        // doubled so the block center sits on whole numbers
+        let a = rotate(self.min * 2 - center, orientation);
+        let b = rotate(self.max * 2 - center, orientation);
+
+        Self {
+            min: (a.min(b) + center) / 2,
+            max: (a.max(b) + center) / 2,
+        }
+    }
+
+    /// Whether the box reaches the border of the block in the given direction.
+    pub fn touches_border(&self, direction: Direction) -> bool {
+        match direction {
+            Direction::Left => self.min.x == 0,
+            Direction::Down => self.min.y == 0,
+            Direction::Forward => self.min.z == 0,
+            Direction::Right => self.max.x == MODEL_RESOLUTION,
+            Direction::Up => self.max.y == MODEL_RESOLUTION,
+            Direction::Back => self.max.z == MODEL_RESOLUTION,
+        }
+    }
+}
+
+/// Geometry of a block.
+///
+/// Models are authored facing north (+z) and turned by [`Block::facing`](crate::block::Block::facing).
+/// The front of a slab is the side it lies against, so a slab facing down is a bottom slab, the
+/// front of stairs is the side they climb towards.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum BlockShape {
+    /// Full block, meshed by the greedy meshers.
+    #[default]
+    Cube,
+    /// Half a block.
+    Slab,
+    Stairs,
+    /// Two crossing diagonal planes, for plants.
+    Cross,
+    /// Model made of boxes.
+    Custom(Arc<[ModelBox]>),
+}
+
+impl BlockShape {
+    /// Boxes of the model facing north, empty for shapes which aren't made of boxes.
+    pub fn boxes(&self) -> Vec<ModelBox> {
+        let half = MODEL_RESOLUTION / 2;
+        let full = MODEL_RESOLUTION;
+
+        match self {
+            BlockShape::Cube => vec![ModelBox::new(IVec3::ZERO, IVec3::splat(full))],
+            BlockShape::Slab => vec![ModelBox::new(IVec3::new(0, 0, half), IVec3::splat(full))],
+            BlockShape::Stairs => vec![
+                ModelBox::new(IVec3::ZERO, IVec3::new(full, half, full)),
+                ModelBox::new(IVec3::new(0, half, half), IVec3::splat(full)),
+            ],
+            BlockShape::Cross => vec![],
+            BlockShape::Custom(boxes) => boxes.to_vec(),
+        }
+    }
+
+    /// Faces of the block it covers completely when turned to `orientation`, one bit per
+    /// [`Direction`]. Only covered faces hide the faces of neighbors.
+    pub fn covered_faces(&self, orientation: Orientation) -> u8 {
+        let boxes: Vec<ModelBox> = self.boxes().iter().map(|b| b.oriented(orientation)).collect();
+
+        Direction::ALL
+            .into_iter()
+            .filter(|&direction| covers_face(&boxes, direction))
+            .fold(0, |faces, direction| faces | face_bit(direction))
+    }
+}
+
+/// Bit of a face in a face mask such as [`BlockShape::covered_faces`].
+#[inline]
+pub fn face_bit(direction: Direction) -> u8 {
+    1 << direction as u8
+}
+
+/// Faces of every box turned to the block orientation, as quads in block units with the normal
+/// facing out of the box. Faces touching the block border come with the direction of that border.
+pub fn model_faces(shape: &BlockShape, orientation: Orientation) -> Vec<ModelFace> {
+    if *shape == BlockShape::Cross {
+        return cross_faces();
+    }
+
+    let mut faces = vec![];
+    for model_box in shape.boxes() {
+        let model_box = model_box.oriented(orientation);
+        let min = model_box.min.as_vec3() / MODEL_RESOLUTION as f32;
+        let max = model_box.max.as_vec3() / MODEL_RESOLUTION as f32;
+
+        for direction in Direction::ALL {
+            let normal = direction.offset().as_vec3();
+            let on_border = model_box.touches_border(direction);
+
+            // the face plane and the two axes spanning it
+            let (u, v) = match direction {
+                Direction::Left | Direction::Right => (Vec3::Z, Vec3::Y),
+                Direction::Down | Direction::Up => (Vec3::X, Vec3::Z),
+                Direction::Forward | Direction::Back => (Vec3::X, Vec3::Y),
+            };
+            let base = Vec3::select(normal.cmpgt(Vec3::ZERO), max, min);
+            let plane = base * normal.abs() + min * (Vec3::ONE - normal.abs());
+            let size = max - min;
+
+            let corners = [
+                plane,
+                plane + u * size,
+                plane + u * size + v * size,
+                plane + v * size,
+            ];
+            faces.push(ModelFace::new(corners, normal, on_border.then_some(direction)));
+        }
+    }
+
+    faces
+}
+
+/// Quad of a block model.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ModelFace {
+    /// Corners in block units, counter clockwise when looking at the face.
+    pub corners: [Vec3; 4],
+    pub normal: Vec3,
+    /// Border of the block the face lies on, such faces can be hidden by neighbors.
+    pub border: Option<Direction>,
+}
+
+impl ModelFace {
+    fn new(mut corners: [Vec3; 4], normal: Vec3, border: Option<Direction>) -> Self {
+        let winding = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+        if winding.dot(normal) < 0.0 {
+            corners.reverse();
+        }
+
+        Self {
+            corners,
+            normal,
+            border,
+        }
+    }
+}
+
+/// Two diagonal planes, visible from both sides.
+fn cross_faces() -> Vec<ModelFace> {
+    let diagonals = [
+        [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 1.0)],
+        [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)],
+    ];
+
+    let mut faces = vec![];
+    for [start, end] in diagonals {
+        let corners = [start, end, end + Vec3::Y, start + Vec3::Y];
+        let normal = (end - start).cross(Vec3::Y).normalize();
+        faces.push(ModelFace::new(corners, normal, None));
+        faces.push(ModelFace::new(corners, -normal, None));
+    }
+
+    faces
+}
+
+/// Whether the boxes cover the whole face of the block in the given direction.
+fn covers_face(boxes: &[ModelBox], direction: Direction) -> bool {
+    let mut covered = [[false; MODEL_RESOLUTION as usize]; MODEL_RESOLUTION as usize];
+
+    for model_box in boxes {
+        if !model_box.touches_border(direction) {
+            continue;
+        }
+
+        let (_, min_u, min_v) = direction.sample_to_plane(model_box.min);
+        let (_, max_u, max_v) = direction.sample_to_plane(model_box.max);
+        for row in covered.iter_mut().take(max_u as usize).skip(min_u as usize) {
+            for cell in row.iter_mut().take(max_v as usize).skip(min_v as usize) {
+                *cell = true;
+            }
+        }
+    }
+
+    covered.iter().flatten().all(|&cell| cell)
+}
+
+/// Rotates a position around the block center, taking north to `orientation`.
+fn rotate(pos: IVec3, orientation: Orientation) -> IVec3 {
+    let IVec3 { x, y, z } = pos;
+    match orientation {
+        Orientation::North => pos,
+        Orientation::East => IVec3::new(z, y, -x),
+        Orientation::South => IVec3::new(-x, y, -z),
+        Orientation::West => IVec3::new(-z, y, x),
+        Orientation::Up => IVec3::new(x, z, -y),
+        Orientation::Down => IVec3::new(x, -z, y),
+    }
+}
@@ -0,0 +1,97 @@
+use crate::block::{Axis, Block};
+use crate::block_registry::BlockRegistry;
+use bevy::math::IVec3;
+
+/// Box of blocks detached from the world, positions go from zero to `size` exclusive.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BlockVolume {
+    size: IVec3,
+    blocks: Vec<Block>,
+}
+
+impl BlockVolume {
+    /// Volume of the given size filled with air. Sizes holding more than `i32::MAX` blocks
+    /// panic, sizes read from files should be checked first.
+    pub fn new(size: IVec3) -> Self {
+        let size = size.max(IVec3::ZERO);
+        let len = size.x.checked_mul(size.y).and_then(|area| area.checked_mul(size.z));
+        Self {
+            size,
+            blocks: vec![Block(0); len.expect("volume too large") as usize],
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.size
+    }
+
+    #[inline]
+    fn index(&self, pos: IVec3) -> Option<usize> {
+        if pos.cmplt(IVec3::ZERO).any() || pos.cmpge(self.size).any() {
+            return None;
+        }
+
+        Some((pos.x + pos.z * self.size.x + pos.y * self.size.x * self.size.z) as usize)
+    }
+
+    pub fn set(&mut self, pos: IVec3, block: Block) {
+        if let Some(index) = self.index(pos) {
+            self.blocks[index] = block;
+        }
+    }
+
+    /// Every position of the volume with its block.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, Block)> + '_ {
+        let size = self.size;
+        (0..size.y).flat_map(move |y| {
+            (0..size.z).flat_map(move |z| {
+                (0..size.x).map(move |x| {
+                    let pos = IVec3::new(x, y, z);
+                    (pos, self.blocks[(x + z * size.x + y * size.x * size.z) as usize])
+                })
+            })
+        })
+    }
+
+    /// Turns the volume by quarter turns counter-clockwise seen from above, together with the
+    /// oriented blocks in it. A quarter turn takes north to east, the minimum corner stays at zero.
+    pub fn rotate_y(&self, quarter_turns: i32, registry: &BlockRegistry) -> BlockVolume {
+        let turns = quarter_turns.rem_euclid(4);
+        let size = self.size;
+        let mut rotated = BlockVolume::new(if turns % 2 == 1 {
+            IVec3::new(size.z, size.y, size.x)
+        } else {
+            size
+        });
+
+        for (pos, block) in self.iter() {
+            let IVec3 { x, y, z } = pos;
+            let target = match turns {
+                1 => IVec3::new(z, y, size.x - 1 - x),
+                2 => IVec3::new(size.x - 1 - x, y, size.z - 1 - z),
+                3 => IVec3::new(size.z - 1 - z, y, x),
+                _ => pos,
+            };
+            rotated.set(target, registry.rotate_y(block, turns));
+        }
+
+        rotated
+    }
+
+    /// Mirrors the volume along an axis, together with the oriented blocks in it.
+    pub fn mirror(&self, axis: Axis, registry: &BlockRegistry) -> BlockVolume {
+        let mut mirrored = BlockVolume::new(self.size);
+
+        for (pos, block) in self.iter() {
+            let mut target = pos;
+            match axis {
+                Axis::X => target.x = self.size.x - 1 - pos.x,
+                Axis::Y => target.y = self.size.y - 1 - pos.y,
+                Axis::Z => target.z = self.size.z - 1 - pos.z,
+            }
+            mirrored.set(target, registry.mirror(block, axis));
+        }
+
+        mirrored
+    }
+}
@@ -0,0 +1,85 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+/// Cursor over the bytes of a binary file, shared by the `.vox`, NBT and schematic readers.
+#[derive(Debug, Clone)]
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+/// The bytes ended before the value being read, converted into the error of each format.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Truncated;
+
+impl Display for Truncated {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "truncated data")
+    }
+}
+
+impl From<Truncated> for io::Error {
+    fn from(truncated: Truncated) -> Self {
+        io::Error::new(io::ErrorKind::UnexpectedEof, truncated.to_string())
+    }
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, at: 0 }
+    }
+
+    /// Next `len` bytes, the reader doesn't move when there are fewer left.
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], Truncated> {
+        if len > self.remaining() {
+            return Err(Truncated);
+        }
+        let taken = &self.bytes[self.at..self.at + len];
+        self.at += len;
+        Ok(taken)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], Truncated> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Truncated> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u32_le(&mut self) -> Result<u32, Truncated> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32_be(&mut self) -> Result<i32, Truncated> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    /// How many bytes are left.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.at
+    }
+
+    /// Bytes which haven't been read yet.
+    pub fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.at..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_until_the_bytes_run_out() {
+        let mut reader = ByteReader::new(&[1, 2, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(reader.u8(), Ok(1));
+        assert_eq!(reader.u32_le(), Ok(2));
+        assert_eq!(reader.i32_be(), Err(Truncated));
+        assert_eq!(reader.remaining(), 3);
+
+        assert_eq!(reader.take(usize::MAX), Err(Truncated));
+        assert_eq!(reader.take(2), Ok(&[0, 0][..]));
+        assert_eq!(reader.rest(), &[1]);
+    }
+}
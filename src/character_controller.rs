@@ -0,0 +1,202 @@
+use crate::block_interaction::PlayerCollider;
+use crate::collision::{Aabb, Sweep};
+use crate::world::World;
+use bevy::app::{App, Plugin, Update};
+use bevy::input::ButtonInput;
+use bevy::math::{BVec3, Vec2, Vec3, Vec3Swizzles};
+use bevy::prelude::{Component, IntoScheduleConfigs, KeyCode, Query, Res, ResMut, Time, Transform, With};
+use bevy::window::{CursorGrabMode, CursorOptions, PrimaryWindow};
+use bevy_flycam::MovementSettings;
+
+/// Walks an entity with a [`PlayerCollider`] through the world, colliding with solid blocks.
+///
+/// While flying the entity is moved by `bevy_flycam` instead, which keeps turning the camera in
+/// both modes.
+#[derive(Component, Debug, Clone)]
+#[require(PlayerCollider)]
+pub struct CharacterController {
+    pub flying: bool,
+    pub velocity: Vec3,
+    /// Walking speed in blocks per second.
+    pub speed: f32,
+    /// Flying speed given to `bevy_flycam` when switching to flying.
+    pub fly_speed: f32,
+    /// Vertical speed of a jump.
+    pub jump_speed: f32,
+    pub gravity: f32,
+    pub max_fall_speed: f32,
+    /// Highest ledge walked onto without jumping.
+    pub step_height: f32,
+    /// Whether the entity stands on a block, updated every frame.
+    pub grounded: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            flying: true,
+            velocity: Vec3::ZERO,
+            speed: 5.0,
+            fly_speed: 12.0,
+            jump_speed: 9.0,
+            gravity: 32.0,
+            max_fall_speed: 60.0,
+            step_height: 1.0,
+            grounded: false,
+        }
+    }
+}
+
+pub struct CharacterControllerPlugin;
+
+impl Plugin for CharacterControllerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (Self::toggle_flying, Self::walk).chain());
+    }
+}
+
+impl CharacterControllerPlugin {
+    /// Switches between walking and flying when `F` is pressed.
+    pub fn toggle_flying(
+        keys: Res<ButtonInput<KeyCode>>,
+        mut controllers: Query<&mut CharacterController>,
+        mut fly_cam: ResMut<MovementSettings>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyF) {
+            return;
+        }
+
+        for mut controller in controllers.iter_mut() {
+            controller.flying = !controller.flying;
+            controller.velocity = Vec3::ZERO;
+            fly_cam.speed = if controller.flying { controller.fly_speed } else { 0.0 };
+        }
+    }
+
+    /// Moves walking controllers with `WASD` relative to where they look and jumps with `Space`,
+    /// applying gravity and colliding with the world. Input is only read while the cursor is grabbed,
+    /// like `bevy_flycam` does.
+    pub fn walk(
+        keys: Res<ButtonInput<KeyCode>>,
+        time: Res<Time>,
+        cursor: Query<&CursorOptions, With<PrimaryWindow>>,
+        world: Res<World>,
+        mut controllers: Query<(&mut CharacterController, &PlayerCollider, &mut Transform)>,
+    ) {
+        let grabbed = cursor.single().is_ok_and(|cursor| cursor.grab_mode != CursorGrabMode::None);
+        let delta = time.delta_secs();
+
+        for (mut controller, collider, mut transform) in controllers.iter_mut() {
+            if controller.flying {
+                continue;
+            }
+
+            let forward = transform.forward().xz().normalize_or_zero();
+            let right = transform.right().xz().normalize_or_zero();
+            let mut input = Vec2::ZERO;
+            if grabbed {
+                for (key, direction) in [
+                    (KeyCode::KeyW, forward),
+                    (KeyCode::KeyS, -forward),
+                    (KeyCode::KeyD, right),
+                    (KeyCode::KeyA, -right),
+                ] {
+                    if keys.pressed(key) {
+                        input += direction;
+                    }
+                }
+            }
+            let horizontal = input.normalize_or_zero() * controller.speed;
+            controller.velocity.x = horizontal.x;
+            controller.velocity.z = horizontal.y;
+
+            if grabbed && controller.grounded && keys.pressed(KeyCode::Space) {
+                controller.velocity.y = controller.jump_speed;
+            }
+            controller.velocity.y = (controller.velocity.y - controller.gravity * delta).max(-controller.max_fall_speed);
+
+            let aabb = collider.aabb(transform.translation);
+            let step_height = if controller.grounded { controller.step_height } else { 0.0 };
+            let sweep = move_and_step(&world, aabb, controller.velocity * delta, step_height);
+
+            transform.translation += sweep.motion;
+            controller.grounded = sweep.blocked.y && controller.velocity.y < 0.0;
+            if sweep.blocked.y {
+                controller.velocity.y = 0.0;
+            }
+        }
+    }
+}
+
+/// Sweeps the box through the world, and when a wall stops it horizontally tries again from up to
+/// `step_height` higher, keeping whichever gets further.
+pub fn move_and_step(world: &World, aabb: Aabb, motion: Vec3, step_height: f32) -> Sweep {
+    let sweep = world.sweep_aabb(aabb, motion);
+    if step_height <= 0.0 || !(sweep.blocked.x || sweep.blocked.z) {
+        return sweep;
+    }
+
+    let up = world.sweep_aabb(aabb, Vec3::Y * step_height);
+    let raised = aabb.translated(up.motion);
+    let across = world.sweep_aabb(raised, motion.with_y(0.0));
+    let down = world.sweep_aabb(raised.translated(across.motion), Vec3::Y * (motion.y.min(0.0) - up.motion.y));
+
+    if across.motion.xz().length_squared() <= sweep.motion.xz().length_squared() {
+        return sweep;
+    }
+
+    Sweep {
+        motion: up.motion + across.motion + down.motion,
+        blocked: BVec3::new(across.blocked.x, down.blocked.y, across.blocked.z),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, Orientation};
+    use crate::block_registry::BlockRegistry;
+    use bevy::math::IVec3;
+
+    fn block(name: &str) -> Block {
+        BlockRegistry::default().by_name(name).unwrap()
+    }
+
+    /// Stone floor at y 0 with the given blocks on top.
+    fn floor_with(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> World {
+        let floor = (-16..16).flat_map(|x| (-16..16).map(move |z| (IVec3::new(x, 0, z), block("voxel:stone"))));
+        World::with_blocks(1, floor.chain(blocks))
+    }
+
+    fn player_at(feet: Vec3) -> Aabb {
+        Aabb::new(feet - Vec3::new(0.3, 0.0, 0.3), feet + Vec3::new(0.3, 1.8, 0.3))
+    }
+
+    /// Walking 1.5 blocks along x against a little gravity.
+    const WALK: Vec3 = Vec3::new(1.5, -0.1, 0.0);
+
+    #[test]
+    fn steps_onto_slabs_and_blocks() {
+        let slab = block("voxel:stone_slab").with_facing(Orientation::Down);
+        let world = floor_with([(IVec3::new(2, 1, 0), slab)]);
+        let sweep = move_and_step(&world, player_at(Vec3::new(0.5, 1.0, 0.5)), WALK, 1.0);
+        assert!(sweep.motion.abs_diff_eq(Vec3::new(1.5, 0.5, 0.0), 1e-4), "moved {}", sweep.motion);
+        assert_eq!(sweep.blocked, BVec3::new(false, true, false));
+
+        let world = floor_with([(IVec3::new(2, 1, 0), block("voxel:stone"))]);
+        let sweep = move_and_step(&world, player_at(Vec3::new(0.5, 1.0, 0.5)), WALK, 1.0);
+        assert!(sweep.motion.abs_diff_eq(Vec3::new(1.5, 1.0, 0.0), 1e-4), "moved {}", sweep.motion);
+    }
+
+    #[test]
+    fn doesnt_step_over_walls_higher_than_the_step() {
+        let world = floor_with([(IVec3::new(2, 1, 0), block("voxel:stone")), (IVec3::new(2, 2, 0), block("voxel:stone"))]);
+        let sweep = move_and_step(&world, player_at(Vec3::new(0.5, 1.0, 0.5)), WALK, 1.0);
+        assert!(sweep.motion.abs_diff_eq(Vec3::new(1.2, 0.0, 0.0), 1e-4), "moved {}", sweep.motion);
+        assert_eq!(sweep.blocked, BVec3::new(true, true, false));
+
+        let world = floor_with([(IVec3::new(2, 1, 0), block("voxel:stone"))]);
+        let sweep = move_and_step(&world, player_at(Vec3::new(0.5, 1.0, 0.5)), WALK, 0.0);
+        assert!(sweep.motion.abs_diff_eq(Vec3::new(1.2, 0.0, 0.0), 1e-4), "moved {}", sweep.motion);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::block::Block;
+use crate::lighting::Light;
 use bevy::math::IVec2;
 use bevy::prelude::{Component, IVec3};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 pub const CHUNK_SIZE: i32 = 16;
@@ -16,15 +18,25 @@ pub const PADDED_CHUNK_SIZE3_USIZE: usize = 5832;
 #[derive(Component, Copy, Clone, Eq, PartialEq, Debug, Default, Hash)]
 pub struct ChunkPos(pub IVec2);
 
-#[derive(Default, Debug, Ord, PartialOrd, Eq, PartialEq)]
+impl ChunkPos {
+    /// Chunk a block position belongs to.
+    pub fn of_block(pos: IVec3) -> Self {
+        Self(IVec2::new(pos.x.div_euclid(CHUNK_SIZE), pos.z.div_euclid(CHUNK_SIZE)))
+    }
+}
+
+#[derive(Default, Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct ChunkSection {
     blocks: Vec<Block>,
+    /// Packed light per block, see [`crate::lighting::LightChannel`].
+    light: Vec<Light>,
 }
 
 impl ChunkSection {
     pub fn new() -> Self {
         Self {
             blocks: vec![Block(0); (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
+            light: vec![0; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
         }
     }
 
@@ -39,6 +51,24 @@ impl ChunkSection {
         empty
     }
 
+    /// Section made of the given blocks, ordered like [`ChunkSection::blocks`], without light.
+    /// `None` when there aren't exactly as many blocks as the section holds.
+    pub fn from_blocks(blocks: Vec<Block>) -> Option<Self> {
+        if blocks.len() != CHUNK_SIZE3 as usize {
+            return None;
+        }
+
+        Some(Self {
+            blocks,
+            light: vec![0; CHUNK_SIZE3 as usize],
+        })
+    }
+
+    /// Every block of the section, x first, then y, then z.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
     pub fn get_by_xyz(&self, x: i32, y: i32, z: i32) -> Option<Block> {
         if x < 0 || x >= CHUNK_SIZE || y < 0 || y >= CHUNK_SIZE || z < 0 || z >= CHUNK_SIZE {
             return None;
@@ -54,16 +84,54 @@ impl ChunkSection {
 
         self.blocks[(x + (y * CHUNK_SIZE) + (z * CHUNK_SIZE2)) as usize] = id;
     }
+
+    pub fn get_light_by_xyz(&self, x: i32, y: i32, z: i32) -> Option<Light> {
+        Self::index(x, y, z).map(|index| self.light[index])
+    }
+
+    pub fn set_light_by_xyz(&mut self, x: i32, y: i32, z: i32, light: Light) {
+        if let Some(index) = Self::index(x, y, z) {
+            self.light[index] = light;
+        }
+    }
+
+    fn index(x: i32, y: i32, z: i32) -> Option<usize> {
+        let range = 0..CHUNK_SIZE;
+        if !range.contains(&x) || !range.contains(&y) || !range.contains(&z) {
+            return None;
+        }
+
+        Some((x + (y * CHUNK_SIZE) + (z * CHUNK_SIZE2)) as usize)
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct Chunk {
     pub sections: Vec<Arc<RwLock<ChunkSection>>>,
+    /// Whether blocks changed since the chunk was loaded, generated or last saved.
+    dirty: AtomicBool,
 }
 
 impl Chunk {
     pub fn new() -> Self {
-        Self { sections: vec![] }
+        Self {
+            sections: vec![],
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether blocks changed since the chunk was loaded, generated or last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire)
+    }
+
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Release);
+    }
+
+    /// Clears the dirty flag right before the chunk gets saved, returning whether it was set.
+    pub fn take_dirty(&self) -> bool {
+        self.dirty.swap(false, Ordering::AcqRel)
     }
 
     pub fn generate(&mut self) {
@@ -92,6 +160,11 @@ impl Chunk {
         }
     }
 
+    /// Height of the chunk in blocks.
+    pub fn height(&self) -> i32 {
+        self.sections.len() as i32 * CHUNK_SIZE
+    }
+
     pub fn coords_by_index(mut index: i32) -> IVec3 {
         let z = index / CHUNK_SIZE2;
         index -= z * CHUNK_SIZE2;
@@ -122,6 +195,7 @@ impl Chunk {
         let y_in_section = y % CHUNK_SIZE;
         let mut guard = self.sections[section as usize].write().unwrap();
         guard.set_by_xyz(x, y_in_section, z, id);
+        self.mark_dirty();
     }
 
     pub fn get(&self, coords: IVec3) -> Option<Block> {
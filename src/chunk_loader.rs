@@ -34,8 +34,7 @@ impl ChunkLoaderPlugin {
         mut world: ResMut<World>,
     ) {
         for (mut loader, transform) in loaders {
-            let current_chunk =
-                (transform.translation() / Vec3::splat(CHUNK_SIZE as f32)).as_ivec3();
+            let current_chunk = chunk_coords(transform.translation());
             //info!("{:?}", current_chunk);
             //info!("{:?}", transform.translation());
             let previous_chunk = loader.previous_chunk;
@@ -66,6 +65,11 @@ impl ChunkLoaderPlugin {
     }
 }
 
+/// Chunk coordinates a world position falls into.
+pub fn chunk_coords(translation: Vec3) -> IVec3 {
+    (translation / Vec3::splat(CHUNK_SIZE as f32)).floor().as_ivec3()
+}
+
 fn get_chunks_in_radius(center: ChunkPos, radius: i32) -> Vec<ChunkPos> {
     let mut chunks = vec![];
     let radius_sq = radius * radius;
@@ -82,3 +86,14 @@ fn get_chunks_in_radius(center: ChunkPos, radius: i32) -> Vec<ChunkPos> {
     chunks.sort_by_key(|pos| pos.0.distance_squared(center.0));
     chunks
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_coords_round_down() {
+        assert_eq!(chunk_coords(Vec3::new(0.5, 15.9, 16.0)), IVec3::new(0, 0, 1));
+        assert_eq!(chunk_coords(Vec3::new(-0.5, -16.0, -16.1)), IVec3::new(-1, -1, -2));
+    }
+}
@@ -1,18 +1,27 @@
 ﻿use bevy::math::Vec4;
-use bevy::mesh::{Mesh, MeshVertexBufferLayout, MeshVertexBufferLayoutRef};
+use bevy::mesh::{Mesh, MeshVertexAttribute, MeshVertexBufferLayout, MeshVertexBufferLayoutRef};
 use bevy::pbr::{Material, MaterialExtension, MaterialPipeline, MaterialPipelineKey, MeshPipelineKey};
 use bevy::pbr::wireframe::WireframeConfig;
 use bevy::prelude::{AlphaMode, Asset, Reflect, Res, TypePath};
-use bevy::render::render_resource::{AsBindGroup, PolygonMode, RenderPipelineDescriptor, SpecializedMeshPipelineError};
+use bevy::render::render_resource::{AsBindGroup, PolygonMode, RenderPipelineDescriptor, SpecializedMeshPipelineError, VertexFormat};
 use bevy::shader::ShaderRef;
 
+/// Coloured block light and sky light of a vertex, see [`crate::lighting::vertex_light`].
+pub const ATTRIBUTE_VOXEL_LIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_VoxelLight", 988_540_917, VertexFormat::Float32x4);
+
 #[derive(Asset, AsBindGroup, Reflect, Debug, Clone, Default)]
 pub struct ChunkMaterial {
-    //#[uniform(0)]
-    pub color: Vec4
+    #[uniform(0)]
+    pub color: Vec4,
+    pub alpha_mode: AlphaMode,
 }
 
 impl Material for ChunkMaterial {
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
     fn vertex_shader() -> ShaderRef {
         "shaders/testt.wgsl".into()
     }
@@ -28,4 +37,19 @@ impl Material for ChunkMaterial {
     fn prepass_fragment_shader() -> ShaderRef {
         "shaders/chunk_prepass.wgsl".into()
     }
-}
\ No newline at end of file
+
+    fn specialize(
+        _pipeline: &MaterialPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.0.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(1),
+            ATTRIBUTE_VOXEL_LIGHT.at_shader_location(2),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        Ok(())
+    }
+}
@@ -1,16 +1,50 @@
+use crate::block::Block;
+
 #[derive(Clone, Debug, Default, PartialOrd, PartialEq)]
 pub struct ChunkSectionMesh {
     pub vertices: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
     pub normals: Vec<[f32; 3]>,
+    /// `[red, green, blue, sky]` light of every vertex, see [`crate::lighting::vertex_light`].
+    pub lights: Vec<[f32; 4]>,
+    /// Block of every vertex, empty for meshers which don't keep track of it.
+    pub blocks: Vec<Block>,
 }
 
 impl ChunkSectionMesh {
-    pub fn new(vertices: Vec<[f32; 3]>, normals: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+    pub fn new(
+        vertices: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        lights: Vec<[f32; 4]>,
+        indices: Vec<u32>,
+    ) -> Self {
         Self {
             vertices,
             indices,
             normals,
+            lights,
+            blocks: vec![],
+        }
+    }
+
+    pub fn with_blocks(mut self, blocks: Vec<Block>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+}
+
+#[cfg(test)]
+impl ChunkSectionMesh {
+    /// Area covered by the quads of the mesh for every face normal and block.
+    pub(crate) fn face_area(&self) -> std::collections::HashMap<([i32; 3], Block), f32> {
+        use bevy::math::Vec3;
+
+        let mut area = std::collections::HashMap::new();
+        for (index, quad) in self.vertices.chunks_exact(4).enumerate() {
+            let [a, b, _, d] = [quad[0], quad[1], quad[2], quad[3]].map(Vec3::from_array);
+            let normal = Vec3::from_array(self.normals[index * 4]).round().as_ivec3().to_array();
+            *area.entry((normal, self.blocks[index * 4])).or_default() += (b - a).cross(d - a).length();
         }
+        area
     }
 }
@@ -0,0 +1,135 @@
+use crate::chunk::{ChunkPos, CHUNK_SIZE};
+use crate::chunk_loader::ChunkLoader;
+use crate::world::WorldPlugin;
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::math::{Vec2, Vec3Swizzles};
+use bevy::prelude::{GlobalTransform, IntoScheduleConfigs, Query, Res, ResMut, Resource, With};
+
+#[derive(Resource, Debug, Clone)]
+pub struct ChunkSchedulerSettings {
+    /// Maximum amount of chunks generating at the same time.
+    pub max_data_tasks: usize,
+    /// Maximum amount of sections meshing at the same time.
+    pub max_mesh_tasks: usize,
+    /// Maximum amount of section meshes uploaded and spawned per frame.
+    pub max_mesh_uploads: usize,
+    /// How many chunks closer a chunk straight ahead of a loader is treated compared to one behind it.
+    pub view_bias: f32,
+}
+
+impl Default for ChunkSchedulerSettings {
+    fn default() -> Self {
+        Self {
+            max_data_tasks: 32,
+            max_mesh_tasks: 64,
+            max_mesh_uploads: 16,
+            view_bias: 4.0,
+        }
+    }
+}
+
+/// Position and horizontal view direction of every loader, in chunk space.
+#[derive(Resource, Debug, Default)]
+pub struct ChunkPriorities {
+    loaders: Vec<(Vec2, Vec2)>,
+    view_bias: f32,
+}
+
+impl ChunkPriorities {
+    /// Lower values are loaded and meshed first.
+    pub fn priority(&self, position: ChunkPos) -> f32 {
+        let center = position.0.as_vec2() + Vec2::splat(0.5);
+
+        self.loaders
+            .iter()
+            .map(|&(loader, forward)| {
+                let offset = center - loader;
+                let distance = offset.length();
+                let facing = if distance > 0.0 {
+                    forward.dot(offset / distance)
+                } else {
+                    1.0
+                };
+
+                distance - facing * self.view_bias * 0.5
+            })
+            .reduce(f32::min)
+            .unwrap_or(0.0)
+    }
+
+    /// Sorts chunk positions from most to least urgent.
+    pub fn sort(&self, positions: &mut [ChunkPos]) {
+        positions.sort_by(|&a, &b| self.priority(a).total_cmp(&self.priority(b)));
+    }
+}
+
+pub struct ChunkSchedulerPlugin;
+
+impl Plugin for ChunkSchedulerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkSchedulerSettings>()
+            .init_resource::<ChunkPriorities>()
+            .add_systems(
+                PostUpdate,
+                Self::update_priorities
+                    .before(WorldPlugin::start_data_tasks)
+                    .before(WorldPlugin::start_mesh_tasks),
+            );
+    }
+}
+
+impl ChunkSchedulerPlugin {
+    pub fn update_priorities(
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        settings: Res<ChunkSchedulerSettings>,
+        mut priorities: ResMut<ChunkPriorities>,
+    ) {
+        priorities.view_bias = settings.view_bias;
+        priorities.loaders = loaders
+            .iter()
+            .map(|transform| {
+                let position = transform.translation().xz() / CHUNK_SIZE as f32;
+                let forward = transform.forward().xz().normalize_or_zero();
+                (position, forward)
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::IVec2;
+
+    fn sorted(priorities: &ChunkPriorities, positions: &[IVec2]) -> Vec<IVec2> {
+        let mut positions: Vec<_> = positions.iter().map(|&pos| ChunkPos(pos)).collect();
+        priorities.sort(&mut positions);
+        positions.into_iter().map(|pos| pos.0).collect()
+    }
+
+    #[test]
+    fn near_and_ahead_chunks_come_first() {
+        // looking towards +x from the middle of chunk (0, 0)
+        let mut priorities = ChunkPriorities {
+            loaders: vec![(Vec2::splat(0.5), Vec2::X)],
+            view_bias: 0.0,
+        };
+        let ring = [IVec2::new(-3, 0), IVec2::new(2, 0), IVec2::new(0, 1), IVec2::new(0, -5), IVec2::ZERO];
+        assert_eq!(
+            sorted(&priorities, &ring),
+            [IVec2::ZERO, IVec2::new(0, 1), IVec2::new(2, 0), IVec2::new(-3, 0), IVec2::new(0, -5)]
+        );
+
+        // at the same distance, ahead beats the sides, which beat behind
+        priorities.view_bias = ChunkSchedulerSettings::default().view_bias;
+        let around = [IVec2::new(-3, 0), IVec2::new(0, 3), IVec2::new(3, 0)];
+        assert_eq!(sorted(&priorities, &around), [IVec2::new(3, 0), IVec2::new(0, 3), IVec2::new(-3, 0)]);
+        // a chunk right behind is treated as `view_bias` chunks further than one ahead
+        assert!(priorities.priority(ChunkPos(IVec2::new(-2, 0))) > priorities.priority(ChunkPos(IVec2::new(5, 0))));
+        assert!(priorities.priority(ChunkPos(IVec2::new(-2, 0))) < priorities.priority(ChunkPos(IVec2::new(7, 0))));
+
+        // the nearest loader counts
+        priorities.loaders.push((Vec2::new(-9.5, 0.5), Vec2::NEG_X));
+        assert!(priorities.priority(ChunkPos(IVec2::new(-12, 0))) < priorities.priority(ChunkPos(IVec2::new(4, 0))));
+    }
+}
@@ -0,0 +1,415 @@
+use crate::block::{Axis, Block};
+use crate::block_interaction::TargetedBlock;
+use crate::block_registry::BlockRegistry;
+use crate::block_volume::BlockVolume;
+use crate::byte_reader::ByteReader;
+use crate::edit_history::EditHistory;
+use crate::region::{RegionStorage, invalid_data, write_atomically};
+use crate::world::World;
+use crate::world_edit::WorldEdit;
+use bevy::app::{App, Plugin, Update};
+use bevy::input::ButtonInput;
+use bevy::log::{error, info, warn};
+use bevy::math::IVec3;
+use bevy::prelude::{DetectChanges, IntoScheduleConfigs, KeyCode, Res, ResMut, Resource, not};
+use bevy_inspector_egui::bevy_egui::input::egui_wants_any_keyboard_input;
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::Path;
+use std::fs;
+
+const SCHEMATIC_MAGIC: &[u8; 4] = b"VXSC";
+const SCHEMATIC_VERSION: u32 = 1;
+
+/// Name of the schematic the clipboard is saved to in the world directory.
+const CLIPBOARD_FILE: &str = "clipboard.vxschem";
+
+/// Schematics with more blocks than this are treated as corrupt rather than allocated.
+const MAX_SCHEMATIC_BLOCKS: usize = 64 * 1024 * 1024;
+
+/// Corners of the box copied by [`ClipboardPlugin`], both included.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Selection {
+    pub first: Option<IVec3>,
+    pub second: Option<IVec3>,
+}
+
+impl Selection {
+    /// Minimum and maximum corner once both are selected.
+    pub fn bounds(&self) -> Option<(IVec3, IVec3)> {
+        let (first, second) = (self.first?, self.second?);
+        Some((first.min(second), first.max(second)))
+    }
+}
+
+/// How [`Clipboard::paste`] transforms the clipboard.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PasteOptions {
+    /// Quarter turns counter-clockwise seen from above, applied after mirroring.
+    pub quarter_turns: i32,
+    pub mirror: Option<Axis>,
+    /// Whether air leaves the blocks pasted over as they are.
+    pub skip_air: bool,
+}
+
+/// Blocks copied out of the world, with the minimum corner of the copied box at zero.
+///
+/// Clipboards are saved as schematic files: a header with the size and the names of the blocks
+/// used, followed by the compressed blocks with their ids replaced by indices into those names.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Clipboard {
+    pub volume: BlockVolume,
+}
+
+impl Clipboard {
+    /// Copies a box of the world, blocks which aren't loaded are copied as air.
+    pub fn copy(world: &World, a: IVec3, b: IVec3) -> Self {
+        Self {
+            volume: world.read_box(a, b),
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.volume.size()
+    }
+
+    /// Blocks of the clipboard mirrored and turned, oriented blocks included.
+    pub fn transformed(&self, options: &PasteOptions, registry: &BlockRegistry) -> BlockVolume {
+        let mirrored = match options.mirror {
+            Some(axis) => self.volume.mirror(axis, registry),
+            None => self.volume.clone(),
+        };
+        mirrored.rotate_y(options.quarter_turns, registry)
+    }
+
+    /// Edit pasting the transformed clipboard with its minimum corner at `to`.
+    pub fn paste(&self, to: IVec3, options: &PasteOptions, registry: &BlockRegistry) -> WorldEdit {
+        let mut edit = WorldEdit::new();
+        edit.paste(&self.transformed(options, registry), to, options.skip_air);
+        edit
+    }
+
+    pub fn to_bytes(&self, registry: &BlockRegistry) -> Vec<u8> {
+        let mut palette: Vec<u16> = vec![];
+        let mut indices = HashMap::new();
+        let mut raw = vec![];
+        for (_, block) in self.volume.iter() {
+            let index = *indices.entry(block.id()).or_insert_with(|| {
+                palette.push(block.id());
+                palette.len() as u16 - 1
+            });
+            let mut stored = block;
+            stored.set_id(index);
+            raw.extend_from_slice(&stored.0.to_le_bytes());
+        }
+
+        let mut bytes = vec![];
+        bytes.extend_from_slice(SCHEMATIC_MAGIC);
+        bytes.extend_from_slice(&SCHEMATIC_VERSION.to_le_bytes());
+        for size in self.size().to_array() {
+            bytes.extend_from_slice(&size.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+        for id in palette {
+            let name = &registry.get(Block::from_id(id)).name;
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        bytes.extend_from_slice(&compress_to_vec(raw.as_slice(), CompressionLevel::Fastest));
+        bytes
+    }
+
+    /// Reads a schematic, blocks which aren't registered become air.
+    pub fn from_bytes(bytes: &[u8], registry: &BlockRegistry) -> io::Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != SCHEMATIC_MAGIC {
+            return Err(invalid_data("not a schematic"));
+        }
+        let version = reader.u32_le()?;
+        if version != SCHEMATIC_VERSION {
+            return Err(invalid_data(format!("unsupported schematic version {version}")));
+        }
+
+        let size = IVec3::new(reader.u32_le()? as i32, reader.u32_le()? as i32, reader.u32_le()? as i32);
+        let len = size
+            .to_array()
+            .into_iter()
+            .try_fold(1usize, |len, axis| len.checked_mul(usize::try_from(axis).ok()?))
+            .filter(|&len| len <= MAX_SCHEMATIC_BLOCKS)
+            .ok_or_else(|| invalid_data(format!("schematic size {size}")))?;
+        let mut palette = vec![];
+        for _ in 0..reader.u32_le()? {
+            let len = reader.u32_le()? as usize;
+            let name = std::str::from_utf8(reader.take(len)?).map_err(invalid_data)?;
+            palette.push(registry.by_name(name).map_or_else(
+                || {
+                    warn!("Block `{name}` isn't registered, replacing it with air");
+                    0
+                },
+                |block| block.id(),
+            ));
+        }
+
+        let mut raw = vec![];
+        // one byte more than expected is enough to tell the size is wrong
+        StreamingDecoder::new(reader.rest())
+            .map_err(invalid_data)?
+            .take(len as u64 * 2 + 1)
+            .read_to_end(&mut raw)?;
+
+        if raw.len() != len * 2 {
+            return Err(invalid_data("schematic of the wrong size"));
+        }
+        let mut volume = BlockVolume::new(size);
+        let positions: Vec<_> = volume.iter().map(|(pos, _)| pos).collect();
+        for (pos, bytes) in positions.into_iter().zip(raw.chunks_exact(2)) {
+            let mut block = Block(u16::from_le_bytes([bytes[0], bytes[1]]));
+            let id = *palette.get(block.id() as usize).ok_or_else(|| invalid_data("block outside of the palette"))?;
+            block.set_id(id);
+            // blocks which became air lose their variant and orientation
+            volume.set(pos, if id == 0 { Block(0) } else { block });
+        }
+
+        Ok(Self { volume })
+    }
+
+    /// Writes the clipboard to a schematic file, replacing it at once.
+    pub fn save(&self, path: &Path, registry: &BlockRegistry) -> io::Result<()> {
+        write_atomically(path, &self.to_bytes(registry))
+    }
+
+    pub fn load(path: &Path, registry: &BlockRegistry) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?, registry)
+    }
+}
+
+pub struct ClipboardPlugin;
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .init_resource::<Clipboard>()
+            .init_resource::<PasteOptions>()
+            .add_systems(
+                Update,
+                (Self::select_corners, Self::copy_and_paste, Self::change_paste_options, Self::save_and_load)
+                    .run_if(not(egui_wants_any_keyboard_input)),
+            );
+    }
+}
+
+impl ClipboardPlugin {
+    /// Selects the targeted block as the first corner with `1` and as the second one with `2`.
+    pub fn select_corners(keys: Res<ButtonInput<KeyCode>>, targeted: Res<TargetedBlock>, mut selection: ResMut<Selection>) {
+        let Some(hit) = targeted.0 else {
+            return;
+        };
+
+        if keys.just_pressed(KeyCode::Digit1) {
+            selection.first = Some(hit.pos);
+            info!("First corner at {}", hit.pos);
+        }
+        if keys.just_pressed(KeyCode::Digit2) {
+            selection.second = Some(hit.pos);
+            info!("Second corner at {}", hit.pos);
+        }
+    }
+
+    /// Copies the selection with `Ctrl+C`, and pastes against the targeted face with `Ctrl+V`,
+    /// which can be undone.
+    pub fn copy_and_paste(
+        keys: Res<ButtonInput<KeyCode>>,
+        selection: Res<Selection>,
+        targeted: Res<TargetedBlock>,
+        options: Res<PasteOptions>,
+        mut clipboard: ResMut<Clipboard>,
+        mut history: ResMut<EditHistory>,
+        mut world: ResMut<World>,
+    ) {
+        if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            return;
+        }
+
+        if keys.just_pressed(KeyCode::KeyC) {
+            let Some((min, max)) = selection.bounds() else {
+                warn!("Select two corners before copying");
+                return;
+            };
+            *clipboard = Clipboard::copy(&world, min, max);
+            info!("Copied the blocks from {min} to {max}");
+        }
+
+        if keys.just_pressed(KeyCode::KeyV) {
+            let Some(hit) = targeted.0 else {
+                return;
+            };
+            let edit = clipboard.paste(hit.pos + hit.normal.offset(), &options, &world.registry);
+            match history.apply(&mut world, &edit) {
+                Ok(changed) => info!("Paste changed {changed} blocks"),
+                Err(err) => error!("Failed to paste: {err}"),
+            }
+        }
+    }
+
+    /// Turns the paste a quarter counter-clockwise with `R`, cycles mirroring along x, z, y or
+    /// nothing with `M` and toggles skipping air with `H`.
+    pub fn change_paste_options(keys: Res<ButtonInput<KeyCode>>, mut options: ResMut<PasteOptions>) {
+        if keys.just_pressed(KeyCode::KeyR) {
+            options.quarter_turns = (options.quarter_turns + 1).rem_euclid(4);
+        }
+        if keys.just_pressed(KeyCode::KeyM) {
+            options.mirror = match options.mirror {
+                None => Some(Axis::X),
+                Some(Axis::X) => Some(Axis::Z),
+                Some(Axis::Z) => Some(Axis::Y),
+                Some(Axis::Y) => None,
+            };
+        }
+        if keys.just_pressed(KeyCode::KeyH) {
+            options.skip_air = !options.skip_air;
+        }
+        if options.is_changed() && !options.is_added() {
+            info!("Pasting with {:?}", *options);
+        }
+    }
+
+    /// Saves the clipboard to the world directory with `F5` and loads it back with `F6`.
+    pub fn save_and_load(
+        keys: Res<ButtonInput<KeyCode>>,
+        storage: Res<RegionStorage>,
+        world: Res<World>,
+        mut clipboard: ResMut<Clipboard>,
+    ) {
+        let path = storage.dir().join(CLIPBOARD_FILE);
+
+        if keys.just_pressed(KeyCode::F5) {
+            match clipboard.save(&path, &world.registry) {
+                Ok(()) => info!("Saved the clipboard to {}", path.display()),
+                Err(err) => error!("Failed to save the clipboard to {}: {err}", path.display()),
+            }
+        }
+        if keys.just_pressed(KeyCode::F6) {
+            match Clipboard::load(&path, &world.registry) {
+                Ok(loaded) => {
+                    *clipboard = loaded;
+                    info!("Loaded a clipboard of size {} from {}", clipboard.size(), path.display());
+                }
+                Err(err) => error!("Failed to load the clipboard from {}: {err}", path.display()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Orientation;
+
+    fn block(world: &World, name: &str) -> Block {
+        world.registry.by_name(name).unwrap()
+    }
+
+    /// World with a row of stairs facing east, stone and air to copy, over a floor of glass.
+    fn row_world() -> World {
+        let registry = BlockRegistry::default();
+        let stairs = registry.by_name("voxel:stone_stairs").unwrap().with_facing(Orientation::East);
+        let stone = registry.by_name("voxel:stone").unwrap();
+        let glass = registry.by_name("voxel:glass").unwrap();
+        let floor = (0..3).map(|x| (IVec3::new(x, 4, 0), glass));
+        World::with_blocks(1, [(IVec3::ZERO, stairs), (IVec3::X, stone)].into_iter().chain(floor))
+    }
+
+    fn stairs_of(volume: &BlockVolume, world: &World) -> (IVec3, Orientation) {
+        let stairs = block(world, "voxel:stone_stairs");
+        let (pos, block) = volume.iter().find(|(_, block)| block.id() == stairs.id()).unwrap();
+        (pos, block.facing())
+    }
+
+    #[test]
+    fn schematics_keep_blocks_and_orientations() {
+        let world = row_world();
+        let clipboard = Clipboard::copy(&world, IVec3::new(2, 4, 0), IVec3::ZERO);
+        assert_eq!(clipboard.size(), IVec3::new(3, 5, 1));
+
+        let bytes = clipboard.to_bytes(&world.registry);
+        assert_eq!(Clipboard::from_bytes(&bytes, &world.registry).unwrap(), clipboard);
+
+        let path = std::env::temp_dir().join(format!("voxel-clipboard-{}.vxschem", std::process::id()));
+        clipboard.save(&path, &world.registry).unwrap();
+        assert_eq!(Clipboard::load(&path, &world.registry).unwrap(), clipboard);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn unknown_blocks_load_as_air() {
+        let world = row_world();
+        let clipboard = Clipboard::copy(&world, IVec3::new(2, 4, 0), IVec3::ZERO);
+        let mut bytes = clipboard.to_bytes(&world.registry);
+        let name = bytes.windows(11).position(|name| name == b"voxel:glass").unwrap();
+        bytes[name..name + 11].copy_from_slice(b"voxel:gless");
+
+        let loaded = Clipboard::from_bytes(&bytes, &world.registry).unwrap();
+        for ((pos, block), (_, loaded)) in clipboard.volume.iter().zip(loaded.volume.iter()) {
+            let expected = if pos.y == 4 { Block(0) } else { block };
+            assert_eq!(loaded, expected, "at {pos}");
+        }
+    }
+
+    #[test]
+    fn rejects_broken_schematics() {
+        let world = row_world();
+        let bytes = Clipboard::copy(&world, IVec3::ZERO, IVec3::X).to_bytes(&world.registry);
+        for len in [0, 4, 12, 20, bytes.len() - 1] {
+            assert!(Clipboard::from_bytes(&bytes[..len], &world.registry).is_err(), "cut at {len}");
+        }
+
+        for size in [[u32::MAX, 1, 1], [1 << 16, 1 << 16, 1 << 16], [1 << 12, 1 << 12, 1 << 12], [3, 1, 1]] {
+            let mut resized = bytes.clone();
+            for (axis, size) in size.into_iter().enumerate() {
+                resized[8 + axis * 4..12 + axis * 4].copy_from_slice(&size.to_le_bytes());
+            }
+            assert!(Clipboard::from_bytes(&resized, &world.registry).is_err(), "size {size:?}");
+        }
+    }
+
+    #[test]
+    fn turns_and_mirrors_oriented_blocks() {
+        let world = row_world();
+        let clipboard = Clipboard::copy(&world, IVec3::ZERO, IVec3::new(2, 0, 0));
+        let transformed = |quarter_turns, mirror| {
+            let options = PasteOptions { quarter_turns, mirror, skip_air: false };
+            clipboard.transformed(&options, &world.registry)
+        };
+
+        let turned = transformed(1, None);
+        assert_eq!(turned.size(), IVec3::new(1, 1, 3));
+        assert_eq!(stairs_of(&turned, &world), (IVec3::new(0, 0, 2), Orientation::South));
+        let stone = block(&world, "voxel:stone");
+        assert!(turned.iter().any(|(pos, turned)| pos == IVec3::new(0, 0, 1) && turned == stone));
+
+        assert_eq!(stairs_of(&transformed(2, None), &world), (IVec3::new(2, 0, 0), Orientation::West));
+        assert_eq!(stairs_of(&transformed(0, Some(Axis::X)), &world), (IVec3::new(2, 0, 0), Orientation::West));
+        assert_eq!(stairs_of(&transformed(0, Some(Axis::Z)), &world), (IVec3::ZERO, Orientation::East));
+        // mirrored first, then turned
+        assert_eq!(stairs_of(&transformed(1, Some(Axis::X)), &world), (IVec3::ZERO, Orientation::North));
+    }
+
+    #[test]
+    fn pastes_air_unless_skipped() {
+        let mut world = row_world();
+        let clipboard = Clipboard::copy(&world, IVec3::ZERO, IVec3::new(2, 0, 0));
+        let glass = block(&world, "voxel:glass");
+        let stone = block(&world, "voxel:stone");
+        let row = |world: &World| (0..3).map(|x| world.get_block(IVec3::new(x, 4, 0)).unwrap().id()).collect::<Vec<_>>();
+        let stairs = block(&world, "voxel:stone_stairs").id();
+
+        let skipping = PasteOptions { skip_air: true, ..PasteOptions::default() };
+        world.apply_edit(&clipboard.paste(IVec3::new(0, 4, 0), &skipping, &world.registry)).unwrap();
+        assert_eq!(row(&world), [stairs, stone.id(), glass.id()]);
+
+        world.apply_edit(&clipboard.paste(IVec3::new(0, 4, 0), &PasteOptions::default(), &world.registry)).unwrap();
+        assert_eq!(row(&world), [stairs, stone.id(), 0]);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::block_shape::{MODEL_RESOLUTION, ModelBox};
+use bevy::math::{BVec3, IVec3, Vec3};
+
+/// Gap below which boxes count as touching rather than overlapping, absorbs float error of
+/// boxes resting on each other.
+const EPSILON: f32 = 1e-4;
+
+/// Axis aligned box in world space.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Box of a block model box at a block position.
+    pub fn of_model_box(model_box: ModelBox, pos: IVec3) -> Self {
+        let scale = 1.0 / MODEL_RESOLUTION as f32;
+        Self::new(
+            pos.as_vec3() + model_box.min.as_vec3() * scale,
+            pos.as_vec3() + model_box.max.as_vec3() * scale,
+        )
+    }
+
+    pub fn translated(&self, offset: Vec3) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Smallest box containing the box before and after moving by `motion`.
+    pub fn swept(&self, motion: Vec3) -> Self {
+        let moved = self.translated(motion);
+        Self::new(self.min.min(moved.min), self.max.max(moved.max))
+    }
+
+    /// Whether the boxes overlap, boxes which only touch don't.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (self.min + EPSILON).cmplt(other.max).all() && (self.max - EPSILON).cmpgt(other.min).all()
+    }
+
+    /// Minimum and maximum block positions, both inclusive, of the blocks the box overlaps.
+    pub fn block_range(&self) -> (IVec3, IVec3) {
+        let min = (self.min + EPSILON).floor().as_ivec3();
+        let max = (self.max - EPSILON).ceil().as_ivec3() - 1;
+        (min, min.max(max))
+    }
+}
+
+/// Movement of a box stopped by the boxes in its way, see [`World::sweep_aabb`](crate::world::World::sweep_aabb).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sweep {
+    /// How far the box can move, the full motion when nothing is in the way.
+    pub motion: Vec3,
+    /// Axes along which the motion was cut short.
+    pub blocked: BVec3,
+}
+
+/// Moves `aabb` by `motion` one axis at a time, y first, then x and z, stopping every axis where
+/// the box would start overlapping a box returned by `boxes_at` for the blocks along the way.
+/// Boxes the moving box already overlaps don't stop it, so it can get out of them.
+pub fn sweep(aabb: Aabb, motion: Vec3, boxes_at: impl Fn(IVec3) -> Vec<Aabb>) -> Sweep {
+    let mut aabb = aabb;
+    let mut moved = Vec3::ZERO;
+    let mut blocked = BVec3::FALSE;
+
+    for axis in [1, 0, 2] {
+        let wanted = motion[axis];
+        if wanted == 0.0 {
+            continue;
+        }
+
+        let mut step = Vec3::ZERO;
+        step[axis] = wanted;
+        let (min, max) = aabb.swept(step).block_range();
+
+        let mut allowed = wanted;
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    for other in boxes_at(IVec3::new(x, y, z)) {
+                        allowed = clip_axis(&aabb, &other, axis, allowed);
+                    }
+                }
+            }
+        }
+
+        step[axis] = allowed;
+        aabb = aabb.translated(step);
+        moved[axis] = allowed;
+        blocked.set(axis, allowed != wanted);
+    }
+
+    Sweep { motion: moved, blocked }
+}
+
+/// Shortens a movement of `aabb` along an axis so it stops at `other`.
+fn clip_axis(aabb: &Aabb, other: &Aabb, axis: usize, motion: f32) -> f32 {
+    let overlaps_across = (0..3)
+        .filter(|&across| across != axis)
+        .all(|across| aabb.min[across] + EPSILON < other.max[across] && aabb.max[across] - EPSILON > other.min[across]);
+    if !overlaps_across {
+        return motion;
+    }
+
+    if motion > 0.0 && other.min[axis] >= aabb.max[axis] - EPSILON {
+        motion.min((other.min[axis] - aabb.max[axis]).max(0.0))
+    } else if motion < 0.0 && other.max[axis] <= aabb.min[axis] + EPSILON {
+        motion.max((other.max[axis] - aabb.min[axis]).min(0.0))
+    } else {
+        motion
+    }
+}
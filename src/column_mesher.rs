@@ -0,0 +1,179 @@
+use crate::block::Block;
+use crate::block_registry::BlockRegistry;
+use crate::chunk::CHUNK_SIZE;
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::{build_section_mesh, greedy_face_quads, padded_section};
+use crate::lighting::Light;
+use crate::lod::LodLevel;
+use crate::quad::{Direction, FaceQuad, GreedyQuad};
+use crate::mesher::SectionMeshes;
+use crate::model_mesher::mesh_models;
+use crate::section_neighbors::SectionNeighbors;
+use crate::translucent_mesher::mesh_translucent;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// (direction, block, light, axis_pos, x, w) of side quads that can be stacked on top of each other
+type SpanKey = (Direction, Block, Light, i32, u32, u32);
+
+/// Input of a column mesh for one section, either its quads from a previous run or its data.
+/// Only dirty sections get their model and translucent meshes rebuilt.
+pub enum ColumnSection {
+    Cached(Arc<Vec<FaceQuad>>),
+    Dirty(SectionNeighbors),
+}
+
+pub struct ColumnMesh {
+    /// Quads of every section, to be cached for the next run.
+    pub sections: Vec<(i32, Arc<Vec<FaceQuad>>)>,
+    /// Mesh of the whole column relative to the bottom of the chunk.
+    pub mesh: Option<ChunkSectionMesh>,
+    /// Model and translucent meshes of the dirty sections, their opaque cubes are in `mesh`.
+    pub dirty: Vec<(i32, SectionMeshes)>,
+}
+
+/// Greedy meshes a whole chunk column into a single mesh.
+///
+/// Sections are meshed on their own, only the dirty ones get meshed again. Their side quads are
+/// then merged vertically with the quads of the section above whenever they line up.
+pub fn mesh_column(sections: Vec<(i32, ColumnSection)>, registry: &BlockRegistry) -> ColumnMesh {
+    let mut dirty = vec![];
+    let sections: Vec<_> = sections
+        .into_iter()
+        .map(|(section_y, section)| {
+            let quads = match section {
+                ColumnSection::Cached(quads) => quads,
+                ColumnSection::Dirty(neighbors) => {
+                    dirty.push((
+                        section_y,
+                        SectionMeshes {
+                            opaque: None,
+                            models: mesh_models(&neighbors, LodLevel::FULL, registry),
+                            translucent: mesh_translucent(&neighbors, LodLevel::FULL, registry),
+                        },
+                    ));
+                    Arc::new(
+                        padded_section(&neighbors, LodLevel::FULL, registry)
+                            .map(|padded| greedy_face_quads(&padded))
+                            .unwrap_or_default(),
+                    )
+                }
+            };
+            (section_y, quads)
+        })
+        .collect();
+
+    let mut faces = vec![];
+    let mut side_spans: HashMap<SpanKey, Vec<(u32, u32)>> = HashMap::new();
+
+    for (section_y, quads) in sections.iter() {
+        let base = (section_y * CHUNK_SIZE) as u32;
+        for face in quads.iter() {
+            match face.direction {
+                Direction::Up | Direction::Down => faces.push(FaceQuad {
+                    axis_pos: face.axis_pos + base as i32,
+                    ..*face
+                }),
+                _ => side_spans
+                    .entry((face.direction, face.block, face.light, face.axis_pos, face.quad.x, face.quad.w))
+                    .or_default()
+                    .push((face.quad.y + base, face.quad.h)),
+            }
+        }
+    }
+
+    for ((direction, block, light, axis_pos, x, w), mut spans) in side_spans {
+        spans.sort_unstable();
+
+        let mut current: Option<(u32, u32)> = None;
+        for (y, h) in spans {
+            current = match current {
+                Some((start, height)) if start + height == y => Some((start, height + h)),
+                Some((start, height)) => {
+                    faces.push(side_face((direction, block, light, axis_pos, x, w), start, height));
+                    Some((y, h))
+                }
+                None => Some((y, h)),
+            };
+        }
+
+        if let Some((start, height)) = current {
+            faces.push(side_face((direction, block, light, axis_pos, x, w), start, height));
+        }
+    }
+
+    let mesh = (!faces.is_empty()).then(|| {
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut lights = vec![];
+        let mut blocks = vec![];
+        for face in faces {
+            face.append_vertices(&mut vertices, &mut normals, &mut lights);
+            blocks.extend([face.block; 4]);
+        }
+        build_section_mesh(vertices, normals, lights, 1).with_blocks(blocks)
+    });
+
+    ColumnMesh {
+        sections,
+        mesh,
+        dirty,
+    }
+}
+
+fn side_face(
+    (direction, block, light, axis_pos, x, w): SpanKey,
+    y: u32,
+    h: u32,
+) -> FaceQuad {
+    FaceQuad {
+        direction,
+        block,
+        axis_pos,
+        light,
+        quad: GreedyQuad { x, y, w, h },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkPos;
+    use crate::greedy_chunk_render_plugin::GreedyMesher;
+    use crate::mesher::Mesher;
+    use crate::world::World;
+    use bevy::math::{IVec2, IVec3};
+
+    #[test]
+    fn columns_cover_the_faces_of_their_sections() {
+        // pillars and scattered blocks crossing the border between the two sections
+        let blocks = (0..32).flat_map(|y| {
+            (-4..20).flat_map(move |x| {
+                (-4..20).filter_map(move |z| {
+                    let pillar = (x + z) % 5 == 0 && y < 24;
+                    let scattered = (x * 7 + y * 13 + z * 3) % 11 < 3;
+                    (pillar || scattered).then(|| (IVec3::new(x, y, z), Block::from_id(1 + (x + y).rem_euclid(2) as u16)))
+                })
+            })
+        });
+        let world = World::with_blocks(2, blocks);
+        let registry = &world.registry;
+        let neighbors = |section_y: i32| SectionNeighbors::new(&world.loaded_chunks, ChunkPos(IVec2::ZERO), section_y as usize);
+
+        let column = mesh_column((0..2).map(|section_y| (section_y, ColumnSection::Dirty(neighbors(section_y)))).collect(), registry);
+        let column = column.mesh.unwrap().face_area();
+
+        let mut sections = HashMap::new();
+        for section_y in 0..2 {
+            let mesh = GreedyMesher.mesh(&neighbors(section_y), LodLevel::FULL, registry).unwrap();
+            for (key, area) in mesh.face_area() {
+                *sections.entry(key).or_insert(0.0) += area;
+            }
+        }
+
+        assert_eq!(column.len(), sections.len());
+        for (key, area) in sections {
+            assert!((column[&key] - area).abs() < 1e-3, "{key:?}");
+        }
+    }
+}
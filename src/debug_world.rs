@@ -1,10 +1,28 @@
-use crate::world::World;
+use crate::block::Block;
+use crate::chunk_loader::ChunkLoader;
+use crate::mesher::{ChunkMesher, MeshLayout, MesherKind};
+use crate::region::RegionStorage;
+use crate::vox::{VoxFile, VoxMapping, VoxModel};
+use crate::block_interaction::TargetedBlock;
+use crate::edit_history::{EditDelta, EditHistory};
+use crate::pathfinding::{PathFollower, PathRequest, PathSettings};
+use crate::voxel_body::VoxelBody;
+use crate::world::{Explosion, World};
+use crate::world_edit::WorldEdit;
 use bevy::app::{App, Plugin, Update};
-use bevy::prelude::{IntoScheduleConfigs, ReflectResource, Time};
+use bevy::input::ButtonInput;
+use bevy::log::{error, info};
+use bevy::asset::Assets;
+use bevy::math::{IVec3, Vec3};
+use bevy::math::primitives::Cuboid;
+use bevy::mesh::{Mesh, Mesh3d};
+use bevy::pbr::{MeshMaterial3d, StandardMaterial};
+use bevy::prelude::{Color, Commands, DetectChanges, MessageReader, Transform, GlobalTransform, IntoScheduleConfigs, KeyCode, Query, ReflectResource, Time, With};
 use bevy::prelude::{Reflect, Res, ResMut, Resource};
 use bevy::time::common_conditions::on_timer;
 use bevy_inspector_egui::prelude::*;
 use bevy_inspector_egui::quick::ResourceInspectorPlugin;
+use std::fs;
 use std::time::Duration;
 
 #[derive(Resource, Default, Reflect, InspectorOptions)]
@@ -17,6 +35,7 @@ pub struct WorldStats {
     pub mesh_to_unload: usize,
     pub active_data_tasks: usize,
     pub active_mesh_tasks: usize,
+    pub pending_mesh_uploads: usize,
 
     #[inspector(min = 0, max = 100)]
     pub sample_size: usize,
@@ -26,6 +45,49 @@ pub struct WorldStats {
     pub mesh_load_queue: Vec<(i32, i32)>,
 }
 
+/// Mesher picked in the inspector, handed to the [`ChunkMesher`] by
+/// [`DebugWorldPlugin::select_mesher`].
+#[derive(Resource, Default, Reflect, InspectorOptions, Copy, Clone, Eq, PartialEq)]
+#[reflect(Resource, InspectorOptions)]
+pub struct DebugMesher {
+    pub mesher: MesherKind,
+}
+
+/// Block placed by [`DebugWorldPlugin::toggle_light_block`].
+#[derive(Resource, Default, Reflect, InspectorOptions, Copy, Clone, Eq, PartialEq)]
+#[reflect(Resource, InspectorOptions)]
+pub enum DebugLightBlock {
+    #[default]
+    Glowstone,
+    Lava,
+    Crystal,
+}
+
+impl DebugLightBlock {
+    fn name(&self) -> &'static str {
+        match self {
+            DebugLightBlock::Glowstone => "voxel:glowstone",
+            DebugLightBlock::Lava => "voxel:lava",
+            DebugLightBlock::Crystal => "voxel:crystal",
+        }
+    }
+}
+
+/// Shape built around the targeted block by [`DebugWorldPlugin::apply_edit_shape`].
+#[derive(Resource, Default, Reflect, InspectorOptions, Copy, Clone, Eq, PartialEq)]
+#[reflect(Resource, InspectorOptions)]
+pub enum DebugEditShape {
+    #[default]
+    Fill,
+    Hollow,
+    Sphere,
+    Cylinder,
+    /// Turns stone into glass.
+    Replace,
+    /// Copies the blocks around the target on top of them.
+    Copy,
+}
+
 pub struct DebugWorldPlugin;
 
 impl Plugin for DebugWorldPlugin {
@@ -35,14 +97,273 @@ impl Plugin for DebugWorldPlugin {
             .register_type::<WorldStats>()
             .add_plugins(ResourceInspectorPlugin::<WorldStats>::default())
             .add_plugins(ResourceInspectorPlugin::<Time>::default())
+            .init_resource::<DebugMesher>()
+            .register_type::<DebugMesher>()
+            .add_plugins(ResourceInspectorPlugin::<DebugMesher>::default())
+            .init_resource::<DebugLightBlock>()
+            .register_type::<DebugLightBlock>()
+            .add_plugins(ResourceInspectorPlugin::<DebugLightBlock>::default())
+            .init_resource::<DebugEditShape>()
+            .register_type::<DebugEditShape>()
+            .add_plugins(ResourceInspectorPlugin::<DebugEditShape>::default())
+            .register_type::<MeshLayout>()
+            .add_plugins(ResourceInspectorPlugin::<MeshLayout>::default())
             .add_systems(
                 Update,
-                Self::update_world_stats.run_if(on_timer(Duration::from_secs_f32(0.5))),
+                (
+                    Self::update_world_stats.run_if(on_timer(Duration::from_secs_f32(0.5))),
+                    Self::select_mesher,
+                    Self::toggle_light_block,
+                    Self::import_vox,
+                    Self::export_vox,
+                    Self::throw_body,
+                    Self::send_walker,
+                    Self::explode_targeted_block,
+                    Self::record_explosions,
+                    Self::spawn_debris,
+                    Self::apply_edit_shape,
+                ),
             );
     }
 }
 
 impl DebugWorldPlugin {
+    pub fn select_mesher(selected: Res<DebugMesher>, mut mesher: ResMut<ChunkMesher>) {
+        if selected.is_changed() && mesher.kind() != selected.mesher {
+            mesher.set_kind(selected.mesher);
+        }
+    }
+
+    /// Places the selected light block at every loader when `L` is pressed, or removes the one
+    /// already there.
+    pub fn toggle_light_block(
+        keys: Res<ButtonInput<KeyCode>>,
+        selected: Res<DebugLightBlock>,
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        mut world: ResMut<World>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyL) {
+            return;
+        }
+
+        let light_block = world.registry.by_name(selected.name()).unwrap_or(Block(0));
+        for transform in loaders.iter() {
+            let pos = transform.translation().floor().as_ivec3();
+            let block = match world.get_block(pos) {
+                Some(block) if block == light_block => Block(0),
+                Some(_) => light_block,
+                None => continue,
+            };
+            world.set_block(pos, block);
+        }
+    }
+
+    /// Places `assets/structures/tower.vox` at every loader when `I` is pressed.
+    pub fn import_vox(
+        keys: Res<ButtonInput<KeyCode>>,
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        mut world: ResMut<World>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyI) {
+            return;
+        }
+
+        let read = || -> Result<_, Box<dyn std::error::Error>> {
+            let file = VoxFile::parse(&fs::read("assets/structures/tower.vox")?)?;
+            let mapping = VoxMapping::parse(&fs::read_to_string("assets/structures/tower.mapping")?, &world.registry)?;
+            let model = file.models.first().ok_or("the file has no model")?;
+            Ok(model.to_volume(&mapping))
+        };
+        let volume = match read() {
+            Ok(volume) => volume,
+            Err(err) => {
+                error!("Failed to import tower.vox: {err}");
+                return;
+            }
+        };
+
+        for transform in loaders.iter() {
+            let pos = transform.translation().floor().as_ivec3();
+            world.write_volume(pos - IVec3::new(volume.size().x / 2, 0, volume.size().z / 2), &volume);
+        }
+    }
+
+    /// Exports the 32 blocks around the first loader to `export.vox` in the world directory when
+    /// `O` is pressed.
+    pub fn export_vox(
+        keys: Res<ButtonInput<KeyCode>>,
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        world: Res<World>,
+        storage: Res<RegionStorage>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyO) {
+            return;
+        }
+        let Some(transform) = loaders.iter().next() else {
+            return;
+        };
+
+        let center = transform.translation().floor().as_ivec3();
+        let volume = world.read_volume(center - IVec3::splat(16), IVec3::splat(32));
+        let path = storage.dir().join("export.vox");
+        let write = || -> Result<_, Box<dyn std::error::Error>> {
+            let mapping = VoxMapping::parse(&fs::read_to_string("assets/structures/tower.mapping")?, &world.registry)?;
+            let file = VoxFile {
+                models: vec![VoxModel::from_volume(&volume, &mapping)?],
+                palette: Some(mapping.palette()),
+            };
+            fs::write(&path, file.to_bytes())?;
+            Ok(())
+        };
+
+        match write() {
+            Ok(()) => info!("Exported the blocks around {center} to {}", path.display()),
+            Err(err) => error!("Failed to export {}: {err}", path.display()),
+        }
+    }
+
+    /// Throws a small box with a [`VoxelBody`] from every loader when `G` is pressed.
+    pub fn throw_body(
+        keys: Res<ButtonInput<KeyCode>>,
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyG) {
+            return;
+        }
+
+        let mesh = meshes.add(Cuboid::from_length(0.5));
+        let material = materials.add(StandardMaterial::default());
+        for transform in loaders.iter() {
+            let mut body = VoxelBody::new(Vec3::splat(0.25));
+            body.velocity = transform.forward() * 8.0 + Vec3::Y * 4.0;
+            commands.spawn((
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::from_translation(transform.translation() + transform.forward() * 1.5),
+                body,
+            ));
+        }
+    }
+
+    /// Drops a walker on the ground below the first loader when `N` is pressed, which finds its
+    /// way to the top of the targeted block.
+    pub fn send_walker(
+        keys: Res<ButtonInput<KeyCode>>,
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        targeted: Res<TargetedBlock>,
+        world: Res<World>,
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyN) {
+            return;
+        }
+        let (Some(transform), Some(goal)) = (loaders.iter().next(), targeted.0) else {
+            return;
+        };
+        let Some(ground) = world.raycast(transform.translation(), Vec3::NEG_Y, 64.0) else {
+            return;
+        };
+
+        let start = ground.pos + IVec3::Y;
+        let half_size = Vec3::new(0.3, 0.9, 0.3);
+        commands.spawn((
+            Mesh3d(meshes.add(Cuboid::from_size(half_size * 2.0))),
+            MeshMaterial3d(materials.add(StandardMaterial::default())),
+            Transform::from_translation(start.as_vec3() + Vec3::new(0.5, half_size.y, 0.5)),
+            VoxelBody::new(half_size),
+            PathRequest {
+                start,
+                goal: goal.pos + IVec3::Y,
+                settings: PathSettings::default(),
+            },
+            PathFollower::default(),
+        ));
+    }
+
+    /// Blows up the targeted block when `X` is pressed.
+    pub fn explode_targeted_block(keys: Res<ButtonInput<KeyCode>>, targeted: Res<TargetedBlock>, mut world: ResMut<World>) {
+        if !keys.just_pressed(KeyCode::KeyX) {
+            return;
+        }
+        if let Some(hit) = targeted.0 {
+            world.explode(hit.pos.as_vec3() + 0.5, 4.0);
+        }
+    }
+
+    /// Records every explosion in the edit history, so it can be undone.
+    pub fn record_explosions(mut explosions: MessageReader<Explosion>, mut history: ResMut<EditHistory>) {
+        for explosion in explosions.read() {
+            history.record(EditDelta::new(explosion.destroyed.clone()));
+        }
+    }
+
+    /// Throws a few boxes colored like the destroyed blocks out of every explosion.
+    pub fn spawn_debris(
+        mut explosions: MessageReader<Explosion>,
+        world: Res<World>,
+        mut commands: Commands,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut materials: ResMut<Assets<StandardMaterial>>,
+    ) {
+        for explosion in explosions.read() {
+            info!("Explosion at {} destroyed {} blocks", explosion.center, explosion.destroyed.len());
+
+            let mesh = meshes.add(Cuboid::from_length(0.25));
+            for &(pos, block) in explosion.destroyed.iter().step_by(8).take(32) {
+                let [r, g, b, a] = world.registry.get(block).color;
+                let center = pos.as_vec3() + 0.5;
+                let mut body = VoxelBody::new(Vec3::splat(0.125));
+                body.velocity = (center - explosion.center).normalize_or_zero() * explosion.power * 2.0 + Vec3::Y * 4.0;
+                commands.spawn((
+                    Mesh3d(mesh.clone()),
+                    MeshMaterial3d(materials.add(StandardMaterial::from_color(Color::srgba_u8(r, g, b, a)))),
+                    Transform::from_translation(center),
+                    body,
+                ));
+            }
+        }
+    }
+
+    /// Builds the selected shape out of stone around the targeted block when `B` is pressed, which
+    /// can be undone.
+    pub fn apply_edit_shape(
+        keys: Res<ButtonInput<KeyCode>>,
+        shape: Res<DebugEditShape>,
+        targeted: Res<TargetedBlock>,
+        mut world: ResMut<World>,
+        mut history: ResMut<EditHistory>,
+    ) {
+        if !keys.just_pressed(KeyCode::KeyB) {
+            return;
+        }
+        let Some(hit) = targeted.0 else {
+            return;
+        };
+
+        let stone = world.registry.by_name("voxel:stone").unwrap_or(Block(1));
+        let glass = world.registry.by_name("voxel:glass").unwrap_or(Block(0));
+        let (min, max) = (hit.pos - 2, hit.pos + 2);
+        let mut edit = WorldEdit::new();
+        match *shape {
+            DebugEditShape::Fill => edit.fill(min, max, stone),
+            DebugEditShape::Hollow => edit.hollow(min, max, stone),
+            DebugEditShape::Sphere => edit.sphere(hit.pos, 3.5, stone),
+            DebugEditShape::Cylinder => edit.cylinder(hit.pos + IVec3::Y, 2.5, 5, stone),
+            DebugEditShape::Replace => edit.replace(&world, min, max, |block| block == stone, glass),
+            DebugEditShape::Copy => edit.copy(&world, min, max, min + IVec3::Y * 5),
+        };
+
+        match history.apply(&mut world, &edit) {
+            Ok(changed) => info!("Edit changed {changed} blocks"),
+            Err(err) => error!("Failed to apply the edit: {err}"),
+        }
+    }
+
     pub fn update_world_stats(world: Res<World>, mut stats: ResMut<WorldStats>) {
         stats.loaded_chunks = world.loaded_chunks.len();
         stats.data_to_load = world.chunks_data_to_load.len();
@@ -51,6 +372,7 @@ impl DebugWorldPlugin {
         stats.mesh_to_unload = world.chunks_mesh_to_unload.len();
         stats.active_data_tasks = world.data_tasks.len();
         stats.active_mesh_tasks = world.mesh_tasks.len();
+        stats.pending_mesh_uploads = world.mesh_uploads.len();
 
         if stats.sample_size == 0 {
             stats.sample_size = 10;
@@ -0,0 +1,394 @@
+use crate::block::Block;
+use crate::chunk::{CHUNK_SIZE, CHUNK_SIZE2, CHUNK_SIZE3, Chunk, ChunkPos};
+use crate::world::World;
+use crate::world_edit::{self, EditError, WorldEdit};
+use bevy::app::{App, Plugin, Update};
+use bevy::input::ButtonInput;
+use bevy::log::{info, warn};
+use bevy::math::IVec3;
+use bevy::prelude::{IntoScheduleConfigs, KeyCode, Res, ResMut, Resource, not};
+use bevy_inspector_egui::bevy_egui::input::egui_wants_any_keyboard_input;
+use std::collections::{HashMap, VecDeque};
+use std::mem::size_of;
+
+/// Words of the mask of changed blocks of a section.
+const MASK_WORDS: usize = CHUNK_SIZE3 as usize / 64;
+
+/// Changed blocks of a section above which they're kept with a mask of their positions instead,
+/// as that takes less memory.
+const MASK_THRESHOLD: usize = MASK_WORDS * size_of::<u64>() / (size_of::<(IVec3, Block)>() - size_of::<Block>());
+
+/// Blocks which were there before an edit, applied to revert it. Positions are kept rather than
+/// chunks, so reverting works the same after the chunks were unloaded and loaded again. Only the
+/// blocks the edit changed are kept, so reverting leaves later changes to other blocks alone.
+#[derive(Debug, Clone, Default)]
+pub struct EditDelta {
+    blocks: Vec<(IVec3, Block)>,
+    /// Sections the edit changed a lot.
+    sections: Vec<SectionChanges>,
+}
+
+/// Changed blocks of a section as a mask of their indices into
+/// [`ChunkSection::blocks`](crate::chunk::ChunkSection::blocks), with the blocks in index order.
+#[derive(Debug, Clone)]
+struct SectionChanges {
+    chunk_pos: ChunkPos,
+    section_y: i32,
+    changed: Box<[u64; MASK_WORDS]>,
+    blocks: Vec<Block>,
+}
+
+impl SectionChanges {
+    fn new(chunk_pos: ChunkPos, section_y: i32, mut blocks: Vec<(usize, Block)>) -> Self {
+        blocks.sort_unstable_by_key(|&(index, _)| index);
+        let mut changed = Box::new([0; MASK_WORDS]);
+        for &(index, _) in &blocks {
+            changed[index / 64] |= 1 << (index % 64);
+        }
+
+        Self {
+            chunk_pos,
+            section_y,
+            changed,
+            blocks: blocks.into_iter().map(|(_, block)| block).collect(),
+        }
+    }
+
+    /// Indices of the changed blocks in ascending order.
+    fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.changed.iter().enumerate().flat_map(|(word_index, &word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(word_index * 64 + bit)
+            })
+        })
+    }
+}
+
+impl EditDelta {
+    /// Delta reverting an edit of the world, from the blocks [`World::apply_edit`] replaced.
+    pub fn new(previous: Vec<(IVec3, Block)>) -> Self {
+        let mut by_section: HashMap<_, Vec<_>> = HashMap::new();
+        for (pos, block) in previous {
+            by_section.entry(world_edit::section_of(pos)).or_default().push((pos, block));
+        }
+
+        let mut delta = Self::default();
+        for ((chunk_pos, section_y), blocks) in by_section {
+            if blocks.len() <= MASK_THRESHOLD {
+                delta.blocks.extend(blocks);
+                continue;
+            }
+
+            let origin = section_origin(chunk_pos, section_y);
+            let indexed = blocks
+                .into_iter()
+                .map(|(pos, block)| {
+                    let local = pos - origin;
+                    ((local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE2) as usize, block)
+                })
+                .collect();
+            delta.sections.push(SectionChanges::new(chunk_pos, section_y, indexed));
+        }
+
+        delta
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.sections.is_empty()
+    }
+
+    /// Approximate heap memory taken by the delta in bytes.
+    pub fn memory(&self) -> usize {
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| MASK_WORDS * size_of::<u64>() + section.blocks.len() * size_of::<Block>());
+        self.blocks.len() * size_of::<(IVec3, Block)>() + sections.sum::<usize>()
+    }
+
+    /// Edit putting the blocks of the delta back.
+    pub fn to_edit(&self) -> WorldEdit {
+        let mut edit = WorldEdit::new();
+        for section in &self.sections {
+            let origin = section_origin(section.chunk_pos, section.section_y);
+            for (index, &block) in section.indices().zip(&section.blocks) {
+                edit.set(origin + Chunk::coords_by_index(index as i32), block);
+            }
+        }
+        for &(pos, block) in &self.blocks {
+            edit.set(pos, block);
+        }
+
+        edit
+    }
+}
+
+/// World position of the first block of a section.
+fn section_origin(chunk_pos: ChunkPos, section_y: i32) -> IVec3 {
+    IVec3::new(chunk_pos.0.x, section_y, chunk_pos.0.y) * CHUNK_SIZE
+}
+
+/// Edits which can be undone and redone. Edits applied through [`EditHistory::apply`] are
+/// recorded, the oldest are forgotten once the history takes more than `max_memory` bytes.
+///
+/// Undoing requires the chunks of the edit to be loaded. Chunks unloaded in between are only
+/// reverted correctly when they were saved, generated chunks are generated again as they were.
+#[derive(Resource, Debug)]
+pub struct EditHistory {
+    undo: VecDeque<EditDelta>,
+    redo: Vec<EditDelta>,
+    pub max_memory: usize,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            max_memory: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl EditHistory {
+    /// Applies an edit and records it, clearing the edits which could be redone. Returns how
+    /// many blocks changed.
+    pub fn apply(&mut self, world: &mut World, edit: &WorldEdit) -> Result<usize, EditError> {
+        let previous = world.apply_edit(edit)?;
+        let changed = previous.len();
+        self.record(EditDelta::new(previous));
+        Ok(changed)
+    }
+
+    /// Records a change already made to the world, like an explosion, clearing the edits which
+    /// could be redone.
+    pub fn record(&mut self, delta: EditDelta) {
+        if !delta.is_empty() {
+            self.undo.push_back(delta);
+            self.redo.clear();
+            self.trim();
+        }
+    }
+
+    /// Reverts the last edit, returning `false` when there's none. The edit stays in the history
+    /// when its chunks aren't loaded.
+    pub fn undo(&mut self, world: &mut World) -> Result<bool, EditError> {
+        let Some(delta) = self.undo.back() else {
+            return Ok(false);
+        };
+
+        let previous = world.apply_edit(&delta.to_edit())?;
+        self.undo.pop_back();
+        self.redo.push(EditDelta::new(previous));
+        Ok(true)
+    }
+
+    /// Applies the last undone edit again, returning `false` when there's none.
+    pub fn redo(&mut self, world: &mut World) -> Result<bool, EditError> {
+        let Some(delta) = self.redo.last() else {
+            return Ok(false);
+        };
+
+        let previous = world.apply_edit(&delta.to_edit())?;
+        self.redo.pop();
+        self.undo.push_back(EditDelta::new(previous));
+        Ok(true)
+    }
+
+    /// Approximate heap memory taken by the history in bytes.
+    pub fn memory(&self) -> usize {
+        self.undo.iter().chain(&self.redo).map(EditDelta::memory).sum()
+    }
+
+    /// Forgets the oldest edits until the history fits in `max_memory`, keeping the last one.
+    fn trim(&mut self) {
+        while self.memory() > self.max_memory && self.undo.len() > 1 {
+            self.undo.pop_front();
+        }
+    }
+}
+
+pub struct EditHistoryPlugin;
+
+impl Plugin for EditHistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EditHistory>()
+            .add_systems(Update, Self::undo_redo.run_if(not(egui_wants_any_keyboard_input)));
+    }
+}
+
+impl EditHistoryPlugin {
+    /// Undoes with `Ctrl+Z` and redoes with `Ctrl+Y` or `Ctrl+Shift+Z`.
+    pub fn undo_redo(keys: Res<ButtonInput<KeyCode>>, mut history: ResMut<EditHistory>, mut world: ResMut<World>) {
+        if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+            return;
+        }
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+        let result = if keys.just_pressed(KeyCode::KeyY) || (shift && keys.just_pressed(KeyCode::KeyZ)) {
+            history.redo(&mut world)
+        } else if keys.just_pressed(KeyCode::KeyZ) {
+            history.undo(&mut world)
+        } else {
+            return;
+        };
+
+        match result {
+            Ok(true) => {}
+            Ok(false) => info!("Nothing to undo or redo"),
+            Err(err) => warn!("Failed to undo or redo: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::{IVec2, Vec3};
+
+    fn block(world: &World, name: &str) -> Block {
+        world.registry.by_name(name).unwrap()
+    }
+
+    fn single(pos: IVec3, block: Block) -> WorldEdit {
+        let mut edit = WorldEdit::new();
+        edit.set(pos, block);
+        edit
+    }
+
+    #[test]
+    fn undoes_and_redoes_in_order() {
+        let mut world = World::with_blocks(1, []);
+        let (stone, glass) = (block(&world, "voxel:stone"), block(&world, "voxel:glass"));
+        let (a, b) = (IVec3::new(1, 1, 1), IVec3::new(2, 1, 1));
+        let mut history = EditHistory::default();
+
+        history.apply(&mut world, &single(a, stone)).unwrap();
+        let mut second = single(a, glass);
+        second.set(b, stone);
+        assert_eq!(history.apply(&mut world, &second), Ok(2));
+
+        assert_eq!(history.undo(&mut world), Ok(true));
+        assert_eq!((world.get_block(a), world.get_block(b)), (Some(stone), Some(Block(0))));
+        assert_eq!(history.undo(&mut world), Ok(true));
+        assert_eq!(world.get_block(a), Some(Block(0)));
+        assert_eq!(history.undo(&mut world), Ok(false));
+
+        assert_eq!(history.redo(&mut world), Ok(true));
+        assert_eq!(world.get_block(a), Some(stone));
+        assert_eq!(history.redo(&mut world), Ok(true));
+        assert_eq!((world.get_block(a), world.get_block(b)), (Some(glass), Some(stone)));
+        assert_eq!(history.redo(&mut world), Ok(false));
+
+        // a new edit drops what could be redone
+        history.undo(&mut world).unwrap();
+        history.apply(&mut world, &single(b, glass)).unwrap();
+        assert_eq!(history.redo(&mut world), Ok(false));
+        assert_eq!((world.get_block(a), world.get_block(b)), (Some(stone), Some(glass)));
+    }
+
+    #[test]
+    fn undoing_leaves_other_changes_alone() {
+        let mut world = World::with_blocks(1, []);
+        let stone = block(&world, "voxel:stone");
+        let mut history = EditHistory::default();
+
+        // enough blocks of one section to keep them as a mask
+        let mut fill = WorldEdit::new();
+        fill.fill(IVec3::new(0, 0, 0), IVec3::new(7, 7, 7), stone);
+        history.apply(&mut world, &fill).unwrap();
+        assert_eq!(history.undo.back().unwrap().sections.len(), 1);
+
+        let other = IVec3::new(12, 3, 12);
+        world.set_block(other, stone);
+        history.undo(&mut world).unwrap();
+        assert_eq!(world.get_block(IVec3::new(3, 3, 3)), Some(Block(0)));
+        assert_eq!(world.get_block(other), Some(stone));
+
+        history.redo(&mut world).unwrap();
+        assert_eq!(world.get_block(IVec3::new(7, 7, 7)), Some(stone));
+    }
+
+    #[test]
+    fn explosions_can_be_undone() {
+        let stone_box = |world: &World| {
+            let mut edit = WorldEdit::new();
+            edit.fill(IVec3::new(-4, 0, -4), IVec3::new(4, 8, 4), block(world, "voxel:stone"));
+            edit
+        };
+        let mut world = World::with_blocks(1, []);
+        world.apply_edit(&stone_box(&world)).unwrap();
+        let before = world.read_volume(IVec3::new(-4, 0, -4), IVec3::splat(9));
+        let mut history = EditHistory::default();
+
+        let destroyed = world.explode(Vec3::new(0.5, 4.5, 0.5), 4.0);
+        assert!(!destroyed.is_empty());
+        assert!(destroyed.iter().all(|&pos| world.get_block(pos) == Some(Block(0))));
+        let stone = block(&world, "voxel:stone");
+        history.record(EditDelta::new(destroyed.iter().map(|&pos| (pos, stone)).collect()));
+
+        assert_eq!(history.undo(&mut world), Ok(true));
+        assert_eq!(world.read_volume(IVec3::new(-4, 0, -4), IVec3::splat(9)), before);
+    }
+
+    #[test]
+    fn forgets_the_oldest_edits_over_the_memory_cap() {
+        let mut world = World::with_blocks(1, []);
+        let stone = block(&world, "voxel:stone");
+        let mut history = EditHistory {
+            max_memory: 3 * size_of::<(IVec3, Block)>(),
+            ..EditHistory::default()
+        };
+
+        for x in 0..10 {
+            history.apply(&mut world, &single(IVec3::new(x, 1, 0), stone)).unwrap();
+            assert!(history.memory() <= history.max_memory);
+        }
+        assert_eq!(history.undo.len(), 3);
+        for _ in 0..3 {
+            assert_eq!(history.undo(&mut world), Ok(true));
+        }
+        assert_eq!(history.undo(&mut world), Ok(false));
+        assert_eq!(world.get_block(IVec3::new(6, 1, 0)), Some(stone));
+        assert_eq!(world.get_block(IVec3::new(7, 1, 0)), Some(Block(0)));
+
+        // the last edit stays even when it doesn't fit
+        history.max_memory = 0;
+        history.apply(&mut world, &single(IVec3::new(0, 2, 0), stone)).unwrap();
+        assert_eq!(history.undo(&mut world), Ok(true));
+        assert_eq!(world.get_block(IVec3::new(0, 2, 0)), Some(Block(0)));
+    }
+
+    #[test]
+    fn edits_across_chunk_borders_revert_everywhere() {
+        let mut world = World::with_blocks(1, []);
+        let glass = block(&world, "voxel:glass");
+        let mut history = EditHistory::default();
+        let (min, max) = (IVec3::new(-5, 10, -5), IVec3::new(5, 20, 5));
+
+        let mut fill = WorldEdit::new();
+        fill.fill(min, max, glass);
+        assert_eq!(history.apply(&mut world, &fill), Ok(11 * 11 * 11));
+        // four chunks, each with blocks in two sections
+        assert_eq!(history.undo.back().unwrap().sections.len(), 8);
+
+        history.undo(&mut world).unwrap();
+        assert!(world.read_volume(min, max - min + 1).iter().all(|(_, block)| block == Block(0)));
+        history.redo(&mut world).unwrap();
+        assert!(world.read_volume(min, max - min + 1).iter().all(|(_, block)| block == glass));
+
+        // edits reaching into chunks which aren't loaded change nothing
+        let mut outside = WorldEdit::new();
+        outside.fill(IVec3::new(10, 1, 0), IVec3::new(20, 1, 0), glass);
+        assert_eq!(history.apply(&mut world, &outside), Err(EditError::NotLoaded(ChunkPos(IVec2::new(1, 0)))));
+        assert_eq!(world.get_block(IVec3::new(10, 1, 0)), Some(Block(0)));
+        assert_eq!(history.undo(&mut world), Ok(true));
+        assert!(world.read_volume(min, max - min + 1).iter().all(|(_, block)| block == Block(0)));
+    }
+}
@@ -3,8 +3,13 @@ use crate::chunk::{
     ChunkSection, CHUNK_SIZE, PADDED_CHUNK_SIZE2_USIZE, PADDED_CHUNK_SIZE3_USIZE,
     PADDED_CHUNK_SIZE_USIZE,
 };
+use crate::block_registry::BlockRegistry;
+use crate::block_shape::face_bit;
 use crate::chunk_mesh::ChunkSectionMesh;
-use crate::quad::{Direction, GreedyQuad};
+use crate::lighting::{Light, SKY_LIGHT};
+use crate::lod::{downsample_padded, LodLevel};
+use crate::mesher::Mesher;
+use crate::quad::{Direction, FaceQuad, GreedyQuad};
 use crate::section_neighbors::SectionNeighbors;
 use bevy::prelude::*;
 use bevy::reflect::Array;
@@ -53,29 +58,59 @@ fn greedy_mesh_binary_plane(mut data: [u16; 16]) -> Vec<GreedyQuad> {
 }
 
 #[inline]
-fn get_block_at_section(section: &Option<Arc<RwLock<ChunkSection>>>, x: i32, y: i32, z: i32) -> Block {
+fn get_block_at_section(section: &Option<Arc<RwLock<ChunkSection>>>, x: i32, y: i32, z: i32) -> (Block, Light) {
     if section.is_none() {
-        return Block(0)
+        // missing neighbors are treated as open air
+        return (Block(0), SKY_LIGHT)
     }
 
     let up = section.as_ref().unwrap();
     let up_section = up.read().unwrap();
-    up_section.get_by_xyz(x, y, z).unwrap()
+    (up_section.get_by_xyz(x, y, z).unwrap(), up_section.get_light_by_xyz(x, y, z).unwrap())
 }
 
-pub fn generate_section_mesh(sections: SectionNeighbors) -> Option<ChunkSectionMesh> {
-    let section_data = sections.center.read().unwrap();
-    if section_data.is_empty() {
-        return None;
+#[inline]
+pub fn padded_index(x: usize, y: usize, z: usize) -> usize {
+    x + z * PADDED_CHUNK_SIZE_USIZE + y * PADDED_CHUNK_SIZE2_USIZE
+}
+
+/// Blocks and light of a section with a one block border taken from its neighbors, as 18^3 grids.
+pub struct PaddedSection {
+    pub blocks: Vec<Block>,
+    pub light: Vec<Light>,
+    /// Faces every block covers, see [`BlockRegistry::covered_faces`].
+    pub covered: Vec<u8>,
+}
+
+impl PaddedSection {
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![Block(0); PADDED_CHUNK_SIZE3_USIZE],
+            light: vec![SKY_LIGHT; PADDED_CHUNK_SIZE3_USIZE],
+            covered: vec![0; PADDED_CHUNK_SIZE3_USIZE],
+        }
     }
 
-    let mut vertices = vec![];
-    let mut normals = vec![];
+    /// Whether the block at `pos` hides the faces of its neighbor in the given direction.
+    #[inline]
+    pub fn covers(&self, pos: IVec3, face: Direction) -> bool {
+        self.covered[padded_index(pos.x as usize, pos.y as usize, pos.z as usize)] & face_bit(face) != 0
+    }
 
-    // solid voxels as binary per axis x, y, z
-    let mut solid_voxels_per_axis = vec![0u64; 3 * PADDED_CHUNK_SIZE3_USIZE];
-    // cull mask for greedy slicing based on solids on previous axis column
-    let mut voxels_face_mask = [[[0u64; PADDED_CHUNK_SIZE_USIZE]; PADDED_CHUNK_SIZE_USIZE]; 6];
+    #[inline]
+    pub fn block(&self, pos: IVec3) -> Block {
+        self.blocks[padded_index(pos.x as usize, pos.y as usize, pos.z as usize)]
+    }
+
+    #[inline]
+    pub fn light(&self, pos: IVec3) -> Light {
+        self.light[padded_index(pos.x as usize, pos.y as usize, pos.z as usize)]
+    }
+}
+
+/// Copies the section and a one block border taken from its neighbors into a padded 18^3 grid.
+fn padded_blocks(sections: &SectionNeighbors, section_data: &ChunkSection) -> PaddedSection {
+    let mut padded = PaddedSection::new();
 
     for y in 0..PADDED_CHUNK_SIZE_USIZE {
         for z in 0..PADDED_CHUNK_SIZE_USIZE {
@@ -92,7 +127,10 @@ pub fn generate_section_mesh(sections: SectionNeighbors) -> Option<ChunkSectionM
                 assert!(section_y >= 0 && section_y < CHUNK_SIZE);
                 assert!(section_z >= 0 && section_z < CHUNK_SIZE);
 
-                let block = section_data.get_by_xyz(block_x, block_y, block_z).unwrap_or_else(|| {
+                let own = section_data
+                    .get_by_xyz(block_x, block_y, block_z)
+                    .zip(section_data.get_light_by_xyz(block_x, block_y, block_z));
+                let (block, light) = own.unwrap_or_else(|| {
                     if block_y < 0 {
                         return get_block_at_section(&sections.down, section_x, section_y, section_z);
                     } else if block_y >= CHUNK_SIZE {
@@ -113,14 +151,154 @@ pub fn generate_section_mesh(sections: SectionNeighbors) -> Option<ChunkSectionM
                         return get_block_at_section(&sections.north, section_x, section_y, section_z);
                     }
 
-                    Block(0)
+                    (Block(0), SKY_LIGHT)
                 });
 
-                if block.is_solid() {
+                padded.blocks[padded_index(x, y, z)] = block;
+                padded.light[padded_index(x, y, z)] = light;
+            }
+        }
+    }
+
+    padded
+}
+
+/// Padded grids of the cubes of a section for the opaque pass, `None` when the section is empty.
+///
+/// Translucent blocks are left out as air so they don't hide the faces behind them, they get
+/// meshed on their own by [`crate::translucent_mesher::mesh_translucent`]. So are blocks with a
+/// model at full detail, which [`crate::model_mesher::mesh_models`] takes care of. Left out
+/// blocks still hide the neighbor faces they cover.
+pub fn padded_section(
+    sections: &SectionNeighbors,
+    lod: LodLevel,
+    registry: &BlockRegistry,
+) -> Option<PaddedSection> {
+    let mut padded = padded_section_all(sections, lod, registry)?;
+    for block in padded.blocks.iter_mut() {
+        if registry.get(*block).translucent || (lod.is_full() && registry.has_model(*block)) {
+            *block = Block(0);
+        }
+    }
+
+    Some(padded)
+}
+
+/// Padded grids of a section at the given level of detail, `None` when the section is empty.
+///
+/// The border comes from the neighbors in `sections`, downsampled when the section is. Missing
+/// neighbors leave an air border, so downsampled sections keep skirts against neighbors at
+/// another LOD. Downsampled blocks with a model count as cubes.
+pub fn padded_section_all(
+    sections: &SectionNeighbors,
+    lod: LodLevel,
+    registry: &BlockRegistry,
+) -> Option<PaddedSection> {
+    let section_data = sections.center.read().unwrap();
+    if section_data.is_empty() {
+        return None;
+    }
+
+    let mut padded = if lod.is_full() {
+        padded_blocks(sections, &section_data)
+    } else {
+        downsample_padded(sections, &section_data, lod)
+    };
+
+    for (covered, &block) in padded.covered.iter_mut().zip(padded.blocks.iter()) {
+        *covered = if !lod.is_full() && registry.has_model(block) && !registry.get(block).translucent {
+            u8::MAX
+        } else {
+            registry.covered_faces(block)
+        };
+    }
+
+    if !lod.is_full() {
+        // the far border of a downsampled section lies inside the meshed grid, it keeps hiding
+        // faces without getting meshed itself
+        let end = (CHUNK_SIZE / lod.scale() + 1) as usize;
+        for y in 0..=end {
+            for z in 0..=end {
+                for x in 0..=end {
+                    if x == end || y == end || z == end {
+                        padded.blocks[padded_index(x, y, z)] = Block(0);
+                    }
+                }
+            }
+        }
+    }
+
+    Some(padded)
+}
+
+/// Binary greedy mesher, merges coplanar faces of the same block into as few quads as possible.
+pub struct GreedyMesher;
+
+impl Mesher for GreedyMesher {
+    fn mesh(&self, sections: &SectionNeighbors, lod: LodLevel, registry: &BlockRegistry) -> Option<ChunkSectionMesh> {
+        generate_section_mesh(sections, lod, registry)
+    }
+}
+
+pub fn generate_section_mesh(
+    sections: &SectionNeighbors,
+    lod: LodLevel,
+    registry: &BlockRegistry,
+) -> Option<ChunkSectionMesh> {
+    let padded = padded_section(sections, lod, registry)?;
+
+    Some(mesh_padded_blocks(&padded, lod.scale()))
+}
+
+/// Greedy meshes a padded section, scaling the output vertices by `scale`.
+fn mesh_padded_blocks(padded: &PaddedSection, scale: i32) -> ChunkSectionMesh {
+    let mut vertices = vec![];
+    let mut normals = vec![];
+    let mut lights = vec![];
+    let mut blocks = vec![];
+
+    for face in greedy_face_quads(padded) {
+        face.append_vertices(&mut vertices, &mut normals, &mut lights);
+        blocks.extend([face.block; 4]);
+    }
+
+    build_section_mesh(vertices, normals, lights, scale).with_blocks(blocks)
+}
+
+/// Greedy quads of a padded section, in section local coordinates. Faces only merge when they
+/// share the block and the light in front of them.
+pub fn greedy_face_quads(padded: &PaddedSection) -> Vec<FaceQuad> {
+    let mut faces = vec![];
+
+    // solid voxels as binary per axis x, y, z
+    let mut solid_voxels_per_axis = vec![0u64; 3 * PADDED_CHUNK_SIZE3_USIZE];
+    // voxels hiding the faces of each face direction, as binary per axis
+    let mut covering_voxels_per_face = vec![0u64; 6 * PADDED_CHUNK_SIZE2_USIZE];
+    // cull mask for greedy slicing based on solids on previous axis column
+    let mut voxels_face_mask = [[[0u64; PADDED_CHUNK_SIZE_USIZE]; PADDED_CHUNK_SIZE_USIZE]; 6];
+
+    for y in 0..PADDED_CHUNK_SIZE_USIZE {
+        for z in 0..PADDED_CHUNK_SIZE_USIZE {
+            for x in 0..PADDED_CHUNK_SIZE_USIZE {
+                if padded.blocks[padded_index(x, y, z)].is_solid() {
                     solid_voxels_per_axis[x + z * PADDED_CHUNK_SIZE_USIZE] |= 1u64 << y;
                     solid_voxels_per_axis[z + y * PADDED_CHUNK_SIZE_USIZE + PADDED_CHUNK_SIZE2_USIZE] |= 1u64 << x;
                     solid_voxels_per_axis[x + y * PADDED_CHUNK_SIZE_USIZE + PADDED_CHUNK_SIZE2_USIZE * 2] |= 1u64 << z;
                 }
+
+                let pos = IVec3::new(x as i32, y as i32, z as i32);
+                for face in 0..6 {
+                    // a face is hidden when the voxel in front of it covers the opposite side
+                    if !padded.covers(pos, axis_direction(face).opposite()) {
+                        continue;
+                    }
+                    let (column, bit) = match face / 2 {
+                        0 => (x + z * PADDED_CHUNK_SIZE_USIZE, y),
+                        1 => (z + y * PADDED_CHUNK_SIZE_USIZE, x),
+                        _ => (x + y * PADDED_CHUNK_SIZE_USIZE, z),
+                    };
+                    covering_voxels_per_face[face * PADDED_CHUNK_SIZE2_USIZE + column] |= 1u64 << bit;
+                }
             }
         }
     }
@@ -131,15 +309,17 @@ pub fn generate_section_mesh(sections: SectionNeighbors) -> Option<ChunkSectionM
             for x in 0..PADDED_CHUNK_SIZE_USIZE {
                 let i = z * PADDED_CHUNK_SIZE_USIZE + x;
                 let col = solid_voxels_per_axis[(PADDED_CHUNK_SIZE2_USIZE * axis) + i];
-                // sample ascending/descending axes and set true if air meets solid aka need to draw face.
-                voxels_face_mask[2 * axis + 1][z][x] = col & !(col >> 1);
-                voxels_face_mask[2 * axis + 0][z][x] = col & !(col << 1);
+                let ascending = covering_voxels_per_face[PADDED_CHUNK_SIZE2_USIZE * (2 * axis + 1) + i];
+                let descending = covering_voxels_per_face[PADDED_CHUNK_SIZE2_USIZE * (2 * axis) + i];
+                // sample ascending/descending axes and set true if solid meets a non covering voxel aka need to draw face.
+                voxels_face_mask[2 * axis + 1][z][x] = col & !(ascending >> 1);
+                voxels_face_mask[2 * axis + 0][z][x] = col & !(descending << 1);
             }
         }
     }
 
-    // (axis, block, y) -> binary plane
-    let mut data: HashMap<(u8, Block, u16), [u16; 16]> = Default::default();
+    // (axis, block, light, y) -> binary plane
+    let mut data: HashMap<(u8, Block, Light, u16), [u16; 16]> = Default::default();
 
     for axis in 0..6 {
         for z in 0..CHUNK_SIZE as usize {
@@ -163,33 +343,60 @@ pub fn generate_section_mesh(sections: SectionNeighbors) -> Option<ChunkSectionM
                         _ => ivec3(x as i32, z as i32, y as i32),     // forward | back
                     };
 
-                    let block = section_data.get_by_xyz(voxel_pos.x, voxel_pos.y, voxel_pos.z).unwrap();
+                    let voxel_pos = voxel_pos + IVec3::ONE;
+                    let block = padded.block(voxel_pos);
+                    // light of the block the face looks at
+                    let light = padded.light(voxel_pos + axis_direction(axis).offset());
                     //let key = (axis, block, y);
-                    let data = data.entry((axis as u8, block, y as u16)).or_default();
+                    let data = data.entry((axis as u8, block, light, y as u16)).or_default();
                     data[x] |= 1u16 << z as u16;
                 }
             }
         }
     }
 
-    for (&(axis, _block, axis_pos), &plane) in data.iter() {
-        let face_dir = match axis {
-            0 => Direction::Down,
-            1 => Direction::Up,
-            2 => Direction::Left,
-            3 => Direction::Right,
-            4 => Direction::Forward,
-            _ => Direction::Back,
-        };
+    for (&(axis, block, light, axis_pos), &plane) in data.iter() {
+        let face_dir = axis_direction(axis as usize);
         let quads_from_axis = greedy_mesh_binary_plane(plane);
 
-        quads_from_axis.into_iter().for_each(|q| {
-            q.append_vertices(&mut vertices, &mut normals, face_dir, axis_pos as i32);
-        });
+        faces.extend(quads_from_axis.into_iter().map(|quad| FaceQuad {
+            direction: face_dir,
+            block,
+            axis_pos: axis_pos as i32,
+            light,
+            quad,
+        }));
+    }
+
+    faces
+}
+
+fn axis_direction(axis: usize) -> Direction {
+    match axis {
+        0 => Direction::Down,
+        1 => Direction::Up,
+        2 => Direction::Left,
+        3 => Direction::Right,
+        4 => Direction::Forward,
+        _ => Direction::Back,
+    }
+}
+
+/// Turns quad vertices in padded grid units into a section mesh of `scale` sized voxels.
+pub(crate) fn build_section_mesh(
+    mut vertices: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    lights: Vec<[f32; 4]>,
+    scale: i32,
+) -> ChunkSectionMesh {
+    if scale != 1 {
+        for vertex in vertices.iter_mut() {
+            *vertex = vertex.map(|v| v * scale as f32);
+        }
     }
 
     let indices = generate_indices(vertices.len());
-    Some(ChunkSectionMesh::new(vertices, normals, indices))
+    ChunkSectionMesh::new(vertices, normals, lights, indices)
 }
 
 //https://github.com/TanTanDev/binary_greedy_mesher_demo/blob/main/src/utils.rs#L95
@@ -0,0 +1,404 @@
+use crate::block_registry::BlockRegistry;
+use crate::region::{RegionStorage, write_atomically};
+use bevy::log::warn;
+use bevy::math::IVec3;
+use bevy::prelude::Resource;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fmt, fs, io};
+
+/// Version of the saved world format this build reads and writes.
+pub const LEVEL_FORMAT_VERSION: u32 = 1;
+
+/// Upgrades a world saved with an older format by one version, the first entry takes version 1
+/// to version 2 and so on.
+pub type LevelMigration = fn(&mut Level, &RegionStorage) -> io::Result<()>;
+
+const MIGRATIONS: &[LevelMigration] = &[];
+
+/// Metadata of a saved world, stored as `key = value` lines in the `level` file of its directory.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Level {
+    pub format_version: u32,
+    pub seed: u64,
+    pub spawn: IVec3,
+    pub generator: String,
+    pub generator_settings: BTreeMap<String, String>,
+    /// Block names by the ids the region files were saved with.
+    pub blocks: Vec<String>,
+    /// Set while migrated region files wait to replace the old ones.
+    pending_migration: bool,
+}
+
+impl Level {
+    /// Metadata of a new world using the ids of `registry`.
+    pub fn new(seed: u64, registry: &BlockRegistry) -> Self {
+        Self {
+            format_version: LEVEL_FORMAT_VERSION,
+            seed,
+            spawn: IVec3::new(0, 40, 0),
+            generator: "voxel:spheres".into(),
+            generator_settings: BTreeMap::from([("radius".into(), "9".into())]),
+            blocks: registry.names().map(String::from).collect(),
+            pending_migration: false,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, LevelError> {
+        let mut entries = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| LevelError::parse(index + 1, "expected `key = value`"))?;
+            entries.push((index + 1, key.trim(), value.trim()));
+        }
+
+        // checked first, newer formats may not even parse
+        let format_version = match entries.iter().find(|(_, key, _)| *key == "format_version") {
+            Some(&(line, _, value)) => parse_value(line, value)?,
+            None => return Err(LevelError::parse(0, "missing `format_version`")),
+        };
+        if format_version == 0 {
+            return Err(LevelError::parse(0, "format version 0 doesn't exist"));
+        }
+        if format_version > LEVEL_FORMAT_VERSION {
+            return Err(LevelError::NewerFormat {
+                found: format_version,
+                supported: LEVEL_FORMAT_VERSION,
+            });
+        }
+
+        let mut level = Self {
+            format_version,
+            seed: 0,
+            spawn: IVec3::ZERO,
+            generator: String::new(),
+            generator_settings: BTreeMap::new(),
+            blocks: vec![],
+            pending_migration: false,
+        };
+        let mut blocks = BTreeMap::new();
+
+        for (line, key, value) in entries {
+            if let Some(setting) = key.strip_prefix("generator.") {
+                level.generator_settings.insert(setting.into(), value.into());
+                continue;
+            }
+            if let Some(id) = key.strip_prefix("block.") {
+                let id: u16 = parse_value(line, id)?;
+                blocks.insert(id, value.to_string());
+                continue;
+            }
+
+            match key {
+                "format_version" => {}
+                "seed" => level.seed = parse_value(line, value)?,
+                "spawn" => {
+                    let coords: Vec<i32> = value
+                        .split_whitespace()
+                        .map(|coord| parse_value(line, coord))
+                        .collect::<Result<_, _>>()?;
+                    let [x, y, z] = coords[..] else {
+                        return Err(LevelError::parse(line, "expected three coordinates"));
+                    };
+                    level.spawn = IVec3::new(x, y, z);
+                }
+                "generator" => level.generator = value.into(),
+                "pending_migration" => level.pending_migration = parse_value(line, value)?,
+                _ => return Err(LevelError::parse(line, format!("unknown key `{key}`"))),
+            }
+        }
+
+        for (expected, (id, name)) in blocks.into_iter().enumerate() {
+            if id as usize != expected {
+                return Err(LevelError::parse(0, format!("block id {expected} is missing")));
+            }
+            level.blocks.push(name);
+        }
+
+        Ok(level)
+    }
+
+    /// Reads the level file of a world.
+    pub fn load(storage: &RegionStorage) -> Result<Option<Self>, LevelError> {
+        match fs::read_to_string(storage.dir().join("level")) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Writes the level file of a world, replacing the old one at once.
+    pub fn save(&self, storage: &RegionStorage) -> io::Result<()> {
+        fs::create_dir_all(storage.dir())?;
+        write_atomically(&storage.dir().join("level"), self.to_string().as_bytes())
+    }
+
+    /// Opens the world of `storage`, creating it when it doesn't exist yet.
+    ///
+    /// Worlds saved by older formats are migrated, and region files are rewritten when the ids of
+    /// `registry` don't match the ones the world was saved with. Blocks which aren't registered
+    /// anymore become air.
+    pub fn open(storage: &RegionStorage, registry: &BlockRegistry) -> Result<Self, LevelError> {
+        let Some(mut level) = Self::load(storage)? else {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos() as u64);
+            let level = Self::new(seed, registry);
+            level.save(storage)?;
+            return Ok(level);
+        };
+
+        if level.pending_migration {
+            storage.finish_migration()?;
+            level.pending_migration = false;
+            level.save(storage)?;
+        }
+
+        for migration in &MIGRATIONS[level.format_version as usize - 1..] {
+            migration(&mut level, storage)?;
+            level.format_version += 1;
+            level.save(storage)?;
+        }
+
+        let remap = level.block_remap(registry);
+        if remap.iter().enumerate().any(|(old, &new)| old as u16 != new) {
+            storage.migrate_regions(|id| remap.get(id as usize).copied().unwrap_or(0))?;
+
+            // the region files are only replaced once the level knows about them
+            level.blocks = registry.names().map(String::from).collect();
+            level.pending_migration = true;
+            level.save(storage)?;
+            storage.finish_migration()?;
+            level.pending_migration = false;
+            level.save(storage)?;
+        } else if level.blocks.len() != registry.names().count() {
+            level.blocks = registry.names().map(String::from).collect();
+            level.save(storage)?;
+        }
+
+        Ok(level)
+    }
+
+    /// New id of every saved block id, air for blocks which aren't registered anymore.
+    fn block_remap(&self, registry: &BlockRegistry) -> Vec<u16> {
+        self.blocks
+            .iter()
+            .map(|name| match registry.by_name(name) {
+                Some(block) => block.id(),
+                None => {
+                    warn!("Block `{name}` isn't registered anymore, replacing it with air");
+                    0
+                }
+            })
+            .collect()
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "format_version = {}", self.format_version)?;
+        writeln!(f, "seed = {}", self.seed)?;
+        writeln!(f, "spawn = {} {} {}", self.spawn.x, self.spawn.y, self.spawn.z)?;
+        writeln!(f, "generator = {}", self.generator)?;
+        for (key, value) in &self.generator_settings {
+            writeln!(f, "generator.{key} = {value}")?;
+        }
+        for (id, name) in self.blocks.iter().enumerate() {
+            writeln!(f, "block.{id} = {name}")?;
+        }
+        if self.pending_migration {
+            writeln!(f, "pending_migration = true")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(line: usize, value: &str) -> Result<T, LevelError> {
+    value
+        .parse()
+        .map_err(|_| LevelError::parse(line, format!("invalid value `{value}`")))
+}
+
+/// Reason a world couldn't be opened.
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    /// Line 0 when the problem isn't on a single line.
+    Parse { line: usize, message: String },
+    /// The world was saved by a newer build.
+    NewerFormat { found: u32, supported: u32 },
+}
+
+impl LevelError {
+    fn parse(line: usize, message: impl Into<String>) -> Self {
+        Self::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for LevelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "failed to access the world: {err}"),
+            LevelError::Parse { line: 0, message } => write!(f, "invalid level file: {message}"),
+            LevelError::Parse { line, message } => write!(f, "invalid level file, line {line}: {message}"),
+            LevelError::NewerFormat { found, supported } => write!(
+                f,
+                "the world was saved with format version {found}, but this build only reads up to version {supported}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<io::Error> for LevelError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::chunk::{CHUNK_SIZE3, Chunk, ChunkPos, ChunkSection};
+    use bevy::math::IVec2;
+    use std::sync::{Arc, RwLock};
+
+    /// Storage in an empty directory of its own.
+    fn empty_storage(name: &str) -> RegionStorage {
+        let dir = std::env::temp_dir().join(format!("voxel-level-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        RegionStorage::new(dir)
+    }
+
+    /// Ids a world was saved with before stone and glass swapped places, with a block which is
+    /// gone since.
+    fn old_blocks() -> Vec<String> {
+        ["voxel:air", "voxel:glass", "voxel:stone", "voxel:gone"].map(String::from).to_vec()
+    }
+
+    /// Chunk holding the old ids 0 to 3 over and over.
+    fn old_chunk() -> Arc<Chunk> {
+        let mut chunk = Chunk::new();
+        for _ in 0..2 {
+            let blocks = (0..CHUNK_SIZE3 as u16).map(|index| Block::from_id(index % 4)).collect();
+            chunk.sections.push(Arc::new(RwLock::new(ChunkSection::from_blocks(blocks).unwrap())));
+        }
+        Arc::new(chunk)
+    }
+
+    /// Asserts the chunk saved by [`old_chunk`] holds the ids of `registry`.
+    fn assert_remapped(storage: &RegionStorage, registry: &BlockRegistry) {
+        let expected = [
+            Block(0),
+            registry.by_name("voxel:glass").unwrap(),
+            registry.by_name("voxel:stone").unwrap(),
+            Block(0),
+        ];
+        let chunk = storage.load_chunk(ChunkPos(IVec2::ZERO)).unwrap().unwrap();
+        for section in &chunk.sections {
+            for (index, &block) in section.read().unwrap().blocks().iter().enumerate() {
+                assert_eq!(block, expected[index % 4], "block {index}");
+            }
+        }
+
+        let leftovers = fs::read_dir(storage.dir().join("region")).unwrap();
+        assert!(leftovers.flatten().all(|entry| entry.path().extension().is_some_and(|extension| extension == "vxr")));
+    }
+
+    #[test]
+    fn level_files_survive_a_round_trip() {
+        let registry = BlockRegistry::default();
+        let mut level = Level::new(u64::MAX, &registry);
+        level.spawn = IVec3::new(-12, 64, 7);
+        level.generator_settings.insert("sea_level".into(), "12".into());
+        assert_eq!(Level::parse(&level.to_string()).unwrap(), level);
+
+        level.pending_migration = true;
+        assert_eq!(Level::parse(&level.to_string()).unwrap(), level);
+
+        let storage = empty_storage("round-trip");
+        assert!(Level::load(&storage).unwrap().is_none());
+        level.save(&storage).unwrap();
+        assert_eq!(Level::load(&storage).unwrap(), Some(level));
+
+        let text = "# comment\n\nformat_version = 1\n  seed=3 \nspawn = 1 2 3\ngenerator = flat\nblock.1 = b\nblock.0 = a\n";
+        let parsed = Level::parse(text).unwrap();
+        assert_eq!((parsed.seed, parsed.spawn, parsed.blocks), (3, IVec3::new(1, 2, 3), vec!["a".into(), "b".into()]));
+    }
+
+    #[test]
+    fn rejects_broken_and_newer_levels() {
+        // keys of newer formats aren't looked at
+        let newer = Level::parse("seed = 1\nsomething_new = yes\nformat_version = 2\n");
+        assert!(matches!(newer, Err(LevelError::NewerFormat { found: 2, supported: LEVEL_FORMAT_VERSION })));
+
+        for (text, line) in [
+            ("seed = 1\n", 0),
+            ("format_version = 0\n", 0),
+            ("format_version = 1\nseed\n", 2),
+            ("format_version = 1\nseed = -1\n", 2),
+            ("format_version = 1\nspawn = 1 2\n", 2),
+            ("format_version = 1\n\nunknown = 1\n", 3),
+            ("format_version = 1\nblock.0 = a\nblock.2 = c\n", 0),
+        ] {
+            match Level::parse(text) {
+                Err(LevelError::Parse { line: found, .. }) => assert_eq!(found, line, "{text:?}"),
+                other => panic!("{text:?} gave {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn opening_remaps_the_saved_block_ids() {
+        let registry = BlockRegistry::default();
+        let storage = empty_storage("remap");
+        let mut level = Level::new(1, &registry);
+        level.blocks = old_blocks();
+        level.save(&storage).unwrap();
+        storage.save_chunks(&[(ChunkPos(IVec2::ZERO), old_chunk())]).unwrap();
+        // left behind by a migration which never got recorded in the level file
+        fs::write(storage.dir().join("region/r.0.0.vxr.migrated"), b"stale").unwrap();
+
+        let opened = Level::open(&storage, &registry).unwrap();
+        assert_eq!(opened.blocks, registry.names().map(String::from).collect::<Vec<_>>());
+        assert!(!opened.pending_migration);
+        assert_eq!(Level::load(&storage).unwrap(), Some(opened));
+        assert_remapped(&storage, &registry);
+
+        // opening again has nothing left to do
+        Level::open(&storage, &registry).unwrap();
+        assert_remapped(&storage, &registry);
+    }
+
+    #[test]
+    fn opening_finishes_an_interrupted_migration() {
+        let registry = BlockRegistry::default();
+        let storage = empty_storage("resume");
+        storage.save_chunks(&[(ChunkPos(IVec2::ZERO), old_chunk())]).unwrap();
+
+        // stopped right after the level recorded the migrated region files
+        let mut level = Level::new(1, &registry);
+        let remap = Level { blocks: old_blocks(), ..level.clone() }.block_remap(&registry);
+        storage.migrate_regions(|id| remap[id as usize]).unwrap();
+        level.pending_migration = true;
+        level.save(&storage).unwrap();
+
+        let opened = Level::open(&storage, &registry).unwrap();
+        assert!(!opened.pending_migration);
+        assert!(!Level::load(&storage).unwrap().unwrap().pending_migration);
+        assert_remapped(&storage, &registry);
+    }
+}
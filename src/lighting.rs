@@ -0,0 +1,599 @@
+use crate::block::Block;
+use crate::block_registry::{BlockDefinition, BlockRegistry};
+use crate::chunk::{Chunk, ChunkPos, CHUNK_SIZE};
+use crate::quad::Direction;
+use bevy::math::IVec3;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+/// Packed light of a block, see [`LightChannel`].
+pub type Light = u16;
+
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light a block exposed to the open sky gets.
+pub const SKY_LIGHT: Light = (MAX_LIGHT as Light) << 12;
+
+/// A 4 bit light value packed into the light of a block. Block light is coloured and spreads in
+/// every colour channel on its own.
+///
+/// msb  ``u4: sky``
+///      ``u4: red``
+///      ``u4: green``
+/// lsb  ``u4: blue``
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LightChannel {
+    Sky,
+    Red,
+    Green,
+    Blue,
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 4] = [
+        LightChannel::Sky,
+        LightChannel::Red,
+        LightChannel::Green,
+        LightChannel::Blue,
+    ];
+
+    fn shift(&self) -> u8 {
+        match self {
+            LightChannel::Sky => 12,
+            LightChannel::Red => 8,
+            LightChannel::Green => 4,
+            LightChannel::Blue => 0,
+        }
+    }
+
+    pub fn get(&self, light: Light) -> u8 {
+        ((light >> self.shift()) & 0xF) as u8
+    }
+
+    pub fn set(&self, light: Light, value: u8) -> Light {
+        (light & !(0xF << self.shift())) | (((value & 0xF) as Light) << self.shift())
+    }
+
+    fn emission(&self, definition: &BlockDefinition) -> u8 {
+        match self {
+            LightChannel::Sky => 0,
+            LightChannel::Red => definition.light_emission[0],
+            LightChannel::Green => definition.light_emission[1],
+            LightChannel::Blue => definition.light_emission[2],
+        }
+    }
+
+    /// Light reaching a neighbor of a block lit with `light` in the given direction. Full sky light
+    /// travels straight down without fading.
+    fn spread(&self, light: u8, direction: Direction) -> u8 {
+        if *self == LightChannel::Sky && direction == Direction::Down && light == MAX_LIGHT {
+            MAX_LIGHT
+        } else {
+            light.saturating_sub(1)
+        }
+    }
+}
+
+/// Brightest value of every channel of two lights.
+pub fn brightest(a: Light, b: Light) -> Light {
+    LightChannel::ALL.iter().fold(0, |light, channel| {
+        channel.set(light, channel.get(a).max(channel.get(b)))
+    })
+}
+
+/// Light values as vertex data, `[red, green, blue, sky]` in the 0 to 1 range.
+pub fn vertex_light(light: Light) -> [f32; 4] {
+    [
+        LightChannel::Red,
+        LightChannel::Green,
+        LightChannel::Blue,
+        LightChannel::Sky,
+    ]
+    .map(|channel| channel.get(light) as f32 / MAX_LIGHT as f32)
+}
+
+/// Blocks and light the propagation runs on.
+trait LightVolume {
+    /// `None` outside of the volume.
+    fn block(&self, pos: IVec3) -> Option<Block>;
+    fn light(&self, pos: IVec3) -> Light;
+    fn set_light(&mut self, pos: IVec3, light: Light);
+    /// Whether the position is above the volume and therefore lit by the sky.
+    fn is_open_sky(&self, pos: IVec3) -> bool;
+}
+
+/// Spreads light outwards from the queued blocks until it fades out.
+fn propagate(
+    volume: &mut impl LightVolume,
+    registry: &BlockRegistry,
+    channel: LightChannel,
+    queue: &mut VecDeque<IVec3>,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let light = channel.get(volume.light(pos));
+
+        for direction in Direction::ALL {
+            let next = pos + direction.offset();
+            let Some(block) = volume.block(next) else {
+                continue;
+            };
+            if registry.get(block).opaque {
+                continue;
+            }
+
+            let value = channel.spread(light, direction);
+            let next_light = volume.light(next);
+            if channel.get(next_light) < value {
+                volume.set_light(next, channel.set(next_light, value));
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Darkens everything lit by the queued blocks and queues the blocks around the darkened area
+/// which still have light from elsewhere, so [`propagate`] can fill it again.
+fn unpropagate(
+    volume: &mut impl LightVolume,
+    registry: &BlockRegistry,
+    channel: LightChannel,
+    removal: &mut VecDeque<(IVec3, u8)>,
+    queue: &mut VecDeque<IVec3>,
+) {
+    while let Some((pos, light)) = removal.pop_front() {
+        for direction in Direction::ALL {
+            let next = pos + direction.offset();
+            let Some(block) = volume.block(next) else {
+                continue;
+            };
+
+            let next_light = volume.light(next);
+            let value = channel.get(next_light);
+            if value == 0 {
+                continue;
+            }
+
+            if value < light || channel.spread(light, direction) == value {
+                volume.set_light(next, channel.set(next_light, 0));
+                removal.push_back((next, value));
+
+                let emission = channel.emission(registry.get(block));
+                if emission > 0 {
+                    volume.set_light(next, channel.set(next_light, emission));
+                    queue.push_back(next);
+                }
+            } else {
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Relights the area around blocks that just changed, removing the light of all of them before
+/// spreading it again so each channel is propagated once for the whole batch.
+fn relight_blocks(volume: &mut impl LightVolume, registry: &BlockRegistry, positions: &[IVec3]) {
+    for channel in LightChannel::ALL {
+        let mut removal = VecDeque::new();
+        let mut queue = VecDeque::new();
+
+        for &pos in positions {
+            if volume.block(pos).is_none() {
+                continue;
+            }
+            let light = volume.light(pos);
+            let old = channel.get(light);
+            if old > 0 {
+                volume.set_light(pos, channel.set(light, 0));
+                removal.push_back((pos, old));
+            }
+        }
+        unpropagate(volume, registry, channel, &mut removal, &mut queue);
+
+        for &pos in positions {
+            let Some(block) = volume.block(pos) else {
+                continue;
+            };
+            let definition = registry.get(block);
+
+            let emission = channel.emission(definition);
+            if emission > 0 {
+                volume.set_light(pos, channel.set(volume.light(pos), emission));
+                queue.push_back(pos);
+            }
+
+            if !definition.opaque {
+                if channel == LightChannel::Sky && volume.is_open_sky(pos + IVec3::Y) {
+                    volume.set_light(pos, channel.set(volume.light(pos), MAX_LIGHT));
+                    queue.push_back(pos);
+                }
+
+                for direction in Direction::ALL {
+                    let next = pos + direction.offset();
+                    if volume.block(next).is_some() && channel.get(volume.light(next)) > 0 {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        propagate(volume, registry, channel, &mut queue);
+    }
+}
+
+/// A single chunk copied into flat arrays, used to light it before it joins the world.
+struct ChunkVolume {
+    height: i32,
+    blocks: Vec<Block>,
+    light: Vec<Light>,
+}
+
+impl ChunkVolume {
+    fn new(chunk: &Chunk) -> Self {
+        let height = chunk.height();
+        let mut blocks = vec![Block(0); (CHUNK_SIZE * CHUNK_SIZE * height) as usize];
+
+        for (section_y, section) in chunk.sections.iter().enumerate() {
+            let section = section.read().unwrap();
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let pos = IVec3::new(x, y + section_y as i32 * CHUNK_SIZE, z);
+                        blocks[Self::index(pos)] = section.get_by_xyz(x, y, z).unwrap();
+                    }
+                }
+            }
+        }
+
+        Self {
+            height,
+            light: vec![0; blocks.len()],
+            blocks,
+        }
+    }
+
+    #[inline]
+    fn index(pos: IVec3) -> usize {
+        (pos.x + pos.z * CHUNK_SIZE + pos.y * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    fn contains(&self, pos: IVec3) -> bool {
+        pos.x >= 0
+            && pos.x < CHUNK_SIZE
+            && pos.z >= 0
+            && pos.z < CHUNK_SIZE
+            && pos.y >= 0
+            && pos.y < self.height
+    }
+
+    fn write_back(&self, chunk: &Chunk) {
+        for (section_y, section) in chunk.sections.iter().enumerate() {
+            let mut section = section.write().unwrap();
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let pos = IVec3::new(x, y + section_y as i32 * CHUNK_SIZE, z);
+                        section.set_light_by_xyz(x, y, z, self.light[Self::index(pos)]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl LightVolume for ChunkVolume {
+    fn block(&self, pos: IVec3) -> Option<Block> {
+        self.contains(pos).then(|| self.blocks[Self::index(pos)])
+    }
+
+    fn light(&self, pos: IVec3) -> Light {
+        if self.contains(pos) {
+            self.light[Self::index(pos)]
+        } else {
+            0
+        }
+    }
+
+    fn set_light(&mut self, pos: IVec3, light: Light) {
+        if self.contains(pos) {
+            self.light[Self::index(pos)] = light;
+        }
+    }
+
+    fn is_open_sky(&self, pos: IVec3) -> bool {
+        pos.y >= self.height
+    }
+}
+
+/// Lights a chunk on its own, as if it was surrounded by darkness. Light crossing over from and to
+/// its neighbors gets added by [`stitch_chunk_light`] once it is part of the world.
+pub fn light_chunk(chunk: &Chunk, registry: &BlockRegistry) {
+    let mut volume = ChunkVolume::new(chunk);
+
+    // sky light falls straight down until it hits something
+    for z in 0..CHUNK_SIZE {
+        for x in 0..CHUNK_SIZE {
+            for y in (0..volume.height).rev() {
+                let pos = IVec3::new(x, y, z);
+                if registry.get(volume.blocks[ChunkVolume::index(pos)]).opaque {
+                    break;
+                }
+                volume.set_light(pos, SKY_LIGHT);
+            }
+        }
+    }
+
+    // and then spreads sideways under overhangs
+    let mut sky_queue = VecDeque::new();
+    let mut block_queues = [LightChannel::Red, LightChannel::Green, LightChannel::Blue].map(|channel| (channel, VecDeque::new()));
+    for y in 0..volume.height {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let pos = IVec3::new(x, y, z);
+                let definition = registry.get(volume.blocks[ChunkVolume::index(pos)]);
+
+                for (channel, queue) in block_queues.iter_mut() {
+                    let emission = channel.emission(definition);
+                    if emission > 0 {
+                        volume.set_light(pos, channel.set(volume.light(pos), emission));
+                        queue.push_back(pos);
+                    }
+                }
+
+                if LightChannel::Sky.get(volume.light(pos)) == MAX_LIGHT
+                    && [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z].iter().any(|&offset| {
+                        volume.block(pos + offset).is_some_and(|block| {
+                            !registry.get(block).opaque
+                                && LightChannel::Sky.get(volume.light(pos + offset)) < MAX_LIGHT - 1
+                        })
+                    })
+                {
+                    sky_queue.push_back(pos);
+                }
+            }
+        }
+    }
+
+    propagate(&mut volume, registry, LightChannel::Sky, &mut sky_queue);
+    for (channel, mut queue) in block_queues {
+        propagate(&mut volume, registry, channel, &mut queue);
+    }
+
+    volume.write_back(chunk);
+}
+
+/// Every loaded chunk of the world, remembering which sections had their light changed.
+struct WorldVolume<'a> {
+    chunks: &'a HashMap<ChunkPos, Arc<Chunk>>,
+    touched: HashSet<(ChunkPos, i32)>,
+}
+
+impl<'a> WorldVolume<'a> {
+    fn new(chunks: &'a HashMap<ChunkPos, Arc<Chunk>>) -> Self {
+        Self {
+            chunks,
+            touched: HashSet::new(),
+        }
+    }
+
+    /// Chunk, section and section local position of a world position.
+    fn locate(&self, pos: IVec3) -> Option<(ChunkPos, &'a Chunk, i32, IVec3)> {
+        let chunk_pos = ChunkPos::of_block(pos);
+        let chunk = self.chunks.get(&chunk_pos)?;
+        if pos.y < 0 || pos.y >= chunk.height() {
+            return None;
+        }
+
+        let local = pos.rem_euclid(IVec3::splat(CHUNK_SIZE));
+        Some((chunk_pos, chunk.as_ref(), pos.y / CHUNK_SIZE, local))
+    }
+}
+
+impl LightVolume for WorldVolume<'_> {
+    fn block(&self, pos: IVec3) -> Option<Block> {
+        let (_, chunk, section_y, local) = self.locate(pos)?;
+        let section = chunk.sections[section_y as usize].read().unwrap();
+        section.get_by_xyz(local.x, local.y, local.z)
+    }
+
+    fn light(&self, pos: IVec3) -> Light {
+        let Some((_, chunk, section_y, local)) = self.locate(pos) else {
+            return 0;
+        };
+        let section = chunk.sections[section_y as usize].read().unwrap();
+        section.get_light_by_xyz(local.x, local.y, local.z).unwrap_or(0)
+    }
+
+    fn set_light(&mut self, pos: IVec3, light: Light) {
+        let Some((chunk_pos, chunk, section_y, local)) = self.locate(pos) else {
+            return;
+        };
+        let mut section = chunk.sections[section_y as usize].write().unwrap();
+        section.set_light_by_xyz(local.x, local.y, local.z, light);
+        self.touched.insert((chunk_pos, section_y));
+    }
+
+    fn is_open_sky(&self, pos: IVec3) -> bool {
+        self.chunks
+            .get(&ChunkPos::of_block(pos))
+            .is_some_and(|chunk| pos.y >= chunk.height())
+    }
+}
+
+/// Lets light flow between a newly loaded chunk and its loaded neighbors.
+/// Returns the sections whose light changed.
+pub fn stitch_chunk_light(
+    chunks: &HashMap<ChunkPos, Arc<Chunk>>,
+    registry: &BlockRegistry,
+    chunk_pos: ChunkPos,
+) -> HashSet<(ChunkPos, i32)> {
+    let mut volume = WorldVolume::new(chunks);
+    let Some(chunk) = chunks.get(&chunk_pos) else {
+        return volume.touched;
+    };
+
+    let origin = IVec3::new(chunk_pos.0.x * CHUNK_SIZE, 0, chunk_pos.0.y * CHUNK_SIZE);
+    let mut border = vec![];
+    for y in 0..chunk.height() {
+        for i in 0..CHUNK_SIZE {
+            // own border blocks and the blocks of the neighbors right next to them
+            for (inside, outside) in [
+                (IVec3::new(0, y, i), IVec3::new(-1, y, i)),
+                (IVec3::new(CHUNK_SIZE - 1, y, i), IVec3::new(CHUNK_SIZE, y, i)),
+                (IVec3::new(i, y, 0), IVec3::new(i, y, -1)),
+                (IVec3::new(i, y, CHUNK_SIZE - 1), IVec3::new(i, y, CHUNK_SIZE)),
+            ] {
+                border.push(origin + inside);
+                border.push(origin + outside);
+            }
+        }
+    }
+
+    for channel in LightChannel::ALL {
+        let mut queue: VecDeque<IVec3> = border
+            .iter()
+            .copied()
+            .filter(|&pos| channel.get(volume.light(pos)) > 0)
+            .collect();
+        propagate(&mut volume, registry, channel, &mut queue);
+    }
+
+    volume.touched
+}
+
+/// Updates the light around blocks of the world after they changed.
+/// Returns the sections whose light changed.
+pub fn relight_world_blocks(
+    chunks: &HashMap<ChunkPos, Arc<Chunk>>,
+    registry: &BlockRegistry,
+    positions: &[IVec3],
+) -> HashSet<(ChunkPos, i32)> {
+    let mut volume = WorldVolume::new(chunks);
+    relight_blocks(&mut volume, registry, positions);
+    volume.touched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::RwLock;
+
+    /// Small deterministic generator to pick edit positions.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    /// Lights every chunk from scratch, as if they all were loaded at once.
+    pub fn relight_all(chunks: &HashMap<ChunkPos, Arc<Chunk>>, registry: &BlockRegistry) {
+        for chunk in chunks.values() {
+            light_chunk(chunk, registry);
+        }
+        for &chunk_pos in chunks.keys() {
+            stitch_chunk_light(chunks, registry, chunk_pos);
+        }
+    }
+
+    /// Square of generated chunks around the origin, lit from scratch.
+    fn lit_chunks(radius: i32, registry: &BlockRegistry) -> HashMap<ChunkPos, Arc<Chunk>> {
+        let mut chunks = HashMap::new();
+        for x in -radius..radius {
+            for z in -radius..radius {
+                let mut chunk = Chunk::new();
+                chunk.generate();
+                chunks.insert(ChunkPos(bevy::math::IVec2::new(x, z)), Arc::new(chunk));
+            }
+        }
+        relight_all(&chunks, registry);
+        chunks
+    }
+
+    fn copy_chunks(chunks: &HashMap<ChunkPos, Arc<Chunk>>) -> HashMap<ChunkPos, Arc<Chunk>> {
+        chunks
+            .iter()
+            .map(|(&chunk_pos, chunk)| {
+                let mut copy = Chunk::new();
+                copy.sections = chunk
+                    .sections
+                    .iter()
+                    .map(|section| Arc::new(RwLock::new(section.read().unwrap().clone())))
+                    .collect();
+                (chunk_pos, Arc::new(copy))
+            })
+            .collect()
+    }
+
+    /// Places and removes `edits` random lights and blockers, relighting every `batch` edits at
+    /// once, and checks the result against lighting the chunks from scratch.
+    fn stress(edits: usize, batch: usize, seed: u64) {
+        let registry = BlockRegistry::default();
+        let chunks = lit_chunks(1, &registry);
+        let chunk_positions: Vec<ChunkPos> = chunks.keys().copied().collect();
+        let placeable: Vec<Block> = (1..=Block::ID_MASK)
+            .map(Block::from_id)
+            .take_while(|&block| !registry.get(block).name.is_empty())
+            .collect();
+
+        let mut random = XorShift(seed);
+        let mut placed = vec![];
+        let mut pending = vec![];
+        for edit in 0..edits {
+            // removes one of the earlier blocks every other edit on average
+            let (pos, block) = if !placed.is_empty() && random.next().is_multiple_of(2) {
+                let index = random.next() as usize % placed.len();
+                (placed.swap_remove(index), Block(0))
+            } else {
+                let chunk_pos = chunk_positions[random.next() as usize % chunk_positions.len()];
+                let height = chunks[&chunk_pos].height();
+                let pos = IVec3::new(
+                    chunk_pos.0.x * CHUNK_SIZE + (random.next() % CHUNK_SIZE as u64) as i32,
+                    (random.next() % height as u64) as i32,
+                    chunk_pos.0.y * CHUNK_SIZE + (random.next() % CHUNK_SIZE as u64) as i32,
+                );
+                placed.push(pos);
+                (pos, placeable[random.next() as usize % placeable.len()])
+            };
+
+            let local = IVec3::new(pos.x.rem_euclid(CHUNK_SIZE), pos.y, pos.z.rem_euclid(CHUNK_SIZE));
+            chunks[&ChunkPos::of_block(pos)].set(local, block);
+            pending.push(pos);
+            if pending.len() == batch || edit == edits - 1 {
+                relight_world_blocks(&chunks, &registry, &pending);
+                pending.clear();
+            }
+        }
+
+        let recomputed = copy_chunks(&chunks);
+        relight_all(&recomputed, &registry);
+        for (chunk_pos, chunk) in &chunks {
+            for (section_y, section) in chunk.sections.iter().enumerate() {
+                let section = section.read().unwrap();
+                let expected = recomputed[chunk_pos].sections[section_y].read().unwrap();
+                for index in 0..CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
+                    let local = Chunk::coords_by_index(index);
+                    assert_eq!(
+                        section.get_light_by_xyz(local.x, local.y, local.z),
+                        expected.get_light_by_xyz(local.x, local.y, local.z),
+                        "light at {local} of section {section_y} in chunk {} after {edits} edits (seed {seed})",
+                        chunk_pos.0,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn relighting_each_edit_matches_a_full_recompute() {
+        stress(5000, 1, 0x9e37_79b9_7f4a_7c15);
+    }
+
+    #[test]
+    fn relighting_batches_of_edits_matches_a_full_recompute() {
+        stress(5000, 37, 0x2545_f491_4f6c_dd1d);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::block::Block;
+use crate::chunk::{ChunkPos, ChunkSection, CHUNK_SIZE};
+use crate::chunk_loader::{chunk_coords, ChunkLoader};
+use crate::greedy_chunk_render_plugin::{padded_index, PaddedSection};
+use crate::lighting::{brightest, Light};
+use crate::section_neighbors::SectionNeighbors;
+use crate::world::{World, WorldPlugin};
+use bevy::app::{App, Plugin, PostUpdate};
+use bevy::math::{IVec2, IVec3, Vec3Swizzles};
+use bevy::prelude::{DetectChanges, GlobalTransform, IntoScheduleConfigs, Local, Query, Res, ResMut, Resource, With};
+
+/// Level of detail a section is meshed at, every level halves the resolution of the previous one.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct LodLevel(pub u8);
+
+impl LodLevel {
+    pub const FULL: LodLevel = LodLevel(0);
+    pub const MAX: LodLevel = LodLevel(3);
+
+    pub fn is_full(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Size of a downsampled voxel in blocks.
+    pub fn scale(&self) -> i32 {
+        1 << self.0
+    }
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct LodSettings {
+    /// Chunk distance from the nearest loader at which LOD 1, 2 and 3 start.
+    pub distances: [i32; LodLevel::MAX.0 as usize],
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            distances: [8, 16, 24],
+        }
+    }
+}
+
+impl LodSettings {
+    pub fn level_for(&self, distance_sq: i32) -> LodLevel {
+        let level = self
+            .distances
+            .iter()
+            .take_while(|&&distance| distance_sq >= distance * distance)
+            .count();
+
+        LodLevel(level as u8)
+    }
+}
+
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LodSettings>().add_systems(
+            PostUpdate,
+            Self::update_chunk_lods.before(WorldPlugin::start_mesh_tasks),
+        );
+    }
+}
+
+impl LodPlugin {
+    /// Assigns a LOD to newly loaded chunks and re-evaluates every chunk once a loader changes chunk.
+    /// Chunks whose LOD changed are remeshed together with their neighbors, which need to switch
+    /// between culling against them and keeping their border faces.
+    pub fn update_chunk_lods(
+        loaders: Query<&GlobalTransform, With<ChunkLoader>>,
+        settings: Res<LodSettings>,
+        mut world: ResMut<World>,
+        mut previous_loaders: Local<Vec<IVec2>>,
+    ) {
+        let loader_chunks: Vec<IVec2> = loaders
+            .iter()
+            .map(|transform| chunk_coords(transform.translation()).xz())
+            .collect();
+
+        let candidates: Vec<ChunkPos> = if *previous_loaders != loader_chunks || settings.is_changed() {
+            *previous_loaders = loader_chunks.clone();
+            world.loaded_chunks.keys().copied().collect()
+        } else {
+            world
+                .chunks_mesh_to_load
+                .iter()
+                .filter(|pos| !world.chunk_lods.contains_key(pos))
+                .copied()
+                .collect()
+        };
+
+        for chunk_pos in candidates {
+            let distance_sq = loader_chunks
+                .iter()
+                .map(|&loader| loader.distance_squared(chunk_pos.0))
+                .min()
+                .unwrap_or(0);
+            let lod = settings.level_for(distance_sq);
+
+            match world.chunk_lods.insert(chunk_pos, lod) {
+                Some(previous) if previous != lod => {
+                    world.remesh_chunk(chunk_pos);
+                    for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                        world.remesh_chunk(ChunkPos(chunk_pos.0 + offset));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Downsamples a section into a padded grid of `16 / scale` voxels per axis, with a border made of
+/// the downsampled cells of its neighbors next to it.
+///
+/// A downsampled voxel is solid when at least half of the blocks it covers are, and takes the
+/// highest of those blocks so the surface keeps its look from a distance. Its light is the
+/// brightest light of the blocks it covers.
+///
+/// Missing neighbors, which includes neighbors at another LOD, leave an air border so the faces
+/// along it act as skirts hiding the cracks between the levels.
+pub fn downsample_padded(sections: &SectionNeighbors, section: &ChunkSection, lod: LodLevel) -> PaddedSection {
+    let scale = lod.scale();
+    let size = CHUNK_SIZE / scale;
+    let mut padded = PaddedSection::new();
+
+    for y in 0..size {
+        for z in 0..size {
+            for x in 0..size {
+                let (block, light) = downsample_cell(section, x * scale, y * scale, z * scale, scale);
+                let index = padded_index(x as usize + 1, y as usize + 1, z as usize + 1);
+                padded.blocks[index] = block;
+                padded.light[index] = light;
+            }
+        }
+    }
+
+    let neighbors = [
+        (&sections.west, 0, -1),
+        (&sections.east, 0, size),
+        (&sections.down, 1, -1),
+        (&sections.up, 1, size),
+        (&sections.south, 2, -1),
+        (&sections.north, 2, size),
+    ];
+    for (neighbor, axis, border) in neighbors {
+        let Some(neighbor) = neighbor else {
+            continue;
+        };
+        let neighbor = neighbor.read().unwrap();
+
+        for a in 0..size {
+            for b in 0..size {
+                let mut cell = [a, a, a];
+                cell[(axis + 1) % 3] = b;
+                cell[axis] = border;
+                let cell = IVec3::from_array(cell);
+
+                // the cell on the other side of the border, in the neighbor's own coordinates
+                let source = cell.rem_euclid(IVec3::splat(size)) * scale;
+                let (block, light) = downsample_cell(&neighbor, source.x, source.y, source.z, scale);
+                let padded_pos = (cell + 1).as_uvec3();
+                let index = padded_index(padded_pos.x as usize, padded_pos.y as usize, padded_pos.z as usize);
+                padded.blocks[index] = block;
+                padded.light[index] = light;
+            }
+        }
+    }
+
+    padded
+}
+
+fn downsample_cell(section: &ChunkSection, x0: i32, y0: i32, z0: i32, scale: i32) -> (Block, Light) {
+    let mut solid = 0;
+    let mut surface = Block(0);
+    let mut light = 0;
+
+    for y in (y0..y0 + scale).rev() {
+        for z in z0..z0 + scale {
+            for x in x0..x0 + scale {
+                let block = section.get_by_xyz(x, y, z).unwrap();
+                light = brightest(light, section.get_light_by_xyz(x, y, z).unwrap());
+
+                if block.is_solid() {
+                    if solid == 0 {
+                        surface = block;
+                    }
+                    solid += 1;
+                }
+            }
+        }
+    }
+
+    if solid * 2 >= scale * scale * scale {
+        (surface, light)
+    } else {
+        (Block(0), light)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_registry::BlockRegistry;
+    use crate::chunk::CHUNK_SIZE3;
+    use crate::greedy_chunk_render_plugin::{greedy_face_quads, padded_section};
+    use crate::quad::Direction;
+    use std::sync::{Arc, RwLock};
+
+    fn stone_section() -> Arc<RwLock<ChunkSection>> {
+        let blocks = vec![Block(1); CHUNK_SIZE3 as usize];
+        Arc::new(RwLock::new(ChunkSection::from_blocks(blocks).unwrap()))
+    }
+
+    fn faces(sections: &SectionNeighbors, lod: LodLevel, direction: Direction) -> usize {
+        let padded = padded_section(sections, lod, &BlockRegistry::default()).unwrap();
+        greedy_face_quads(&padded)
+            .iter()
+            .filter(|face| face.direction == direction)
+            .count()
+    }
+
+    #[test]
+    fn downsampled_neighbors_hide_shared_faces() {
+        for lod in 1..=LodLevel::MAX.0 {
+            let lod = LodLevel(lod);
+            let sections = SectionNeighbors {
+                center: stone_section(),
+                up: Some(stone_section()),
+                down: None,
+                north: Some(stone_section()),
+                south: None,
+                east: None,
+                west: Some(stone_section()),
+            };
+
+            assert_eq!(faces(&sections, lod, Direction::Up), 0);
+            assert_eq!(faces(&sections, lod, Direction::Back), 0);
+            assert_eq!(faces(&sections, lod, Direction::Left), 0);
+            // missing neighbors, like neighbors at another LOD, keep their skirts
+            assert!(faces(&sections, lod, Direction::Down) > 0);
+            assert!(faces(&sections, lod, Direction::Forward) > 0);
+            assert!(faces(&sections, lod, Direction::Right) > 0);
+        }
+    }
+}
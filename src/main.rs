@@ -1,16 +1,50 @@
 mod block;
+mod block_shape;
+mod block_volume;
 mod chunk;
 mod chunk_loader;
+mod chunk_scheduler;
+mod column_mesher;
 mod chunk_mesh;
 mod debug_world;
 mod greedy_chunk_render_plugin;
+mod lod;
+mod mesher;
+mod model_mesher;
+mod naive_mesher;
 mod quad;
 mod section_neighbors;
+mod surface_nets_mesher;
 mod world;
 mod chunk_material;
+mod block_registry;
+mod lighting;
+mod translucent_mesher;
+mod region;
+mod level;
+mod byte_reader;
+mod vox;
+mod nbt;
+mod anvil;
+mod mesh_export;
+mod block_interaction;
+mod collision;
+mod character_controller;
+mod voxel_body;
+mod pathfinding;
+mod world_edit;
+mod edit_history;
+mod clipboard;
 
+use crate::block_interaction::BlockInteractionPlugin;
+use crate::character_controller::{CharacterController, CharacterControllerPlugin};
 use crate::chunk_loader::{ChunkLoader, ChunkLoaderPlugin};
 use crate::debug_world::DebugWorldPlugin;
+use crate::lod::LodPlugin;
+use crate::pathfinding::PathfindingPlugin;
+use crate::edit_history::EditHistoryPlugin;
+use crate::clipboard::ClipboardPlugin;
+use crate::voxel_body::VoxelBodyPlugin;
 use crate::world::WorldPlugin;
 use bevy::app::{App, PluginGroup, PostStartup};
 use bevy::camera::Camera3d;
@@ -31,6 +65,14 @@ use bevy_inspector_egui::bevy_egui::EguiPlugin;
 use crate::chunk_material::ChunkMaterial;
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|command| command == "import-mca") {
+        std::process::exit(anvil::run_cli(&args[2..]));
+    }
+    if args.get(1).is_some_and(|command| command == "export-mesh") {
+        std::process::exit(mesh_export::run_cli(&args[2..]));
+    }
+
     App::new()
         .add_plugins((
             DefaultPlugins
@@ -56,9 +98,15 @@ fn main() {
             EguiPlugin::default(),
             WorldPlugin,
             ChunkLoaderPlugin,
+            LodPlugin,
             DebugWorldPlugin,
+            BlockInteractionPlugin,
+            CharacterControllerPlugin,
+            VoxelBodyPlugin,
+            PathfindingPlugin,
             MaterialPlugin::<ChunkMaterial>::default()
         ))
+        .add_plugins((EditHistoryPlugin, ClipboardPlugin))
         .insert_resource(WireframeConfig {
             global: true,
             default_color: WHITE.into(),
@@ -78,7 +126,8 @@ pub fn setup(
     commands.spawn((
         Transform::default(),
         Camera3d::default(),
-        ChunkLoader::new(6),
+        ChunkLoader::new(32),
+        CharacterController::default(),
         FlyCam,
     ));
 
@@ -0,0 +1,293 @@
+use crate::block::Block;
+use crate::block_registry::BlockRegistry;
+use crate::chunk::{CHUNK_SIZE, Chunk, ChunkPos, ChunkSection};
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::GreedyMesher;
+use crate::level::Level;
+use crate::lighting::{light_chunk, stitch_chunk_light};
+use crate::lod::LodLevel;
+use crate::mesher::SectionMeshes;
+use crate::region::RegionStorage;
+use crate::section_neighbors::SectionNeighbors;
+use crate::world::WorldPlugin;
+use bevy::math::{IVec2, IVec3, Vec3, Vec4};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+/// Triangles of an export with a color per vertex.
+#[derive(Debug, Clone, Default)]
+pub struct ExportPrimitive {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub colors: Vec<[f32; 4]>,
+    /// Block id of every vertex, usable as a texture index.
+    pub blocks: Vec<u16>,
+    pub indices: Vec<u32>,
+}
+
+impl ExportPrimitive {
+    /// Appends a section mesh placed at `offset`, coloring it with the block colors.
+    fn append(&mut self, mesh: &ChunkSectionMesh, offset: Vec3, registry: &BlockRegistry, bake_light: bool) {
+        let base = self.positions.len() as u32;
+        self.indices.extend(mesh.indices.iter().map(|index| base + index));
+        self.positions.extend(mesh.vertices.iter().map(|&vertex| (Vec3::from(vertex) + offset).to_array()));
+        self.normals.extend(&mesh.normals);
+
+        for (vertex, light) in mesh.lights.iter().enumerate() {
+            let block = mesh.blocks.get(vertex).copied().unwrap_or(Block(0));
+            let color = Vec4::from(registry.get(block).color.map(|channel| channel as f32 / 255.0));
+            // same falloff as the chunk shader
+            let brightness = if bake_light {
+                let light = Vec3::new(light[0], light[1], light[2]).max(Vec3::splat(light[3]));
+                Vec3::splat(0.05) + (Vec3::ONE - 0.05) * light * light
+            } else {
+                Vec3::ONE
+            };
+
+            self.colors.push((color.truncate() * brightness).extend(color.w).to_array());
+            self.blocks.push(block.id());
+        }
+    }
+}
+
+/// Meshes of an exported box, opaque blocks and models apart from translucent blocks.
+#[derive(Debug, Clone, Default)]
+pub struct ExportMesh {
+    pub opaque: ExportPrimitive,
+    pub translucent: ExportPrimitive,
+}
+
+/// Meshes the blocks of a box with the greedy mesher, as if everything around it was air.
+/// Positions are relative to `min`, and with `bake_light` vertex colors are darkened like in game.
+pub fn mesh_box(
+    min: IVec3,
+    size: IVec3,
+    registry: &BlockRegistry,
+    mut chunk_at: impl FnMut(ChunkPos) -> Chunk,
+    bake_light: bool,
+) -> ExportMesh {
+    let max = min + size;
+    let min_chunk = ChunkPos::of_block(min).0;
+    let max_chunk = ChunkPos::of_block(max - 1).0;
+
+    // copies of the chunks with everything outside the box removed, lit on their own
+    let mut chunks = HashMap::new();
+    for z in min_chunk.y..=max_chunk.y {
+        for x in min_chunk.x..=max_chunk.x {
+            let chunk_pos = ChunkPos(IVec2::new(x, z));
+            let chunk = chunk_at(chunk_pos);
+            let origin = IVec3::new(x * CHUNK_SIZE, 0, z * CHUNK_SIZE);
+
+            let mut masked = Chunk::new();
+            for (section_y, section) in chunk.sections.iter().enumerate() {
+                let section = section.read().unwrap();
+                let mut copy = ChunkSection::from_blocks(section.blocks().to_vec()).expect("sections hold 4096 blocks");
+                for index in 0..section.blocks().len() as i32 {
+                    let local = Chunk::coords_by_index(index);
+                    let pos = origin + local + IVec3::Y * section_y as i32 * CHUNK_SIZE;
+                    if pos.cmplt(min).any() || pos.cmpge(max).any() {
+                        copy.set_by_xyz(local.x, local.y, local.z, Block(0));
+                    }
+                }
+                masked.sections.push(Arc::new(RwLock::new(copy)));
+            }
+
+            light_chunk(&masked, registry);
+            chunks.insert(chunk_pos, Arc::new(masked));
+            stitch_chunk_light(&chunks, registry, chunk_pos);
+        }
+    }
+
+    let mut mesh = ExportMesh::default();
+    for (&chunk_pos, chunk) in &chunks {
+        for section_y in 0..chunk.sections.len() {
+            let sections = SectionNeighbors::new(&chunks, chunk_pos, section_y);
+            let meshes = SectionMeshes::build(&GreedyMesher, &sections, LodLevel::FULL, registry);
+            let origin = IVec3::new(chunk_pos.0.x, section_y as i32, chunk_pos.0.y) * CHUNK_SIZE;
+            let offset = (origin - min).as_vec3();
+
+            for section_mesh in [meshes.opaque, meshes.models].into_iter().flatten() {
+                mesh.opaque.append(&section_mesh, offset, registry, bake_light);
+            }
+            if let Some(section_mesh) = meshes.translucent {
+                mesh.translucent.append(&section_mesh, offset, registry, bake_light);
+            }
+        }
+    }
+
+    mesh
+}
+
+/// Binary glTF 2.0 of the mesh, translucent blocks are blended. Block ids are kept in the custom
+/// `_BLOCK` vertex attribute.
+pub fn write_glb(mesh: &ExportMesh) -> Vec<u8> {
+    let mut buffer = vec![];
+    let mut views = vec![];
+    let mut accessors = vec![];
+    let mut primitives = vec![];
+
+    // appends a buffer view and an accessor over it, returning the accessor
+    let mut accessor = |bytes: Vec<u8>, count: usize, component: u32, kind: &str, bounds: String| {
+        let offset = buffer.len();
+        buffer.extend(&bytes);
+        buffer.resize(buffer.len().next_multiple_of(4), 0);
+        views.push(format!(r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{}}}"#, bytes.len()));
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{component},"count":{count},"type":"{kind}"{bounds}}}"#,
+            views.len() - 1
+        ));
+        accessors.len() - 1
+    };
+
+    for (primitive, material) in [(&mesh.opaque, 0), (&mesh.translucent, 1)] {
+        if primitive.indices.is_empty() {
+            continue;
+        }
+
+        let count = primitive.positions.len();
+        let min = primitive.positions.iter().fold(Vec3::MAX, |min, &p| min.min(p.into()));
+        let max = primitive.positions.iter().fold(Vec3::MIN, |max, &p| max.max(p.into()));
+        let bounds = format!(r#","min":[{},{},{}],"max":[{},{},{}]"#, min.x, min.y, min.z, max.x, max.y, max.z);
+
+        let position = accessor(floats(primitive.positions.iter().flatten()), count, 5126, "VEC3", bounds);
+        let normal = accessor(floats(primitive.normals.iter().flatten()), count, 5126, "VEC3", String::new());
+        let color = accessor(floats(primitive.colors.iter().flatten()), count, 5126, "VEC4", String::new());
+        let blocks = primitive.blocks.iter().flat_map(|id| id.to_le_bytes()).collect();
+        let block = accessor(blocks, count, 5123, "SCALAR", String::new());
+        let indices = primitive.indices.iter().flat_map(|index| index.to_le_bytes()).collect();
+        let indices = accessor(indices, primitive.indices.len(), 5125, "SCALAR", String::new());
+
+        primitives.push(format!(
+            r#"{{"attributes":{{"POSITION":{position},"NORMAL":{normal},"COLOR_0":{color},"_BLOCK":{block}}},"indices":{indices},"material":{material}}}"#
+        ));
+    }
+
+    let json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"voxel"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+            r#""meshes":[{{"primitives":[{}]}}],"#,
+            r#""materials":[{{"name":"opaque","pbrMetallicRoughness":{{"metallicFactor":0,"roughnessFactor":1}}}},"#,
+            r#"{{"name":"translucent","alphaMode":"BLEND","pbrMetallicRoughness":{{"metallicFactor":0,"roughnessFactor":1}}}}],"#,
+            r#""buffers":[{{"byteLength":{}}}],"bufferViews":[{}],"accessors":[{}]}}"#
+        ),
+        primitives.join(","),
+        buffer.len(),
+        views.join(","),
+        accessors.join(","),
+    );
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+
+    let mut glb = vec![];
+    glb.extend(b"glTF");
+    glb.extend(2u32.to_le_bytes());
+    glb.extend(((12 + 8 + json.len() + 8 + buffer.len()) as u32).to_le_bytes());
+    glb.extend((json.len() as u32).to_le_bytes());
+    glb.extend(b"JSON");
+    glb.extend(json);
+    glb.extend((buffer.len() as u32).to_le_bytes());
+    glb.extend(b"BIN\0");
+    glb.extend(buffer);
+    glb
+}
+
+fn floats<'a>(values: impl Iterator<Item = &'a f32>) -> Vec<u8> {
+    values.flat_map(|value| value.to_le_bytes()).collect()
+}
+
+/// Wavefront OBJ of the mesh with vertex colors after the positions, which most tools read.
+/// Opaque and translucent blocks are in their own groups.
+pub fn write_obj(mesh: &ExportMesh) -> String {
+    let mut obj = String::from("# exported by voxel\no voxel_export\n");
+    let mut base = 1;
+
+    for (primitive, group) in [(&mesh.opaque, "opaque"), (&mesh.translucent, "translucent")] {
+        if primitive.indices.is_empty() {
+            continue;
+        }
+
+        writeln!(obj, "g {group}").unwrap();
+        for (position, color) in primitive.positions.iter().zip(&primitive.colors) {
+            let [x, y, z] = position;
+            let [r, g, b, _] = color;
+            writeln!(obj, "v {x} {y} {z} {r} {g} {b}").unwrap();
+        }
+        for [x, y, z] in &primitive.normals {
+            writeln!(obj, "vn {x} {y} {z}").unwrap();
+        }
+        for triangle in primitive.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index + base);
+            writeln!(obj, "f {a}//{a} {b}//{b} {c}//{c}").unwrap();
+        }
+
+        base += primitive.positions.len() as u32;
+    }
+
+    obj
+}
+
+/// `voxel export-mesh <out.glb|out.obj> --min <x> <y> <z> --size <x> <y> <z> [--world <dir>]
+/// [--unlit]`, meshes a box of a saved world without opening a window. Chunks which were never
+/// saved are generated. Returns the exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let usage = "usage: voxel export-mesh <out.glb|out.obj> --min <x> <y> <z> --size <x> <y> <z> [--world <dir>] [--unlit]";
+    let mut out = None;
+    let mut min = None;
+    let mut size = None;
+    let mut storage = RegionStorage::default();
+    let mut bake_light = true;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut vector = || -> Option<IVec3> {
+            let mut coord = || args.next()?.parse().ok();
+            Some(IVec3::new(coord()?, coord()?, coord()?))
+        };
+        match arg.as_str() {
+            "--min" => min = vector(),
+            "--size" => size = vector(),
+            "--world" => storage = RegionStorage::new(args.next().cloned().unwrap_or_default()),
+            "--unlit" => bake_light = false,
+            _ => out = Some(Path::new(arg)),
+        }
+    }
+    let (Some(out), Some(min), Some(size)) = (out, min, size) else {
+        eprintln!("{usage}");
+        return 2;
+    };
+    if size.cmplt(IVec3::ONE).any() {
+        eprintln!("the size has to be positive");
+        return 2;
+    }
+
+    let export = || -> Result<(), Box<dyn std::error::Error>> {
+        let registry = BlockRegistry::default();
+        Level::open(&storage, &registry)?;
+        let chunk_at = |chunk_pos| WorldPlugin::load_or_generate_chunk_at(&storage, chunk_pos);
+        let mesh = mesh_box(min, size, &registry, chunk_at, bake_light);
+        if mesh.opaque.indices.is_empty() && mesh.translucent.indices.is_empty() {
+            return Err("the box has no visible blocks".into());
+        }
+
+        match out.extension().and_then(|extension| extension.to_str()) {
+            Some("glb") => fs::write(out, write_glb(&mesh))?,
+            Some("obj") => fs::write(out, write_obj(&mesh))?,
+            _ => return Err("the output has to end with .glb or .obj".into()),
+        }
+
+        let triangles = (mesh.opaque.indices.len() + mesh.translucent.indices.len()) / 3;
+        println!("{}: exported {triangles} triangles", out.display());
+        Ok(())
+    };
+
+    match export() {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("export failed: {err}");
+            1
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::block_registry::BlockRegistry;
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::GreedyMesher;
+use crate::lod::LodLevel;
+use crate::model_mesher::mesh_models;
+use crate::naive_mesher::NaiveMesher;
+use crate::section_neighbors::SectionNeighbors;
+use crate::surface_nets_mesher::SurfaceNetsMesher;
+use crate::translucent_mesher::mesh_translucent;
+use bevy::prelude::{Reflect, ReflectResource, Resource};
+use std::sync::Arc;
+
+/// Turns a section and its neighbors into the mesh of its opaque pass, `None` when there is nothing
+/// to draw. Translucent blocks are meshed separately by [`mesh_translucent`].
+pub trait Mesher: Send + Sync + 'static {
+    fn mesh(&self, sections: &SectionNeighbors, lod: LodLevel, registry: &BlockRegistry) -> Option<ChunkSectionMesh>;
+}
+
+/// Meshes a section is split into, each drawn by its own entity.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MeshPass {
+    /// Opaque cubes, meshed by the [`ChunkMesher`].
+    Opaque,
+    /// Blocks with a model, see [`mesh_models`].
+    Models,
+    /// Translucent blocks, see [`mesh_translucent`].
+    Translucent,
+}
+
+impl MeshPass {
+    pub const ALL: [MeshPass; 3] = [MeshPass::Opaque, MeshPass::Models, MeshPass::Translucent];
+}
+
+/// Meshes of a section for every pass.
+pub struct SectionMeshes {
+    pub opaque: Option<ChunkSectionMesh>,
+    pub models: Option<ChunkSectionMesh>,
+    pub translucent: Option<ChunkSectionMesh>,
+}
+
+impl SectionMeshes {
+    pub fn build(mesher: &dyn Mesher, sections: &SectionNeighbors, lod: LodLevel, registry: &BlockRegistry) -> Self {
+        Self {
+            opaque: mesher.mesh(sections, lod, registry),
+            models: mesh_models(sections, lod, registry),
+            translucent: mesh_translucent(sections, lod, registry),
+        }
+    }
+
+    /// Meshes with the pass they belong to.
+    pub fn into_passes(self) -> [(MeshPass, Option<ChunkSectionMesh>); 3] {
+        [
+            (MeshPass::Opaque, self.opaque),
+            (MeshPass::Models, self.models),
+            (MeshPass::Translucent, self.translucent),
+        ]
+    }
+}
+
+/// Meshers the world can mesh its sections with.
+#[derive(Reflect, Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MesherKind {
+    #[default]
+    Greedy,
+    Naive,
+    SurfaceNets,
+}
+
+impl MesherKind {
+    pub fn build(&self) -> Arc<dyn Mesher> {
+        match self {
+            MesherKind::Greedy => Arc::new(GreedyMesher),
+            MesherKind::Naive => Arc::new(NaiveMesher),
+            MesherKind::SurfaceNets => Arc::new(SurfaceNetsMesher),
+        }
+    }
+}
+
+/// Mesher used by the world to mesh its sections. Every loaded chunk is meshed again when it
+/// changes.
+#[derive(Resource, Clone)]
+pub struct ChunkMesher {
+    kind: MesherKind,
+    mesher: Arc<dyn Mesher>,
+}
+
+impl ChunkMesher {
+    pub fn new(kind: MesherKind) -> Self {
+        Self {
+            kind,
+            mesher: kind.build(),
+        }
+    }
+
+    pub fn kind(&self) -> MesherKind {
+        self.kind
+    }
+
+    pub fn set_kind(&mut self, kind: MesherKind) {
+        *self = Self::new(kind);
+    }
+
+    pub fn mesher(&self) -> Arc<dyn Mesher> {
+        Arc::clone(&self.mesher)
+    }
+}
+
+impl Default for ChunkMesher {
+    fn default() -> Self {
+        Self::new(MesherKind::default())
+    }
+}
+
+/// How the world splits its meshes.
+#[derive(Resource, Reflect, Default, Debug, Copy, Clone, Eq, PartialEq)]
+#[reflect(Resource)]
+pub enum MeshLayout {
+    /// One mesh per chunk section, built by the [`ChunkMesher`].
+    #[default]
+    Sections,
+    /// One greedy mesh per full detail chunk column with side faces merged across sections.
+    /// Downsampled chunks keep using the [`ChunkMesher`] per section.
+    Columns,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::chunk::{CHUNK_SIZE3, ChunkSection};
+    use std::sync::RwLock;
+
+    /// Small deterministic generator for the random sections.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    /// Section with roughly `density` percent of its blocks set to one of the first block ids.
+    fn random_section(random: &mut XorShift, density: u64) -> Arc<RwLock<ChunkSection>> {
+        let blocks = (0..CHUNK_SIZE3)
+            .map(|_| {
+                if random.next() % 100 < density {
+                    Block::from_id(1 + (random.next() % 6) as u16)
+                } else {
+                    Block(0)
+                }
+            })
+            .collect();
+        Arc::new(RwLock::new(ChunkSection::from_blocks(blocks).unwrap()))
+    }
+
+    fn random_neighbors(random: &mut XorShift) -> SectionNeighbors {
+        let density = random.next() % 100;
+        let neighbor = |random: &mut XorShift| random.next().is_multiple_of(2).then(|| random_section(random, density));
+        SectionNeighbors {
+            center: random_section(random, density),
+            up: neighbor(random),
+            down: neighbor(random),
+            north: neighbor(random),
+            south: neighbor(random),
+            east: neighbor(random),
+            west: neighbor(random),
+        }
+    }
+
+    #[test]
+    fn greedy_and_naive_cover_the_same_faces() {
+        let registry = BlockRegistry::default();
+        let mut random = XorShift(0x9E37_79B9_7F4A_7C15);
+
+        for _ in 0..64 {
+            let sections = random_neighbors(&mut random);
+            let lod = LodLevel((random.next() % (LodLevel::MAX.0 as u64 + 1)) as u8);
+
+            let naive = NaiveMesher.mesh(&sections, lod, &registry);
+            let greedy = GreedyMesher.mesh(&sections, lod, &registry);
+            let (naive, greedy) = match (naive, greedy) {
+                (Some(naive), Some(greedy)) => (naive, greedy),
+                (None, None) => continue,
+                _ => panic!("only one mesher skipped the section at {lod:?}"),
+            };
+
+            let (naive_area, greedy_area) = (naive.face_area(), greedy.face_area());
+            assert_eq!(naive_area.len(), greedy_area.len(), "at {lod:?}");
+            for (key, area) in naive_area {
+                assert!((greedy_area[&key] - area).abs() < 1e-3, "{key:?} at {lod:?}");
+            }
+            assert!(greedy.vertices.len() <= naive.vertices.len());
+        }
+    }
+
+    #[test]
+    fn a_lone_block_has_six_faces() {
+        let registry = BlockRegistry::default();
+        let mut blocks = vec![Block(0); CHUNK_SIZE3 as usize];
+        blocks[0] = Block(1);
+        let sections = SectionNeighbors {
+            center: Arc::new(RwLock::new(ChunkSection::from_blocks(blocks).unwrap())),
+            up: None,
+            down: None,
+            north: None,
+            south: None,
+            east: None,
+            west: None,
+        };
+
+        for mesher in [MesherKind::Greedy, MesherKind::Naive] {
+            let mesh = mesher.build().mesh(&sections, LodLevel::FULL, &registry).unwrap();
+            assert_eq!(mesh.vertices.len(), 6 * 4);
+            assert!(mesh.face_area().values().all(|&area| (area - 1.0).abs() < 1e-3));
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::block::Block;
+use crate::block_registry::BlockRegistry;
+use crate::block_shape::{model_faces, ModelFace};
+use crate::chunk::CHUNK_SIZE;
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::{build_section_mesh, padded_section_all};
+use crate::lighting::vertex_light;
+use crate::lod::LodLevel;
+use crate::section_neighbors::SectionNeighbors;
+use bevy::math::IVec3;
+use std::collections::HashMap;
+
+/// Meshes the blocks of a section which aren't cubes from their models, `None` when there are none.
+///
+/// Faces on the border of a block are hidden when the neighbor covers that side completely, faces
+/// inside the block are always kept. Downsampled sections draw these blocks as cubes instead.
+pub fn mesh_models(
+    sections: &SectionNeighbors,
+    lod: LodLevel,
+    registry: &BlockRegistry,
+) -> Option<ChunkSectionMesh> {
+    if !lod.is_full() {
+        return None;
+    }
+    let padded = padded_section_all(sections, lod, registry)?;
+
+    let mut models: HashMap<Block, Vec<ModelFace>> = HashMap::new();
+    let mut vertices = vec![];
+    let mut normals = vec![];
+    let mut lights = vec![];
+    let mut blocks = vec![];
+
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let pos = IVec3::new(x, y, z);
+                let block = padded.block(pos + IVec3::ONE);
+                if !registry.has_model(block) || registry.get(block).translucent {
+                    continue;
+                }
+
+                let faces = models
+                    .entry(block)
+                    .or_insert_with(|| model_faces(&registry.get(block).shape, block.facing()));
+
+                for face in faces.iter() {
+                    let light = match face.border {
+                        Some(border) => {
+                            let facing = pos + IVec3::ONE + border.offset();
+                            if padded.covers(facing, border.opposite()) {
+                                continue;
+                            }
+                            padded.light(facing)
+                        }
+                        None => padded.light(pos + IVec3::ONE),
+                    };
+
+                    for corner in face.corners {
+                        vertices.push((corner + pos.as_vec3()).to_array());
+                        normals.push(face.normal.to_array());
+                        lights.push(vertex_light(light));
+                        blocks.push(block);
+                    }
+                }
+            }
+        }
+    }
+
+    if vertices.is_empty() {
+        return None;
+    }
+
+    Some(build_section_mesh(vertices, normals, lights, 1).with_blocks(blocks))
+}
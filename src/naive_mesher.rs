@@ -0,0 +1,63 @@
+use crate::block_registry::BlockRegistry;
+use crate::chunk::CHUNK_SIZE;
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::{build_section_mesh, padded_section};
+use crate::lod::LodLevel;
+use crate::mesher::Mesher;
+use crate::quad::{Direction, FaceQuad, GreedyQuad};
+use crate::section_neighbors::SectionNeighbors;
+use bevy::math::IVec3;
+
+/// Reference mesher emitting one quad per visible block face.
+///
+/// Far slower than [`crate::greedy_chunk_render_plugin::GreedyMesher`], but simple enough to
+/// compare other meshers against.
+pub struct NaiveMesher;
+
+impl Mesher for NaiveMesher {
+    fn mesh(&self, sections: &SectionNeighbors, lod: LodLevel, registry: &BlockRegistry) -> Option<ChunkSectionMesh> {
+        let padded = padded_section(sections, lod, registry)?;
+        let is_solid = |pos: IVec3| padded.block(pos).is_solid();
+
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut lights = vec![];
+        let mut blocks = vec![];
+
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let pos = IVec3::new(x, y, z);
+                    if !is_solid(pos + IVec3::ONE) {
+                        continue;
+                    }
+
+                    for face_dir in Direction::ALL {
+                        let facing = pos + IVec3::ONE + face_dir.offset();
+                        if padded.covers(facing, face_dir.opposite()) {
+                            continue;
+                        }
+
+                        let (axis_pos, quad_x, quad_y) = face_dir.sample_to_plane(pos);
+                        let face = FaceQuad {
+                            direction: face_dir,
+                            block: padded.block(pos + IVec3::ONE),
+                            axis_pos,
+                            light: padded.light(facing),
+                            quad: GreedyQuad {
+                                x: quad_x as u32,
+                                y: quad_y as u32,
+                                w: 1,
+                                h: 1,
+                            },
+                        };
+                        face.append_vertices(&mut vertices, &mut normals, &mut lights);
+                        blocks.extend([face.block; 4]);
+                    }
+                }
+            }
+        }
+
+        Some(build_section_mesh(vertices, normals, lights, lod.scale()).with_blocks(blocks))
+    }
+}
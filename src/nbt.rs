@@ -0,0 +1,182 @@
+use crate::byte_reader::{ByteReader, Truncated};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+/// Value of Minecraft's Named Binary Tag format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Nbt>),
+    Compound(HashMap<String, Nbt>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Nbt {
+    /// Reads the uncompressed root compound of an NBT document.
+    pub fn parse(bytes: &[u8]) -> Result<Self, NbtError> {
+        let mut reader = Reader { bytes: ByteReader::new(bytes) };
+        let tag = reader.bytes.u8()?;
+        if tag != 10 {
+            return Err(NbtError::Invalid(format!("root tag {tag} isn't a compound")));
+        }
+        reader.string()?;
+
+        reader.payload(tag, 0)
+    }
+
+    /// Entry of a compound, `None` for missing entries and other tags.
+    pub fn get(&self, key: &str) -> Option<&Nbt> {
+        match self {
+            Nbt::Compound(entries) => entries.get(key),
+            _ => None,
+        }
+    }
+
+    /// Integer value of any size.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Nbt::Byte(value) => Some(value as i64),
+            Nbt::Short(value) => Some(value as i64),
+            Nbt::Int(value) => Some(value as i64),
+            Nbt::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Nbt::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Nbt]> {
+        match self {
+            Nbt::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&HashMap<String, Nbt>> {
+        match self {
+            Nbt::Compound(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Nbt::LongArray(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Nesting deeper than this is treated as corrupt rather than overflowing the stack.
+const MAX_DEPTH: usize = 512;
+
+struct Reader<'a> {
+    bytes: ByteReader<'a>,
+}
+
+impl<'a> Reader<'a> {
+    fn len(&mut self) -> Result<usize, NbtError> {
+        let len = self.bytes.i32_be()?;
+        // every element takes at least a byte, longer lengths can only be corrupt
+        if len < 0 || len as usize > self.bytes.remaining() {
+            return Err(NbtError::Invalid(format!("length {len}")));
+        }
+        Ok(len as usize)
+    }
+
+    fn string(&mut self) -> Result<String, NbtError> {
+        let len = u16::from_be_bytes(self.bytes.array()?) as usize;
+        // modified UTF-8 only differs for nul and supplementary characters
+        Ok(String::from_utf8_lossy(self.bytes.take(len)?).into_owned())
+    }
+
+    fn payload(&mut self, tag: u8, depth: usize) -> Result<Nbt, NbtError> {
+        if depth > MAX_DEPTH {
+            return Err(NbtError::Invalid("nested too deep".into()));
+        }
+
+        Ok(match tag {
+            1 => Nbt::Byte(self.bytes.u8()? as i8),
+            2 => Nbt::Short(i16::from_be_bytes(self.bytes.array()?)),
+            3 => Nbt::Int(self.bytes.i32_be()?),
+            4 => Nbt::Long(i64::from_be_bytes(self.bytes.array()?)),
+            5 => Nbt::Float(f32::from_be_bytes(self.bytes.array()?)),
+            6 => Nbt::Double(f64::from_be_bytes(self.bytes.array()?)),
+            7 => {
+                let len = self.len()?;
+                Nbt::ByteArray(self.bytes.take(len)?.iter().map(|&byte| byte as i8).collect())
+            }
+            8 => Nbt::String(self.string()?),
+            9 => {
+                let element = self.bytes.u8()?;
+                let len = self.len()?;
+                let mut values = Vec::with_capacity(len);
+                for _ in 0..len {
+                    values.push(self.payload(element, depth + 1)?);
+                }
+                Nbt::List(values)
+            }
+            10 => {
+                let mut entries = HashMap::new();
+                loop {
+                    let tag = self.bytes.u8()?;
+                    if tag == 0 {
+                        break;
+                    }
+                    let name = self.string()?;
+                    entries.insert(name, self.payload(tag, depth + 1)?);
+                }
+                Nbt::Compound(entries)
+            }
+            11 => {
+                let len = self.len()?;
+                let bytes = self.bytes.take(len.checked_mul(4).ok_or(NbtError::Truncated)?)?;
+                Nbt::IntArray(bytes.chunks_exact(4).map(|int| i32::from_be_bytes(int.try_into().unwrap())).collect())
+            }
+            12 => {
+                let len = self.len()?;
+                let bytes = self.bytes.take(len.checked_mul(8).ok_or(NbtError::Truncated)?)?;
+                Nbt::LongArray(bytes.chunks_exact(8).map(|long| i64::from_be_bytes(long.try_into().unwrap())).collect())
+            }
+            _ => return Err(NbtError::Invalid(format!("unknown tag {tag}"))),
+        })
+    }
+}
+
+/// Reason an NBT document couldn't be read.
+#[derive(Debug)]
+pub enum NbtError {
+    Truncated,
+    Invalid(String),
+}
+
+impl Display for NbtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NbtError::Truncated => write!(f, "truncated NBT"),
+            NbtError::Invalid(message) => write!(f, "invalid NBT: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for NbtError {}
+
+impl From<Truncated> for NbtError {
+    fn from(_: Truncated) -> Self {
+        NbtError::Truncated
+    }
+}
@@ -0,0 +1,434 @@
+use crate::block_registry::BlockRegistry;
+use crate::chunk::{CHUNK_SIZE, Chunk, ChunkPos};
+use crate::voxel_body::VoxelBody;
+use crate::world::World;
+use bevy::app::{App, Plugin, Update};
+use bevy::log::warn;
+use bevy::math::{IVec3, Vec3, Vec3Swizzles};
+use bevy::prelude::{Commands, Component, DetectChanges, Entity, Query, Ref, Res, Transform};
+use bevy::tasks::{Task, block_on, poll_once};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fmt;
+use std::sync::Arc;
+
+/// Costs are doubled so half steps stay whole numbers.
+const WALK_COST: u32 = 2;
+const GAP_JUMP_COST: u32 = 5;
+
+/// Horizontal distance to the center of a cell at which a follower moves on to the next one.
+const ARRIVAL_DISTANCE: f32 = 0.2;
+
+const HORIZONTAL: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+/// Size and abilities of the agent a path is searched for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathSettings {
+    /// Blocks of clearance the agent needs above the ground.
+    pub height: i32,
+    /// Highest ledge the agent climbs, in blocks.
+    pub max_step_up: i32,
+    /// Deepest drop the agent walks off, in blocks.
+    pub max_drop: i32,
+    /// Whether the agent jumps over gaps one block wide.
+    pub jump_gaps: bool,
+    /// Cells expanded before the search gives up.
+    pub max_nodes: usize,
+}
+
+impl Default for PathSettings {
+    fn default() -> Self {
+        Self {
+            height: 2,
+            max_step_up: 1,
+            max_drop: 3,
+            jump_gaps: true,
+            max_nodes: 10_000,
+        }
+    }
+}
+
+/// Reason no path was found.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PathError {
+    /// The agent can't stand at the start or at the goal.
+    NotWalkable(IVec3),
+    /// Every reachable cell was searched without reaching the goal.
+    Unreachable,
+    /// [`PathSettings::max_nodes`] cells were searched without reaching the goal.
+    BudgetExhausted,
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::NotWalkable(pos) => write!(f, "nothing to stand on at {pos}"),
+            PathError::Unreachable => write!(f, "the goal can't be reached"),
+            PathError::BudgetExhausted => write!(f, "gave up searching for a path"),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Loaded chunks of a world at one moment, searched off the main thread. Blocks changing while
+/// a search runs may or may not be seen by it.
+#[derive(Debug, Clone)]
+pub struct PathGrid {
+    chunks: HashMap<ChunkPos, Arc<Chunk>>,
+    registry: BlockRegistry,
+}
+
+impl PathGrid {
+    pub fn new(chunks: HashMap<ChunkPos, Arc<Chunk>>, registry: BlockRegistry) -> Self {
+        Self { chunks, registry }
+    }
+
+    /// Whether agents collide with the block, `None` for chunks which aren't loaded. There's
+    /// nothing above or below the world.
+    pub fn is_solid(&self, pos: IVec3) -> Option<bool> {
+        let chunk = self.chunks.get(&ChunkPos::of_block(pos))?;
+        if pos.y < 0 || pos.y >= chunk.height() {
+            return Some(false);
+        }
+
+        let local = IVec3::new(pos.x.rem_euclid(CHUNK_SIZE), pos.y, pos.z.rem_euclid(CHUNK_SIZE));
+        let block = chunk.get(local)?;
+        Some(self.registry.get(block).solid)
+    }
+
+    fn is_clear(&self, pos: IVec3, height: i32) -> bool {
+        (0..height).all(|y| self.is_solid(pos + IVec3::Y * y) == Some(false))
+    }
+
+    /// Whether an agent of the given height can stand with its feet in the cell.
+    pub fn is_walkable(&self, pos: IVec3, height: i32) -> bool {
+        self.is_solid(pos - IVec3::Y) == Some(true) && self.is_clear(pos, height)
+    }
+
+    /// Cells reachable with one move from a walkable cell, with their cost.
+    fn neighbors(&self, pos: IVec3, settings: &PathSettings) -> Vec<(IVec3, u32)> {
+        let height = settings.height;
+        let mut neighbors = vec![];
+
+        for direction in HORIZONTAL {
+            let next = pos + direction;
+            if self.is_walkable(next, height) {
+                neighbors.push((next, WALK_COST));
+                continue;
+            }
+
+            // climbing needs room above the head before moving over
+            for up in 1..=settings.max_step_up {
+                if !self.is_clear(pos + IVec3::Y * (height + up - 1), 1) {
+                    break;
+                }
+                if self.is_walkable(next + IVec3::Y * up, height) {
+                    neighbors.push((next + IVec3::Y * up, WALK_COST + up as u32));
+                    break;
+                }
+            }
+
+            if !self.is_clear(next, height) {
+                continue;
+            }
+            for down in 1..=settings.max_drop {
+                let below = next - IVec3::Y * down;
+                if self.is_walkable(below, height) {
+                    neighbors.push((below, WALK_COST + down as u32));
+                    break;
+                }
+                if self.is_solid(below) != Some(false) {
+                    break;
+                }
+            }
+
+            // jumping needs an extra block of clearance over the gap
+            let landing = next + direction;
+            if settings.jump_gaps
+                && self.is_solid(next - IVec3::Y) == Some(false)
+                && self.is_clear(next, height + 1)
+                && self.is_clear(pos + IVec3::Y * height, 1)
+                && self.is_clear(landing + IVec3::Y * height, 1)
+                && self.is_walkable(landing, height)
+            {
+                neighbors.push((landing, GAP_JUMP_COST));
+            }
+        }
+
+        neighbors
+    }
+}
+
+/// Lower bound of the cost between two cells, moves cost at least [`WALK_COST`] per block
+/// horizontally plus one per block vertically.
+fn heuristic(from: IVec3, to: IVec3) -> u32 {
+    let distance = (to - from).abs();
+    (distance.x + distance.z) as u32 * WALK_COST + distance.y as u32
+}
+
+/// Shortest walk between two cells with A*, as the cells the feet are in from `start` to `goal`
+/// included.
+pub fn find_path(grid: &PathGrid, start: IVec3, goal: IVec3, settings: &PathSettings) -> Result<Vec<IVec3>, PathError> {
+    for pos in [start, goal] {
+        if !grid.is_walkable(pos, settings.height) {
+            return Err(PathError::NotWalkable(pos));
+        }
+    }
+
+    let mut open = BinaryHeap::from([(Reverse(heuristic(start, goal)), Reverse(0), start.to_array())]);
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut came_from = HashMap::new();
+    let mut expanded = 0;
+
+    while let Some((_, Reverse(cost), pos)) = open.pop() {
+        let pos = IVec3::from_array(pos);
+        if pos == goal {
+            let mut path = vec![goal];
+            while let Some(&previous) = came_from.get(path.last().unwrap()) {
+                path.push(previous);
+            }
+            path.reverse();
+            return Ok(path);
+        }
+        // already reached cheaper
+        if costs.get(&pos).is_some_and(|&best| best < cost) {
+            continue;
+        }
+
+        expanded += 1;
+        if expanded > settings.max_nodes {
+            return Err(PathError::BudgetExhausted);
+        }
+
+        for (next, step_cost) in grid.neighbors(pos, settings) {
+            let next_cost = cost + step_cost;
+            if costs.get(&next).is_some_and(|&best| best <= next_cost) {
+                continue;
+            }
+            costs.insert(next, next_cost);
+            came_from.insert(next, pos);
+            open.push((Reverse(next_cost + heuristic(next, goal)), Reverse(next_cost), next.to_array()));
+        }
+    }
+
+    Err(PathError::Unreachable)
+}
+
+/// Asks [`PathfindingPlugin`] for a path, replaced by a [`Path`] once it's searched.
+#[derive(Component, Debug, Clone)]
+pub struct PathRequest {
+    pub start: IVec3,
+    pub goal: IVec3,
+    pub settings: PathSettings,
+}
+
+/// Result of a [`PathRequest`].
+#[derive(Component, Debug, Clone)]
+pub struct Path(pub Result<Vec<IVec3>, PathError>);
+
+/// Walks a [`VoxelBody`] along its [`Path`], jumping onto ledges and over gaps.
+#[derive(Component, Debug, Clone)]
+pub struct PathFollower {
+    /// Walking speed in blocks per second.
+    pub speed: f32,
+    pub jump_speed: f32,
+    /// Index of the cell of the path walked towards.
+    next: usize,
+}
+
+impl Default for PathFollower {
+    fn default() -> Self {
+        Self {
+            speed: 4.0,
+            jump_speed: 9.0,
+            next: 0,
+        }
+    }
+}
+
+/// Search of a [`PathRequest`] in progress.
+#[derive(Component)]
+struct PathTask(Task<Result<Vec<IVec3>, PathError>>);
+
+pub struct PathfindingPlugin;
+
+impl Plugin for PathfindingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (Self::start_path_tasks, Self::join_path_tasks, Self::follow_paths));
+    }
+}
+
+impl PathfindingPlugin {
+    fn start_path_tasks(
+        mut commands: Commands,
+        requests: Query<(Entity, &PathRequest)>,
+        world: Res<World>,
+    ) {
+        for (entity, request) in requests.iter() {
+            let task = world.find_path(request.start, request.goal, request.settings);
+            commands.entity(entity).remove::<(PathRequest, Path)>().insert(PathTask(task));
+        }
+    }
+
+    fn join_path_tasks(mut commands: Commands, mut tasks: Query<(Entity, &mut PathTask)>) {
+        for (entity, mut task) in tasks.iter_mut() {
+            if let Some(path) = block_on(poll_once(&mut task.0)) {
+                if let Err(err) = &path {
+                    warn!("No path for {entity}: {err}");
+                }
+                commands.entity(entity).remove::<PathTask>().insert(Path(path));
+            }
+        }
+    }
+
+    /// Steers followers towards the center of their next cell, stopping at the end of the path.
+    pub fn follow_paths(mut followers: Query<(&mut PathFollower, Ref<Path>, &mut VoxelBody, &Transform)>) {
+        for (mut follower, path, mut body, transform) in followers.iter_mut() {
+            if path.is_changed() {
+                follower.next = 0;
+            }
+
+            let feet = transform.translation - Vec3::Y * body.half_size.y;
+            let Some(&target) = path.0.as_ref().ok().and_then(|cells| cells.get(follower.next)) else {
+                body.velocity.x = 0.0;
+                body.velocity.z = 0.0;
+                continue;
+            };
+
+            let offset = (target.as_vec3() + Vec3::new(0.5, 0.0, 0.5) - feet).xz();
+            if offset.length() < ARRIVAL_DISTANCE && body.grounded {
+                follower.next += 1;
+                continue;
+            }
+
+            let velocity = offset.normalize_or_zero() * follower.speed;
+            body.velocity.x = velocity.x;
+            body.velocity.z = velocity.y;
+            // ledges are higher than the feet, gaps are two cells away
+            if body.grounded && (target.y as f32 > feet.y + 0.5 || offset.length() > 1.5) {
+                body.velocity.y = follower.jump_speed;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Grid of the two chunks wide square around the origin, stone where `solid` says so below
+    /// y 8.
+    fn grid(solid: impl Fn(IVec3) -> bool) -> PathGrid {
+        let registry = BlockRegistry::default();
+        let stone = registry.by_name("voxel:stone").unwrap();
+        let blocks = (-16..16)
+            .flat_map(|x| (0..8).flat_map(move |y| (-16..16).map(move |z| IVec3::new(x, y, z))))
+            .filter(|&pos| solid(pos))
+            .map(|pos| (pos, stone));
+        let world = World::with_blocks(1, blocks);
+        PathGrid::new(world.loaded_chunks.clone(), world.registry.clone())
+    }
+
+    fn floor(pos: IVec3) -> bool {
+        pos.y == 0
+    }
+
+    /// Checks every step of the path is a move the agent can make, returning the cost of the path.
+    fn path_cost(grid: &PathGrid, path: &[IVec3], settings: &PathSettings) -> u32 {
+        path.windows(2)
+            .map(|step| {
+                let (_, cost) = grid
+                    .neighbors(step[0], settings)
+                    .into_iter()
+                    .find(|&(next, _)| next == step[1])
+                    .unwrap_or_else(|| panic!("no move from {} to {}", step[0], step[1]));
+                cost
+            })
+            .sum()
+    }
+
+    #[test]
+    fn walks_around_walls_the_shortest_way() {
+        let grid = grid(|pos| floor(pos) || (pos.x == 3 && pos.y <= 2 && (-3..=3).contains(&pos.z)));
+        let settings = PathSettings::default();
+
+        let path = find_path(&grid, IVec3::new(0, 1, 0), IVec3::new(6, 1, 0), &settings).unwrap();
+        assert_eq!(path.first(), Some(&IVec3::new(0, 1, 0)));
+        assert_eq!(path.last(), Some(&IVec3::new(6, 1, 0)));
+        // six blocks across and four out and back around the wall
+        assert_eq!(path.len(), 15);
+        assert_eq!(path_cost(&grid, &path, &settings), 14 * WALK_COST);
+    }
+
+    #[test]
+    fn climbs_at_most_the_step_up_limit() {
+        let ridge = |height: i32| move |pos: IVec3| floor(pos) || (pos.x == 2 && pos.y <= height);
+        let (start, goal) = (IVec3::new(0, 1, 0), IVec3::new(4, 1, 0));
+
+        let path = find_path(&grid(ridge(1)), start, goal, &PathSettings::default()).unwrap();
+        assert!(path.contains(&IVec3::new(2, 2, 0)));
+        assert_eq!(path.len(), 5);
+
+        assert_eq!(find_path(&grid(ridge(2)), start, goal, &PathSettings::default()), Err(PathError::Unreachable));
+        let climber = PathSettings { max_step_up: 2, ..PathSettings::default() };
+        assert!(find_path(&grid(ridge(2)), start, goal, &climber).unwrap().contains(&IVec3::new(2, 3, 0)));
+
+        // climbing needs headroom above the agent before moving over
+        let low_ceiling = grid(|pos| ridge(1)(pos) || (pos.x <= 1 && pos.y == 3));
+        assert_eq!(find_path(&low_ceiling, start, goal, &PathSettings::default()), Err(PathError::Unreachable));
+    }
+
+    #[test]
+    fn drops_at_most_the_drop_limit() {
+        let plateau = grid(|pos| floor(pos) || (pos.x <= 0 && pos.y <= 3));
+        let (start, goal) = (IVec3::new(-1, 4, 0), IVec3::new(3, 1, 0));
+
+        let path = find_path(&plateau, start, goal, &PathSettings::default()).unwrap();
+        assert!(path.windows(2).any(|step| step == [IVec3::new(0, 4, 0), IVec3::new(1, 1, 0)]));
+
+        let careful = PathSettings { max_drop: 2, ..PathSettings::default() };
+        assert_eq!(find_path(&plateau, start, goal, &careful), Err(PathError::Unreachable));
+    }
+
+    #[test]
+    fn jumps_gaps_with_headroom() {
+        let trench = |pos: IVec3| floor(pos) && pos.x != 3;
+        let (start, goal) = (IVec3::new(0, 1, 0), IVec3::new(6, 1, 0));
+
+        let path = find_path(&grid(trench), start, goal, &PathSettings::default()).unwrap();
+        assert!(path.windows(2).any(|step| step == [IVec3::new(2, 1, 0), IVec3::new(4, 1, 0)]));
+        assert_eq!(path.len(), 6);
+
+        let walker = PathSettings { jump_gaps: false, ..PathSettings::default() };
+        assert_eq!(find_path(&grid(trench), start, goal, &walker), Err(PathError::Unreachable));
+
+        // a ceiling at head height over the gap leaves no room for the jump
+        let covered = grid(|pos| trench(pos) || (pos.x == 3 && pos.y == 3));
+        assert_eq!(find_path(&covered, start, goal, &PathSettings::default()), Err(PathError::Unreachable));
+        let small = PathSettings { height: 1, ..PathSettings::default() };
+        assert!(find_path(&covered, start, goal, &small).is_ok());
+    }
+
+    #[test]
+    fn tells_unreachable_goals_from_giving_up() {
+        let walled_in = |pos: IVec3| {
+            let offset = (pos - IVec3::new(8, 0, 8)).abs();
+            floor(pos) || (offset.x.max(offset.z) == 2 && pos.y <= 2)
+        };
+        let grid = grid(walled_in);
+        let (start, goal) = (IVec3::new(-8, 1, -8), IVec3::new(8, 1, 8));
+
+        assert_eq!(find_path(&grid, start, goal, &PathSettings::default()), Err(PathError::Unreachable));
+        let impatient = PathSettings { max_nodes: 20, ..PathSettings::default() };
+        assert_eq!(find_path(&grid, start, goal, &impatient), Err(PathError::BudgetExhausted));
+        assert_eq!(find_path(&grid, start, IVec3::new(12, 1, 12), &impatient), Err(PathError::BudgetExhausted));
+        assert!(find_path(&grid, start, IVec3::new(12, 1, 12), &PathSettings::default()).is_ok());
+
+        assert_eq!(
+            find_path(&grid, IVec3::new(0, 3, 0), goal, &PathSettings::default()),
+            Err(PathError::NotWalkable(IVec3::new(0, 3, 0)))
+        );
+    }
+}
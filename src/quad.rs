@@ -1,7 +1,9 @@
+use crate::block::Block;
+use crate::lighting::{vertex_light, Light};
 use bevy::math::IVec3;
 
 // based on https://github.com/TanTanDev/binary_greedy_mesher_demo/blob/main/src/quad.rs
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Direction {
     Left = 0,
     Right,
@@ -12,6 +14,47 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::Left,
+        Direction::Right,
+        Direction::Down,
+        Direction::Up,
+        Direction::Back,
+        Direction::Forward,
+    ];
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::Forward => Direction::Back,
+            Direction::Back => Direction::Forward,
+        }
+    }
+
+    /// Offset to the neighboring block this face looks at.
+    pub fn offset(&self) -> IVec3 {
+        match self {
+            Direction::Up => IVec3::Y,
+            Direction::Down => IVec3::NEG_Y,
+            Direction::Left => IVec3::NEG_X,
+            Direction::Right => IVec3::X,
+            Direction::Forward => IVec3::NEG_Z,
+            Direction::Back => IVec3::Z,
+        }
+    }
+
+    /// Inverse of [`Direction::world_to_sample`], splits a position into (offset, x, y).
+    pub fn sample_to_plane(&self, pos: IVec3) -> (i32, i32, i32) {
+        match self {
+            Direction::Up | Direction::Down => (pos.y, pos.x, pos.z),
+            Direction::Left | Direction::Right => (pos.x, pos.z, pos.y),
+            Direction::Forward | Direction::Back => (pos.z, pos.x, pos.y),
+        }
+    }
+
     pub fn world_to_sample(&self, offset: i32, x: i32, y: i32) -> IVec3 {
         match self {
             Direction::Up => IVec3::new(x, offset, y),
@@ -54,6 +97,30 @@ pub struct GreedyQuad {
     pub h: u32,
 }
 
+/// Greedy quad together with the face it belongs to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FaceQuad {
+    pub direction: Direction,
+    pub block: Block,
+    pub axis_pos: i32,
+    /// Light in front of the face.
+    pub light: Light,
+    pub quad: GreedyQuad,
+}
+
+impl FaceQuad {
+    pub fn append_vertices(
+        &self,
+        vertices: &mut Vec<[f32; 3]>,
+        normals: &mut Vec<[f32; 3]>,
+        lights: &mut Vec<[f32; 4]>,
+    ) {
+        self.quad
+            .append_vertices(vertices, normals, self.direction, self.axis_pos);
+        lights.extend([vertex_light(self.light); 4]);
+    }
+}
+
 impl GreedyQuad {
     pub fn append_vertices(
         &self,
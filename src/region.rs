@@ -0,0 +1,503 @@
+use crate::block::Block;
+use crate::chunk::{CHUNK_SIZE3, Chunk, ChunkPos, ChunkSection};
+use bevy::math::IVec2;
+use bevy::prelude::Resource;
+use bevy::tasks::{IoTaskPool, Task};
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Width and depth of a region in chunks.
+pub const REGION_SIZE: i32 = 32;
+const REGION_CHUNKS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+
+const MAGIC: &[u8; 4] = b"VXRG";
+const VERSION: u32 = 1;
+/// Magic, version and an offset and length per chunk.
+const HEADER_LEN: usize = 8 + REGION_CHUNKS * 8;
+
+/// Region of 32x32 chunks sharing one file.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct RegionPos(pub IVec2);
+
+impl RegionPos {
+    /// Region a chunk belongs to.
+    pub fn of_chunk(pos: ChunkPos) -> Self {
+        Self(pos.0.div_euclid(IVec2::splat(REGION_SIZE)))
+    }
+
+    fn file_name(&self) -> String {
+        format!("r.{}.{}.vxr", self.0.x, self.0.y)
+    }
+}
+
+/// Slot of a chunk in the header of its region file.
+fn chunk_slot(pos: ChunkPos) -> usize {
+    let local = pos.0.rem_euclid(IVec2::splat(REGION_SIZE));
+    (local.x + local.y * REGION_SIZE) as usize
+}
+
+/// Directory of a saved world, chunks are loaded from and saved to the region files in its
+/// `region` directory.
+///
+/// A region file starts with a header holding the offset and length of every chunk in it, followed
+/// by the chunk payloads: the amount of sections and every section's blocks compressed with zstd.
+/// Light isn't stored, it depends on the neighbors and gets computed again on load.
+#[derive(Resource, Debug, Clone)]
+pub struct RegionStorage {
+    dir: PathBuf,
+    /// How often modified chunks get saved while they stay loaded.
+    pub autosave_interval: Duration,
+    /// Held while a region file gets rewritten so saves don't drop each other's chunks.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl Default for RegionStorage {
+    fn default() -> Self {
+        Self::new("world")
+    }
+}
+
+impl RegionStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            autosave_interval: Duration::from_secs(30),
+            write_lock: Arc::default(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn region_dir(&self) -> PathBuf {
+        self.dir.join("region")
+    }
+
+    fn region_path(&self, region: RegionPos) -> PathBuf {
+        self.region_dir().join(region.file_name())
+    }
+
+    /// Reads a chunk from its region file, `None` when it was never saved.
+    pub fn load_chunk(&self, pos: ChunkPos) -> io::Result<Option<Chunk>> {
+        let mut file = match fs::File::open(self.region_path(RegionPos::of_chunk(pos))) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let table = ChunkTable::read(&mut file)?;
+        let (offset, len) = table.slots[chunk_slot(pos)];
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let mut payload = vec![0; len as usize];
+        file.seek(SeekFrom::Start(offset as u64))?;
+        file.read_exact(&mut payload)
+            .map_err(|_| invalid_data("chunk outside of the region file"))?;
+        decode_chunk(&payload).map(Some)
+    }
+
+    /// Writes chunks into their region files, replacing what was saved for them before. Only the
+    /// given chunks are encoded, the other chunks of their regions are copied as they are.
+    ///
+    /// Every region file is written next to the old one and renamed over it once complete, so a
+    /// crash leaves either the old or the new file behind.
+    pub fn save_chunks(&self, chunks: &[(ChunkPos, Arc<Chunk>)]) -> io::Result<()> {
+        // chunks are encoded under the lock too, so the last save to finish has the latest blocks
+        let _guard = self.write_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut regions: HashMap<RegionPos, Vec<(usize, Vec<u8>)>> = HashMap::new();
+        for (pos, chunk) in chunks {
+            regions
+                .entry(RegionPos::of_chunk(*pos))
+                .or_default()
+                .push((chunk_slot(*pos), encode_chunk(chunk)));
+        }
+
+        fs::create_dir_all(self.region_dir())?;
+
+        for (region, payloads) in regions {
+            let path = self.region_path(region);
+            let mut file = read_region(&path)?.unwrap_or_default();
+            for (slot, payload) in payloads {
+                file.chunks[slot] = Some(payload);
+            }
+
+            write_atomically(&path, &file.to_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites every region file with the ids of its blocks changed by `remap`, variants and
+    /// orientations are kept.
+    ///
+    /// The new files are written next to the old ones and only replace them in
+    /// [`RegionStorage::finish_migration`], so an interrupted migration can start over from the
+    /// untouched files.
+    pub fn migrate_regions(&self, remap: impl Fn(u16) -> u16) -> io::Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.discard_migration()?;
+
+        for path in self.files_with_extension("vxr")? {
+            let Some(mut file) = read_region(&path)? else {
+                continue;
+            };
+
+            for payload in file.chunks.iter_mut().flatten() {
+                let mut sections = decode_sections(payload)?;
+                for block in sections.iter_mut().flatten() {
+                    block.set_id(remap(block.id()));
+                }
+                *payload = encode_sections(sections.iter().map(Vec::as_slice));
+            }
+
+            write_atomically(&path.with_extension("vxr.migrated"), &file.to_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the region files with the ones written by [`RegionStorage::migrate_regions`].
+    pub fn finish_migration(&self) -> io::Result<()> {
+        for path in self.files_with_extension("migrated")? {
+            fs::rename(&path, path.with_extension(""))?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes region files left behind by an unfinished [`RegionStorage::migrate_regions`].
+    pub fn discard_migration(&self) -> io::Result<()> {
+        for path in self.files_with_extension("migrated")? {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    fn files_with_extension(&self, extension: &str) -> io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(self.region_dir()) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+
+        let mut paths = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|found| found == extension) {
+                paths.push(path);
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Saves chunks on the [`IoTaskPool`].
+    pub fn spawn_save(&self, chunks: Vec<(ChunkPos, Arc<Chunk>)>) -> Task<io::Result<()>> {
+        let storage = self.clone();
+        IoTaskPool::get().spawn(async move { storage.save_chunks(&chunks) })
+    }
+}
+
+/// Offset and length of every chunk of a region file by slot, zero length for missing chunks.
+struct ChunkTable {
+    slots: Vec<(u32, u32)>,
+}
+
+impl Default for ChunkTable {
+    fn default() -> Self {
+        Self {
+            slots: vec![(0, 0); REGION_CHUNKS],
+        }
+    }
+}
+
+impl ChunkTable {
+    /// Reads the header at the start of a region file.
+    fn read(file: &mut fs::File) -> io::Result<Self> {
+        let mut header = vec![0; HEADER_LEN];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)
+            .map_err(|_| invalid_data("not a region file"))?;
+        Self::from_bytes(&header)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[0..4] != MAGIC {
+            return Err(invalid_data("not a region file"));
+        }
+        let version = read_u32(bytes, 4);
+        if version != VERSION {
+            return Err(invalid_data(format!("unsupported region version {version}")));
+        }
+
+        let slots = (0..REGION_CHUNKS)
+            .map(|slot| (read_u32(bytes, 8 + slot * 8), read_u32(bytes, 12 + slot * 8)))
+            .collect();
+        Ok(Self { slots })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        for &(offset, len) in &self.slots {
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(&len.to_le_bytes());
+        }
+
+        header
+    }
+}
+
+/// Chunk payloads of a region file by slot.
+struct RegionFile {
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        Self {
+            chunks: vec![None; REGION_CHUNKS],
+        }
+    }
+}
+
+impl RegionFile {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut table = ChunkTable::default();
+        let mut payloads = vec![];
+        for (slot, chunk) in table.slots.iter_mut().zip(&self.chunks) {
+            if let Some(payload) = chunk {
+                *slot = ((HEADER_LEN + payloads.len()) as u32, payload.len() as u32);
+                payloads.extend_from_slice(payload);
+            }
+        }
+
+        let mut bytes = table.to_bytes();
+        bytes.extend_from_slice(&payloads);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let table = ChunkTable::from_bytes(bytes)?;
+        let mut file = Self::default();
+        for (chunk, &(offset, len)) in file.chunks.iter_mut().zip(&table.slots) {
+            let (offset, len) = (offset as usize, len as usize);
+            if len == 0 {
+                continue;
+            }
+
+            let payload = bytes
+                .get(offset..offset + len)
+                .ok_or_else(|| invalid_data("chunk outside of the region file"))?;
+            *chunk = Some(payload.to_vec());
+        }
+
+        Ok(file)
+    }
+}
+
+/// Region file at a path, `None` when it doesn't exist yet.
+fn read_region(path: &Path) -> io::Result<Option<RegionFile>> {
+    match fs::read(path) {
+        Ok(bytes) => RegionFile::from_bytes(&bytes).map(Some),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes a file next to `path` and renames it over `path` once complete.
+pub fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut tmp = fs::File::create(&tmp_path)?;
+    tmp.write_all(bytes)?;
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let sections: Vec<_> = chunk.sections.iter().map(|section| section.read().unwrap()).collect();
+    encode_sections(sections.iter().map(|section| section.blocks()))
+}
+
+fn encode_sections<'a>(sections: impl ExactSizeIterator<Item = &'a [Block]>) -> Vec<u8> {
+    let mut payload = vec![];
+    payload.extend_from_slice(&(sections.len() as u32).to_le_bytes());
+
+    for blocks in sections {
+        let raw: Vec<u8> = blocks.iter().flat_map(|block| block.0.to_le_bytes()).collect();
+        let compressed = compress_to_vec(raw.as_slice(), CompressionLevel::Fastest);
+
+        payload.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        payload.extend_from_slice(&compressed);
+    }
+
+    payload
+}
+
+fn decode_chunk(payload: &[u8]) -> io::Result<Chunk> {
+    let mut chunk = Chunk::new();
+    for blocks in decode_sections(payload)? {
+        let section = ChunkSection::from_blocks(blocks).ok_or_else(|| invalid_data("section of the wrong size"))?;
+        chunk.sections.push(Arc::new(RwLock::new(section)));
+    }
+
+    Ok(chunk)
+}
+
+fn decode_sections(payload: &[u8]) -> io::Result<Vec<Vec<Block>>> {
+    let truncated = || invalid_data("truncated chunk");
+    let mut sections = vec![];
+    let count = payload.get(0..4).ok_or_else(truncated)?;
+    let count = u32::from_le_bytes(count.try_into().unwrap());
+
+    let mut cursor = 4;
+    for _ in 0..count {
+        let len = payload.get(cursor..cursor + 4).ok_or_else(truncated)?;
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let compressed = payload.get(cursor + 4..cursor + 4 + len).ok_or_else(truncated)?;
+        cursor += 4 + len;
+
+        let mut raw = Vec::with_capacity(CHUNK_SIZE3 as usize * 2);
+        StreamingDecoder::new(compressed)
+            .map_err(invalid_data)?
+            .read_to_end(&mut raw)?;
+
+        sections.push(
+            raw.chunks_exact(2)
+                .map(|bytes| Block(u16::from_le_bytes([bytes[0], bytes[1]])))
+                .collect(),
+        );
+    }
+
+    Ok(sections)
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+pub(crate) fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Storage in an empty directory of its own.
+    fn empty_storage(name: &str) -> RegionStorage {
+        let dir = std::env::temp_dir().join(format!("voxel-region-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        RegionStorage::new(dir)
+    }
+
+    /// Chunk of two sections whose blocks count up from `first`.
+    fn counting_chunk(first: u16) -> Arc<Chunk> {
+        let mut chunk = Chunk::new();
+        for section in 0..2 {
+            let blocks = (0..CHUNK_SIZE3 as u16).map(|index| Block::from_id(first + section + index % 7)).collect();
+            chunk.sections.push(Arc::new(RwLock::new(ChunkSection::from_blocks(blocks).unwrap())));
+        }
+        Arc::new(chunk)
+    }
+
+    fn blocks(chunk: &Chunk) -> Vec<Block> {
+        chunk
+            .sections
+            .iter()
+            .flat_map(|section| section.read().unwrap().blocks().to_vec())
+            .collect()
+    }
+
+    fn assert_saved(storage: &RegionStorage, pos: ChunkPos, chunk: &Chunk) {
+        let loaded = storage.load_chunk(pos).unwrap().expect("chunk was saved");
+        assert_eq!(blocks(&loaded), blocks(chunk), "chunk {}", pos.0);
+    }
+
+    #[test]
+    fn saving_a_chunk_keeps_the_others_of_its_region() {
+        let storage = empty_storage("keeps");
+        let chunks: Vec<_> = (0..3).map(|x| (ChunkPos(IVec2::new(x, -1)), counting_chunk(x as u16 * 10))).collect();
+        storage.save_chunks(&chunks).unwrap();
+
+        let changed = (chunks[1].0, counting_chunk(100));
+        storage.save_chunks(std::slice::from_ref(&changed)).unwrap();
+
+        assert_saved(&storage, chunks[0].0, &chunks[0].1);
+        assert_saved(&storage, changed.0, &changed.1);
+        assert_saved(&storage, chunks[2].0, &chunks[2].1);
+        assert!(storage.load_chunk(ChunkPos(IVec2::new(3, -1))).unwrap().is_none());
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+
+    #[test]
+    fn saving_again_doesnt_grow_the_file() {
+        let storage = empty_storage("grow");
+        let chunks: Vec<_> = (0..4).map(|z| (ChunkPos(IVec2::new(0, z)), counting_chunk(z as u16))).collect();
+        storage.save_chunks(&chunks).unwrap();
+        let path = storage.region_path(RegionPos(IVec2::ZERO));
+        let len = fs::metadata(&path).unwrap().len();
+
+        for _ in 0..5 {
+            storage.save_chunks(&[(chunks[2].0, counting_chunk(2))]).unwrap();
+            assert_eq!(fs::metadata(&path).unwrap().len(), len);
+        }
+        assert_saved(&storage, chunks[3].0, &chunks[3].1);
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+
+    #[test]
+    fn an_interrupted_save_keeps_the_last_good_chunks() {
+        let storage = empty_storage("interrupted");
+        let pos = ChunkPos(IVec2::new(5, 5));
+        storage.save_chunks(&[(pos, counting_chunk(1))]).unwrap();
+
+        // a crash before the rename leaves half of the new file next to the old one
+        let path = storage.region_path(RegionPos::of_chunk(pos));
+        let mut file = RegionFile::default();
+        file.chunks[chunk_slot(pos)] = Some(encode_chunk(&counting_chunk(9)));
+        let bytes = file.to_bytes();
+        let tmp = path.with_extension("vxr.tmp");
+        fs::write(&tmp, &bytes[..bytes.len() / 2]).unwrap();
+
+        assert_saved(&storage, pos, &counting_chunk(1));
+
+        // the next save replaces the stale file
+        storage.save_chunks(&[(pos, counting_chunk(3))]).unwrap();
+        assert!(!tmp.exists());
+        assert_saved(&storage, pos, &counting_chunk(3));
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+
+    #[test]
+    fn saving_into_a_packed_file_keeps_its_chunks() {
+        let storage = empty_storage("packed");
+        let (first, second) = (ChunkPos(IVec2::new(1, 1)), ChunkPos(IVec2::new(2, 1)));
+        let mut file = RegionFile::default();
+        file.chunks[chunk_slot(first)] = Some(encode_chunk(&counting_chunk(1)));
+        file.chunks[chunk_slot(second)] = Some(encode_chunk(&counting_chunk(2)));
+        fs::create_dir_all(storage.region_dir()).unwrap();
+        write_atomically(&storage.region_path(RegionPos(IVec2::ZERO)), &file.to_bytes()).unwrap();
+
+        storage.save_chunks(&[(first, counting_chunk(50))]).unwrap();
+
+        assert_saved(&storage, first, &counting_chunk(50));
+        assert_saved(&storage, second, &counting_chunk(2));
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+}
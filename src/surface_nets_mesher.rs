@@ -0,0 +1,223 @@
+use crate::block_registry::BlockRegistry;
+use crate::chunk::{CHUNK_SIZE, PADDED_CHUNK_SIZE_USIZE};
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::{build_section_mesh, padded_section, PaddedSection};
+use crate::lighting::{brightest, vertex_light, Light};
+use crate::lod::LodLevel;
+use crate::mesher::Mesher;
+use crate::section_neighbors::SectionNeighbors;
+use bevy::math::{IVec3, Vec3};
+
+// cells sit between the samples of the padded grid
+const CELLS: usize = PADDED_CHUNK_SIZE_USIZE - 1;
+
+/// Smooth mesher placing one vertex in every cell the surface passes through (naive surface nets).
+///
+/// A section emits the quads of the sample edges starting inside it, the cells around those edges
+/// reach one sample into the padding so both sides of a section border agree on the shared
+/// vertices. Normals come from the solidity gradient over a cell's corners, which only depends on
+/// that cell and is therefore continuous across borders as well.
+pub struct SurfaceNetsMesher;
+
+impl Mesher for SurfaceNetsMesher {
+    fn mesh(&self, sections: &SectionNeighbors, lod: LodLevel, registry: &BlockRegistry) -> Option<ChunkSectionMesh> {
+        let padded = padded_section(sections, lod, registry)?;
+        let is_solid = |pos: IVec3| padded.block(pos).is_solid();
+
+        let mut cells = vec![None; CELLS * CELLS * CELLS];
+        for z in 0..CELLS {
+            for y in 0..CELLS {
+                for x in 0..CELLS {
+                    let cell = IVec3::new(x as i32, y as i32, z as i32);
+                    cells[cell_index(cell)] = surface_vertex(&padded, cell);
+                }
+            }
+        }
+
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        let mut lights = vec![];
+
+        for z in 1..=CHUNK_SIZE {
+            for y in 1..=CHUNK_SIZE {
+                for x in 1..=CHUNK_SIZE {
+                    let pos = IVec3::new(x, y, z);
+                    let inside = is_solid(pos);
+
+                    for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+                        if is_solid(pos + axis) == inside {
+                            continue;
+                        }
+
+                        // the four cells sharing the edge between pos and pos + axis
+                        let u = IVec3::new(axis.z, axis.x, axis.y);
+                        let v = IVec3::new(axis.y, axis.z, axis.x);
+                        let mut quad = [pos, pos - u, pos - u - v, pos - v]
+                            .map(|cell| cells[cell_index(cell)].unwrap());
+
+                        let outward = if inside { axis } else { -axis }.as_vec3();
+                        let (p0, p1, p2) = (quad[0].position, quad[1].position, quad[2].position);
+                        if (p1 - p0).cross(p2 - p0).dot(outward) < 0.0 {
+                            quad.reverse();
+                        }
+
+                        for vertex in quad {
+                            vertices.push(vertex.position.to_array());
+                            normals.push(vertex.normal.to_array());
+                            lights.push(vertex_light(vertex.light));
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(build_section_mesh(vertices, normals, lights, lod.scale()))
+    }
+}
+
+#[inline]
+fn cell_index(cell: IVec3) -> usize {
+    cell.x as usize + cell.y as usize * CELLS + cell.z as usize * CELLS * CELLS
+}
+
+#[derive(Copy, Clone)]
+struct SurfaceVertex {
+    position: Vec3,
+    normal: Vec3,
+    light: Light,
+}
+
+/// Vertex of a cell, `None` when the surface doesn't pass through it. Its light is the brightest
+/// light of the cell corners.
+fn surface_vertex(padded: &PaddedSection, cell: IVec3) -> Option<SurfaceVertex> {
+    let mut corners = [false; 8];
+    let mut light = 0;
+    for (i, corner) in corners.iter_mut().enumerate() {
+        *corner = padded.block(cell + corner_offset(i)).is_solid();
+
+        light = brightest(light, padded.light(cell + corner_offset(i)));
+    }
+
+    if corners.iter().all(|&solid| solid == corners[0]) {
+        return None;
+    }
+
+    let mut sum = Vec3::ZERO;
+    let mut crossings = 0;
+    let mut gradient = Vec3::ZERO;
+
+    for i in 0..8 {
+        let offset = corner_offset(i).as_vec3();
+        // points from solid towards air
+        let weight = if corners[i] { -1.0 } else { 1.0 };
+        gradient += (offset * 2.0 - Vec3::ONE) * weight;
+
+        for axis in 0..3 {
+            let j = i | (1 << axis);
+            if j != i && corners[i] != corners[j] {
+                sum += (offset + corner_offset(j).as_vec3()) * 0.5;
+                crossings += 1;
+            }
+        }
+    }
+
+    // samples are voxel centers, which sit half a block into the padded voxel they belong to
+    let position = cell.as_vec3() + sum / crossings as f32 - Vec3::splat(0.5);
+    Some(SurfaceVertex {
+        position,
+        normal: gradient.normalize_or(Vec3::Y),
+        light,
+    })
+}
+
+#[inline]
+fn corner_offset(i: usize) -> IVec3 {
+    IVec3::new((i & 1) as i32, ((i >> 1) & 1) as i32, ((i >> 2) & 1) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::ChunkPos;
+    use crate::world::World;
+    use bevy::math::IVec2;
+    use std::collections::HashMap;
+
+    /// World holding a stone ball of the given radius.
+    fn ball(center: IVec3, radius: i32) -> World {
+        let stone = BlockRegistry::default().by_name("voxel:stone").unwrap();
+        let blocks = (-radius..=radius).flat_map(|x| {
+            (-radius..=radius).flat_map(move |y| {
+                (-radius..=radius).filter_map(move |z| {
+                    let offset = IVec3::new(x, y, z);
+                    (offset.length_squared() <= radius * radius).then_some((center + offset, stone))
+                })
+            })
+        });
+        World::with_blocks(1, blocks)
+    }
+
+    /// Quads of the section mesh at the bottom of a chunk, in world space.
+    fn world_quads(world: &World, chunk_pos: ChunkPos) -> Vec<[(Vec3, Vec3); 4]> {
+        let sections = SectionNeighbors::new(&world.loaded_chunks, chunk_pos, 0);
+        let mesh = SurfaceNetsMesher.mesh(&sections, LodLevel::FULL, &world.registry).unwrap();
+        let origin = IVec3::new(chunk_pos.0.x, 0, chunk_pos.0.y).as_vec3() * CHUNK_SIZE as f32;
+        mesh.vertices
+            .chunks_exact(4)
+            .zip(mesh.normals.chunks_exact(4))
+            .map(|(vertices, normals)| std::array::from_fn(|i| (origin + Vec3::from(vertices[i]), Vec3::from(normals[i]))))
+            .collect()
+    }
+
+    fn key(position: Vec3) -> IVec3 {
+        (position * 1024.0).round().as_ivec3()
+    }
+
+    /// Asserts every edge is shared by exactly two quads going along it in opposite directions.
+    fn assert_closed(quads: &[[(Vec3, Vec3); 4]]) {
+        let mut edges: HashMap<(IVec3, IVec3), usize> = HashMap::new();
+        for quad in quads {
+            for i in 0..4 {
+                *edges.entry((key(quad[i].0), key(quad[(i + 1) % 4].0))).or_default() += 1;
+            }
+        }
+
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "edge {a} {b} used twice the same way");
+            assert_eq!(edges.get(&(b, a)), Some(&1), "open edge {a} {b}");
+        }
+    }
+
+    #[test]
+    fn balls_are_closed_with_outward_normals() {
+        let center = IVec3::splat(8);
+        let world = ball(center, 5);
+        let quads = world_quads(&world, ChunkPos(IVec2::ZERO));
+        assert!(!quads.is_empty());
+        assert_closed(&quads);
+
+        let center = center.as_vec3() + 0.5;
+        for &(position, normal) in quads.iter().flatten() {
+            assert!(normal.dot(position - center) > 0.0, "normal {normal} at {position}");
+        }
+    }
+
+    #[test]
+    fn sections_agree_on_their_border() {
+        // cut in half by the border between the chunks at x -16 and 0
+        let world = ball(IVec3::new(0, 8, 8), 5);
+        let west = world_quads(&world, ChunkPos(IVec2::NEG_X));
+        let east = world_quads(&world, ChunkPos(IVec2::ZERO));
+        assert_closed(&[west.clone(), east.clone()].concat());
+
+        let west_normals: HashMap<_, _> = west.iter().flatten().map(|&(position, normal)| (key(position), normal)).collect();
+        let mut shared = 0;
+        for &(position, normal) in east.iter().flatten() {
+            if let Some(&west_normal) = west_normals.get(&key(position)) {
+                assert!(normal.distance(west_normal) < 1e-5, "{normal} and {west_normal} at {position}");
+                shared += 1;
+            }
+        }
+        assert!(shared > 0);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::block_registry::BlockRegistry;
+use crate::chunk::CHUNK_SIZE;
+use crate::chunk_mesh::ChunkSectionMesh;
+use crate::greedy_chunk_render_plugin::{build_section_mesh, padded_section_all};
+use crate::lod::LodLevel;
+use crate::quad::{Direction, FaceQuad, GreedyQuad};
+use crate::section_neighbors::SectionNeighbors;
+use bevy::math::{IVec3, Vec3};
+use bevy::prelude::Component;
+
+/// Quad centers of a translucent section mesh, used to sort its faces.
+#[derive(Component, Debug, Clone)]
+pub struct TranslucentFaces {
+    pub centers: Vec<Vec3>,
+}
+
+/// Meshes the translucent blocks of a section, `None` when it has none with visible faces.
+///
+/// Faces are hidden by blocks covering them and by identical translucent blocks, so a body of water or a
+/// glass wall only shows its outside. Every face gets its own quad so the faces can be sorted back
+/// to front with [`sort_quads_back_to_front`].
+pub fn mesh_translucent(
+    sections: &SectionNeighbors,
+    lod: LodLevel,
+    registry: &BlockRegistry,
+) -> Option<ChunkSectionMesh> {
+    let padded = padded_section_all(sections, lod, registry)?;
+
+    let mut vertices = vec![];
+    let mut normals = vec![];
+    let mut lights = vec![];
+    let mut blocks = vec![];
+
+    for y in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let pos = IVec3::new(x, y, z);
+                let block = padded.block(pos + IVec3::ONE);
+                if !registry.get(block).translucent {
+                    continue;
+                }
+
+                for face_dir in Direction::ALL {
+                    let facing = pos + IVec3::ONE + face_dir.offset();
+                    let neighbor = padded.block(facing);
+                    if neighbor == block || padded.covers(facing, face_dir.opposite()) {
+                        continue;
+                    }
+
+                    let (axis_pos, quad_x, quad_y) = face_dir.sample_to_plane(pos);
+                    let face = FaceQuad {
+                        direction: face_dir,
+                        block,
+                        axis_pos,
+                        light: padded.light(facing),
+                        quad: GreedyQuad {
+                            x: quad_x as u32,
+                            y: quad_y as u32,
+                            w: 1,
+                            h: 1,
+                        },
+                    };
+                    face.append_vertices(&mut vertices, &mut normals, &mut lights);
+                    blocks.extend([face.block; 4]);
+                }
+            }
+        }
+    }
+
+    if vertices.is_empty() {
+        return None;
+    }
+
+    Some(build_section_mesh(vertices, normals, lights, lod.scale()).with_blocks(blocks))
+}
+
+/// Center of every quad of a mesh built from quads.
+pub fn quad_centers(vertices: &[[f32; 3]]) -> Vec<Vec3> {
+    vertices
+        .chunks_exact(4)
+        .map(|quad| quad.iter().map(|&v| Vec3::from(v)).sum::<Vec3>() / 4.0)
+        .collect()
+}
+
+/// Indices drawing the quads farthest from `eye` first, `eye` being relative to the mesh.
+pub fn sort_quads_back_to_front(centers: &[Vec3], eye: Vec3) -> Vec<u32> {
+    let mut order: Vec<usize> = (0..centers.len()).collect();
+    order.sort_by(|&a, &b| {
+        let a = centers[a].distance_squared(eye);
+        let b = centers[b].distance_squared(eye);
+        b.total_cmp(&a)
+    });
+
+    order
+        .into_iter()
+        .flat_map(|quad| {
+            let base = quad as u32 * 4;
+            [base, base + 1, base + 2, base, base + 2, base + 3]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, Orientation};
+    use crate::chunk::ChunkPos;
+    use crate::world::World;
+    use bevy::math::IVec2;
+
+    fn block(name: &str) -> Block {
+        BlockRegistry::default().by_name(name).unwrap()
+    }
+
+    /// Translucent mesh of the bottom section of chunk (0, 0).
+    fn mesh(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> Option<ChunkSectionMesh> {
+        let world = World::with_blocks(2, blocks);
+        let sections = SectionNeighbors::new(&world.loaded_chunks, ChunkPos(IVec2::ZERO), 0);
+        mesh_translucent(&sections, LodLevel::FULL, &world.registry)
+    }
+
+    fn faces(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> usize {
+        mesh(blocks).map_or(0, |mesh| mesh.vertices.len() / 4)
+    }
+
+    #[test]
+    fn identical_neighbors_hide_their_shared_faces() {
+        let pair = |a: &str, b: &str| faces([(IVec3::new(4, 4, 4), block(a)), (IVec3::new(5, 4, 4), block(b))]);
+        assert_eq!(pair("voxel:water", "voxel:water"), 10);
+        assert_eq!(pair("voxel:glass", "voxel:glass"), 10);
+        // different translucent blocks see each other
+        assert_eq!(pair("voxel:glass", "voxel:water"), 12);
+
+        let pool = (0..4).flat_map(|x| (0..4).map(move |z| (IVec3::new(x, 2, z), block("voxel:water"))));
+        assert_eq!(faces(pool), 4 * 4 * 2 + 4 * 4);
+    }
+
+    #[test]
+    fn opaque_neighbors_hide_the_faces_they_cover() {
+        let water = (IVec3::new(4, 4, 4), block("voxel:water"));
+        assert_eq!(faces([water]), 6);
+        assert_eq!(faces([water, (IVec3::new(4, 3, 4), block("voxel:stone"))]), 5);
+        // the top of a bottom slab doesn't reach the water
+        let slab = block("voxel:stone_slab").with_facing(Orientation::Down);
+        assert_eq!(faces([water, (IVec3::new(4, 3, 4), slab)]), 6);
+        assert_eq!(faces([water, (IVec3::new(4, 5, 4), slab)]), 5);
+
+        // across the border to the next chunk and section
+        let edge = (IVec3::new(15, 15, 4), block("voxel:glass"));
+        assert_eq!(faces([edge, (IVec3::new(16, 15, 4), block("voxel:stone")), (IVec3::new(15, 16, 4), block("voxel:stone"))]), 4);
+        assert_eq!(faces([(IVec3::new(4, 4, 4), block("voxel:stone"))]), 0);
+    }
+
+    #[test]
+    fn faces_are_drawn_back_to_front_after_the_camera_moves() {
+        // separate blocks along x, one quad per face
+        let mesh = mesh((0..4).map(|x| (IVec3::new(x * 3, 4, 4), block("voxel:glass")))).unwrap();
+        let centers = quad_centers(&mesh.vertices);
+        assert_eq!(centers.len(), 4 * 6);
+
+        for eye in [Vec3::new(-10.0, 4.5, 4.5), Vec3::new(20.0, 8.0, 0.0), Vec3::new(4.5, 4.5, 4.5)] {
+            let indices = sort_quads_back_to_front(&centers, eye);
+            assert_eq!(indices.len(), centers.len() * 6);
+            let distances: Vec<_> = indices.chunks_exact(6).map(|quad| centers[quad[0] as usize / 4].distance(eye)).collect();
+            assert!(distances.is_sorted_by(|a, b| a >= b), "from {eye}");
+            // every quad is drawn once with both of its triangles
+            let mut quads: Vec<_> = indices.chunks_exact(6).map(|quad| quad[0] / 4).collect();
+            quads.sort_unstable();
+            assert_eq!(quads, (0..centers.len() as u32).collect::<Vec<_>>());
+        }
+
+        let first = |eye: Vec3| centers[sort_quads_back_to_front(&centers, eye)[0] as usize / 4];
+        assert!(first(Vec3::new(-10.0, 4.5, 4.5)).x > 9.0);
+        assert!(first(Vec3::new(20.0, 4.5, 4.5)).x < 1.0);
+    }
+}
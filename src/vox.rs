@@ -0,0 +1,345 @@
+use crate::block::Block;
+use crate::block_registry::BlockRegistry;
+use crate::block_volume::BlockVolume;
+use crate::byte_reader::{ByteReader, Truncated};
+use bevy::math::IVec3;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+/// Largest model size MagicaVoxel handles along every axis.
+pub const VOX_MAX_SIZE: i32 = 256;
+
+/// Model of a MagicaVoxel file, in its own z up coordinates.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct VoxModel {
+    /// Size along every axis minus one, so 256 fits.
+    pub size: [u8; 3],
+    /// Position and palette index, index 0 is never used.
+    pub voxels: Vec<[u8; 4]>,
+}
+
+/// Contents of a MagicaVoxel `.vox` file. Only models and the palette are kept, scene graph,
+/// materials and layers are skipped.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct VoxFile {
+    pub models: Vec<VoxModel>,
+    /// RGBA color by palette index, `None` when the file uses the default palette.
+    pub palette: Option<Box<[[u8; 4]; 256]>>,
+}
+
+impl VoxFile {
+    pub fn parse(bytes: &[u8]) -> Result<Self, VoxError> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != b"VOX " {
+            return Err(VoxError::NotVox);
+        }
+        let version = reader.u32_le()?;
+        if version != 150 && version != 200 {
+            return Err(VoxError::UnsupportedVersion(version));
+        }
+
+        let (id, _, children) = read_chunk(&mut reader)?;
+        if id != b"MAIN" {
+            return Err(VoxError::Invalid("expected a MAIN chunk".into()));
+        }
+
+        let mut file = VoxFile::default();
+        let mut sizes = vec![];
+        let mut children = ByteReader::new(children);
+        while children.remaining() > 0 {
+            let (id, mut content, _) = read_chunk(&mut children)?;
+            match id {
+                b"SIZE" => {
+                    let size = [content.u32_le()?, content.u32_le()?, content.u32_le()?];
+                    if size.iter().any(|&axis| axis == 0 || axis > VOX_MAX_SIZE as u32) {
+                        return Err(VoxError::Invalid(format!("model size {size:?}")));
+                    }
+                    sizes.push(size.map(|axis| (axis - 1) as u8));
+                }
+                b"XYZI" => {
+                    let size = sizes
+                        .get(file.models.len())
+                        .copied()
+                        .ok_or_else(|| VoxError::Invalid("voxels without a size".into()))?;
+                    let count = content.u32_le()? as usize;
+                    let voxels: Vec<[u8; 4]> = content
+                        .take(count * 4)?
+                        .chunks_exact(4)
+                        .map(|voxel| [voxel[0], voxel[1], voxel[2], voxel[3]])
+                        .collect();
+                    if voxels.iter().any(|v| (0..3).any(|axis| v[axis] > size[axis])) {
+                        return Err(VoxError::Invalid("voxel outside of its model".into()));
+                    }
+                    file.models.push(VoxModel { size, voxels });
+                }
+                b"RGBA" => {
+                    let colors = content.take(256 * 4)?;
+                    let mut palette = Box::new([[0; 4]; 256]);
+                    // the first entry of the chunk is the color of index 1
+                    for (index, color) in colors.chunks_exact(4).take(255).enumerate() {
+                        palette[index + 1] = [color[0], color[1], color[2], color[3]];
+                    }
+                    file.palette = Some(palette);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(file)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut children = vec![];
+        for model in &self.models {
+            let size: Vec<u8> = model.size.iter().flat_map(|&axis| (axis as u32 + 1).to_le_bytes()).collect();
+            write_chunk(&mut children, b"SIZE", &size);
+
+            let mut voxels = (model.voxels.len() as u32).to_le_bytes().to_vec();
+            voxels.extend(model.voxels.iter().flatten());
+            write_chunk(&mut children, b"XYZI", &voxels);
+        }
+        if let Some(palette) = &self.palette {
+            let mut colors: Vec<u8> = palette[1..].iter().flatten().copied().collect();
+            colors.extend([0; 4]);
+            write_chunk(&mut children, b"RGBA", &colors);
+        }
+
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(b"MAIN");
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(children);
+        bytes
+    }
+}
+
+impl VoxModel {
+    /// Size in blocks once turned y up.
+    pub fn block_size(&self) -> IVec3 {
+        let [x, y, z] = self.size.map(|axis| axis as i32 + 1);
+        IVec3::new(x, z, y)
+    }
+
+    /// Blocks of the model turned y up, voxels without a block in `mapping` become air.
+    pub fn to_volume(&self, mapping: &VoxMapping) -> BlockVolume {
+        let size = self.block_size();
+        let mut volume = BlockVolume::new(size);
+        for &[x, y, z, index] in &self.voxels {
+            if let Some(block) = mapping.block(index) {
+                volume.set(IVec3::new(x as i32, z as i32, size.z - 1 - y as i32), block);
+            }
+        }
+
+        volume
+    }
+
+    /// Model of the solid blocks of a volume, using the first palette index mapped to their id.
+    pub fn from_volume(volume: &BlockVolume, mapping: &VoxMapping) -> Result<Self, VoxError> {
+        let size = volume.size();
+        if size.cmpgt(IVec3::splat(VOX_MAX_SIZE)).any() || size.cmplt(IVec3::ONE).any() {
+            return Err(VoxError::TooLarge(size));
+        }
+
+        let mut voxels = vec![];
+        for (pos, block) in volume.iter().filter(|(_, block)| block.is_solid()) {
+            let index = mapping.index_of(block).ok_or(VoxError::Unmapped(block))?;
+            voxels.push([pos.x as u8, (size.z - 1 - pos.z) as u8, pos.y as u8, index]);
+        }
+
+        Ok(Self {
+            size: [size.x - 1, size.z - 1, size.y - 1].map(|axis| axis as u8),
+            voxels,
+        })
+    }
+}
+
+/// Blocks and colors of MagicaVoxel palette indices.
+///
+/// Read from lines like `12 = voxel:stone #808080ff`, one per palette index, the color being
+/// optional and only used when exporting.
+#[derive(Debug, Clone)]
+pub struct VoxMapping {
+    blocks: Box<[Option<Block>; 256]>,
+    colors: Box<[[u8; 4]; 256]>,
+}
+
+impl Default for VoxMapping {
+    fn default() -> Self {
+        let mut colors = Box::new([[128, 128, 128, 255]; 256]);
+        colors[0] = [0; 4];
+
+        Self {
+            blocks: Box::new([None; 256]),
+            colors,
+        }
+    }
+}
+
+impl VoxMapping {
+    pub fn parse(text: &str, registry: &BlockRegistry) -> Result<Self, VoxError> {
+        let mut mapping = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| VoxError::Invalid(format!("mapping line {}: {message}", index + 1));
+
+            let (palette_index, value) = line.split_once('=').ok_or_else(|| invalid("expected `index = block`"))?;
+            let palette_index: u8 = palette_index.trim().parse().map_err(|_| invalid("invalid palette index"))?;
+            if palette_index == 0 {
+                return Err(invalid("palette index 0 is empty space"));
+            }
+
+            let mut parts = value.split_whitespace();
+            let name = parts.next().ok_or_else(|| invalid("missing block name"))?;
+            let block = registry.by_name(name).ok_or_else(|| invalid("unknown block"))?;
+            mapping.set(palette_index, block);
+
+            if let Some(color) = parts.next() {
+                let color = color.strip_prefix('#').ok_or_else(|| invalid("colors start with #"))?;
+                let rgba = u32::from_str_radix(color, 16).map_err(|_| invalid("invalid color"))?;
+                mapping.colors[palette_index as usize] = match color.len() {
+                    6 => ((rgba << 8) | 0xFF).to_be_bytes(),
+                    8 => rgba.to_be_bytes(),
+                    _ => return Err(invalid("colors are #rrggbb or #rrggbbaa")),
+                };
+            }
+        }
+
+        Ok(mapping)
+    }
+
+    pub fn set(&mut self, index: u8, block: Block) {
+        self.blocks[index as usize] = Some(block);
+    }
+
+    pub fn block(&self, index: u8) -> Option<Block> {
+        self.blocks[index as usize]
+    }
+
+    /// First palette index mapped to a block with the same id.
+    pub fn index_of(&self, block: Block) -> Option<u8> {
+        (1..=255).find(|&index| self.blocks[index as usize].is_some_and(|mapped| mapped.id() == block.id()))
+    }
+
+    /// Palette with the colors of the mapping.
+    pub fn palette(&self) -> Box<[[u8; 4]; 256]> {
+        self.colors.clone()
+    }
+}
+
+/// Reason a `.vox` file or mapping couldn't be read or written.
+#[derive(Debug)]
+pub enum VoxError {
+    NotVox,
+    UnsupportedVersion(u32),
+    Truncated,
+    Invalid(String),
+    /// Volumes have to fit into [`VOX_MAX_SIZE`] along every axis.
+    TooLarge(IVec3),
+    /// The block has no palette index in the mapping.
+    Unmapped(Block),
+}
+
+impl Display for VoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::NotVox => write!(f, "not a MagicaVoxel file"),
+            VoxError::UnsupportedVersion(version) => write!(f, "unsupported .vox version {version}"),
+            VoxError::Truncated => write!(f, "truncated .vox file"),
+            VoxError::Invalid(message) => write!(f, "invalid .vox data: {message}"),
+            VoxError::TooLarge(size) => write!(f, "size {size} doesn't fit into a .vox model"),
+            VoxError::Unmapped(block) => write!(f, "block {} has no palette index", block.id()),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+impl From<Truncated> for VoxError {
+    fn from(_: Truncated) -> Self {
+        VoxError::Truncated
+    }
+}
+
+/// Id, content and children of the next chunk.
+fn read_chunk<'a>(reader: &mut ByteReader<'a>) -> Result<(&'a [u8], ByteReader<'a>, &'a [u8]), VoxError> {
+    let id = reader.take(4)?;
+    let content = reader.u32_le()? as usize;
+    let children = reader.u32_le()? as usize;
+    let content = ByteReader::new(reader.take(content)?);
+
+    Ok((id, content, reader.take(children)?))
+}
+
+fn write_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    bytes.extend(id);
+    bytes.extend((content.len() as u32).to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(content);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tower() -> (VoxFile, VoxMapping, BlockRegistry) {
+        let registry = BlockRegistry::default();
+        let file = VoxFile::parse(include_bytes!("../assets/structures/tower.vox")).unwrap();
+        let mapping = VoxMapping::parse(include_str!("../assets/structures/tower.mapping"), &registry).unwrap();
+        (file, mapping, registry)
+    }
+
+    fn block_at(volume: &BlockVolume, pos: IVec3) -> Block {
+        volume.iter().find(|&(found, _)| found == pos).map(|(_, block)| block).unwrap()
+    }
+
+    #[test]
+    fn parses_the_tower() {
+        let (file, mapping, registry) = tower();
+        assert_eq!(file.models.len(), 1);
+        let model = &file.models[0];
+        assert_eq!(model.size, [4, 4, 8]);
+        assert_eq!(model.block_size(), IVec3::new(5, 9, 5));
+        assert_eq!(model.voxels.len(), 146);
+
+        let palette = file.palette.as_ref().unwrap();
+        assert_eq!(palette[1], [0x7f, 0x7f, 0x7f, 0xff]);
+        assert_eq!(palette[2], [0xa8, 0xd8, 0xf0, 0xff]);
+
+        let by_name = |name| registry.by_name(name).unwrap();
+        assert_eq!(mapping.block(0), None);
+        assert_eq!(mapping.block(1), Some(by_name("voxel:stone")));
+        assert_eq!(mapping.block(8), Some(by_name("voxel:stone_slab")));
+        assert_eq!(mapping.block(11), Some(by_name("voxel:post")));
+        assert_eq!(mapping.block(12), None);
+        assert_eq!(mapping.index_of(by_name("voxel:glowstone")), Some(3));
+
+        // turned y up, the model's y going towards -z
+        let volume = model.to_volume(&mapping);
+        assert_eq!(volume.size(), IVec3::new(5, 9, 5));
+        assert_eq!(block_at(&volume, IVec3::new(0, 0, 4)), by_name("voxel:stone"));
+        assert_eq!(block_at(&volume, IVec3::new(2, 1, 2)), by_name("voxel:post"));
+        assert_eq!(block_at(&volume, IVec3::new(2, 3, 0)), by_name("voxel:glass"));
+        assert_eq!(block_at(&volume, IVec3::new(0, 7, 4)), by_name("voxel:stone_slab"));
+        assert_eq!(block_at(&volume, IVec3::new(2, 8, 2)), by_name("voxel:glowstone"));
+        assert_eq!(block_at(&volume, IVec3::new(2, 2, 2)), Block(0));
+        assert_eq!(volume.iter().filter(|(_, block)| block.is_solid()).count(), 146);
+    }
+
+    #[test]
+    fn the_tower_survives_an_export() {
+        let (file, mapping, _) = tower();
+        let volume = file.models[0].to_volume(&mapping);
+        let model = VoxModel::from_volume(&volume, &mapping).unwrap();
+        let exported = VoxFile {
+            models: vec![model],
+            palette: Some(mapping.palette()),
+        };
+
+        let parsed = VoxFile::parse(&exported.to_bytes()).unwrap();
+        assert_eq!(parsed.models[0].to_volume(&mapping), volume);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::chunk::ChunkPos;
+use crate::collision::Aabb;
+use crate::world::World;
+use bevy::app::{App, Plugin, Update};
+use bevy::math::Vec3;
+use bevy::prelude::{Component, Query, Res, Time, Transform};
+
+/// Box moved by its velocity and gravity, stopping at solid blocks. Used for mobs and dropped
+/// items, the player has a [`CharacterController`](crate::character_controller::CharacterController).
+#[derive(Component, Debug, Clone)]
+pub struct VoxelBody {
+    /// Half the size of the box, which is centered on the translation.
+    pub half_size: Vec3,
+    pub velocity: Vec3,
+    pub gravity: f32,
+    pub max_fall_speed: f32,
+    /// Fraction of the horizontal velocity lost per second while on the ground.
+    pub friction: f32,
+    /// Whether the body rests on a block, updated every frame.
+    pub grounded: bool,
+}
+
+impl VoxelBody {
+    pub fn new(half_size: Vec3) -> Self {
+        Self {
+            half_size,
+            velocity: Vec3::ZERO,
+            gravity: 32.0,
+            max_fall_speed: 60.0,
+            friction: 0.9,
+            grounded: false,
+        }
+    }
+
+    /// Box of the body at a translation.
+    pub fn aabb(&self, translation: Vec3) -> Aabb {
+        Aabb::new(translation - self.half_size, translation + self.half_size)
+    }
+}
+
+pub struct VoxelBodyPlugin;
+
+impl Plugin for VoxelBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, Self::move_bodies);
+    }
+}
+
+impl VoxelBodyPlugin {
+    /// Moves bodies as far as the world lets them, stopping their velocity along blocked axes.
+    /// Bodies in chunks which aren't loaded stay where they are until they are.
+    pub fn move_bodies(time: Res<Time>, world: Res<World>, mut bodies: Query<(&mut VoxelBody, &mut Transform)>) {
+        let delta = time.delta_secs();
+
+        for (mut body, mut transform) in bodies.iter_mut() {
+            let chunk_pos = ChunkPos::of_block(transform.translation.floor().as_ivec3());
+            if !world.loaded_chunks.contains_key(&chunk_pos) {
+                continue;
+            }
+
+            body.velocity.y = (body.velocity.y - body.gravity * delta).max(-body.max_fall_speed);
+            if body.grounded {
+                let kept = (1.0 - body.friction).powf(delta);
+                body.velocity.x *= kept;
+                body.velocity.z *= kept;
+            }
+
+            let sweep = world.sweep_aabb(body.aabb(transform.translation), body.velocity * delta);
+            transform.translation += sweep.motion;
+            body.velocity = Vec3::select(sweep.blocked, Vec3::ZERO, body.velocity);
+            body.grounded = world.is_supported(body.aabb(transform.translation));
+        }
+    }
+}
@@ -1,18 +1,64 @@
 use crate::chunk::{CHUNK_SIZE, Chunk, ChunkPos};
 use crate::chunk_mesh::ChunkSectionMesh;
-use crate::greedy_chunk_render_plugin::generate_section_mesh;
 use bevy::app::{App, Plugin, PostUpdate, Startup, Update};
 use bevy::asset::{Assets, Handle, RenderAssetUsages};
 use bevy::color::{Color, Srgba};
 use bevy::mesh::{Indices, Mesh, Mesh3d, PrimitiveTopology};
 use bevy::pbr::{MeshMaterial3d, StandardMaterial};
-use bevy::prelude::{Commands, Entity, IntoScheduleConfigs, Res, ResMut, Resource, Transform};
+use bevy::app::{AppExit, Last};
+use bevy::log::{error, info, warn};
+use bevy::prelude::{Added, Message, MessageReader, MessageWriter, Time, AlphaMode, Camera3d, Commands, DetectChanges, Entity, GlobalTransform, IntoScheduleConfigs, Local, Query, Res, ResMut, Resource, Transform, With};
 use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, poll_once};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io;
 use std::sync::Arc;
-use bevy::math::Vec4;
-use crate::chunk_material::ChunkMaterial;
+use std::time::Duration;
+use bevy::math::{IVec2, IVec3, Vec3, Vec4};
+use crate::block::Block;
+use crate::block_volume::BlockVolume;
+use crate::collision::{self, Aabb, Sweep};
+use crate::block_registry::{BlockDefinition, BlockRegistry};
+use crate::chunk_material::{ChunkMaterial, ATTRIBUTE_VOXEL_LIGHT};
+use crate::lighting::{light_chunk, relight_world_blocks, stitch_chunk_light};
+use crate::chunk_scheduler::{ChunkPriorities, ChunkSchedulerPlugin, ChunkSchedulerSettings};
+use crate::lod::LodLevel;
+use crate::column_mesher::{mesh_column, ColumnMesh, ColumnSection};
+use crate::mesher::{ChunkMesher, MeshLayout, MeshPass, SectionMeshes};
+use crate::quad::{Direction, FaceQuad};
+use crate::level::Level;
+use crate::pathfinding::{self, PathError, PathGrid, PathSettings};
+use crate::region::RegionStorage;
 use crate::section_neighbors::SectionNeighbors;
+use crate::translucent_mesher::{quad_centers, sort_quads_back_to_front, TranslucentFaces};
+use crate::world_edit::{self, EditError, WorldEdit};
+
+/// Block found by [`World::raycast`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub pos: IVec3,
+    pub block: Block,
+    /// Face of the block the ray entered through.
+    pub normal: Direction,
+    /// Where the ray enters the block.
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+/// Rays along every edge of the cube [`World::explode`] casts rays through.
+const EXPLOSION_RAYS: i32 = 16;
+/// Distance between the blocks an explosion ray checks.
+const EXPLOSION_STEP: f32 = 0.3;
+
+/// Sent after [`World::explode`] with the blocks it destroyed, for damage and debris.
+#[derive(Message, Debug, Clone)]
+pub struct Explosion {
+    pub center: Vec3,
+    pub power: f32,
+    pub destroyed: Vec<(IVec3, Block)>,
+}
+
+/// How far below a box [`World::is_supported`] looks for ground.
+const SUPPORT_DEPTH: f32 = 0.01;
 
 #[derive(Resource, Debug, Default)]
 pub struct World {
@@ -25,12 +71,320 @@ pub struct World {
     pub(crate) chunks_mesh_to_unload: Vec<(ChunkPos, usize)>, // pos, sections_amount
 
     pub(crate) data_tasks: HashMap<ChunkPos, Task<Chunk>>,
-    pub(crate) mesh_tasks: HashMap<(ChunkPos, i32), Task<Option<ChunkSectionMesh>>>,
+    pub(crate) mesh_tasks: HashMap<(ChunkPos, i32), Task<SectionMeshes>>,
+
+    pub(crate) column_tasks: HashMap<ChunkPos, Task<ColumnMesh>>,
+    pub(crate) dirty_sections: HashMap<ChunkPos, HashSet<i32>>,
+    pub(crate) section_quads: HashMap<(ChunkPos, i32), Arc<Vec<FaceQuad>>>,
+
+    pub(crate) mesh_uploads: Vec<(ChunkPos, i32, MeshPass, Option<ChunkSectionMesh>)>,
+
+    pub(crate) chunk_lods: HashMap<ChunkPos, LodLevel>,
+
+    section_entities: HashMap<(ChunkPos, i32, MeshPass), Entity>,
+    /// Chunks whose opaque blocks are drawn by one column mesh, in the entity of their bottom
+    /// section.
+    column_chunks: HashSet<ChunkPos>,
+
+    /// Chunks being written to disk with the save writing them. Unloaded chunks stay here until
+    /// their save completes, so loading them again doesn't read an outdated region file.
+    pub(crate) saving_chunks: HashMap<ChunkPos, (Arc<Chunk>, u64)>,
+    pub(crate) save_tasks: HashMap<u64, Task<io::Result<()>>>,
+    next_save: u64,
 
-    section_entities: HashMap<(ChunkPos, i32), Entity>,
+    /// Explosions sent as [`Explosion`] messages at the end of the frame.
+    explosions: Vec<Explosion>,
+
+    pub registry: BlockRegistry,
 }
 
 impl World {
+    /// Block at a world position, `None` when its chunk isn't loaded.
+    pub fn get_block(&self, pos: IVec3) -> Option<Block> {
+        let chunk = self.loaded_chunks.get(&ChunkPos::of_block(pos))?;
+        if pos.y < 0 || pos.y >= chunk.height() {
+            return None;
+        }
+
+        chunk.get(local_block_pos(pos))
+    }
+
+    /// Changes a block at a world position, relighting and remeshing everything it affects.
+    /// Returns `false` when its chunk isn't loaded or the block was already there.
+    pub fn set_block(&mut self, pos: IVec3, block: Block) -> bool {
+        self.set_blocks([(pos, block)]) == 1
+    }
+
+    /// Changes many blocks at once like [`World::set_block`], remeshing every affected section
+    /// once. Blocks in chunks which aren't loaded are skipped, returns how many were changed.
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = (IVec3, Block)>) -> usize {
+        let mut edit = WorldEdit::new();
+        for (pos, block) in blocks {
+            edit.set(pos, block);
+        }
+
+        self.write_edit(&edit).len()
+    }
+
+    /// Applies every change of an edit or none of them when it touches a chunk which isn't
+    /// loaded. Returns the blocks which were there before for the positions that changed.
+    pub fn apply_edit(&mut self, edit: &WorldEdit) -> Result<Vec<(IVec3, Block)>, EditError> {
+        for pos in edit.blocks().map(|(pos, _)| ChunkPos::of_block(pos)) {
+            if !self.loaded_chunks.contains_key(&pos) {
+                return Err(EditError::NotLoaded(pos));
+            }
+        }
+
+        Ok(self.write_edit(edit))
+    }
+
+    /// Writes the changes of each section under a single lock, skipping chunks which aren't
+    /// loaded, then relights the changed blocks and remeshes every affected section once.
+    fn write_edit(&mut self, edit: &WorldEdit) -> Vec<(IVec3, Block)> {
+        let mut previous = vec![];
+
+        for ((chunk_pos, section_y), blocks) in edit.by_section() {
+            let Some(chunk) = self.loaded_chunks.get(&chunk_pos) else {
+                continue;
+            };
+            let Some(section) = usize::try_from(section_y).ok().and_then(|y| chunk.sections.get(y)) else {
+                continue;
+            };
+
+            let mut section = section.write().unwrap();
+            let changed = previous.len();
+            for (pos, block) in blocks {
+                let local = local_block_pos(pos);
+                let (x, y, z) = (local.x, local.y.rem_euclid(CHUNK_SIZE), local.z);
+                let old = section.get_by_xyz(x, y, z).unwrap();
+                if old != block {
+                    section.set_by_xyz(x, y, z, block);
+                    previous.push((pos, old));
+                }
+            }
+            if previous.len() > changed {
+                chunk.mark_dirty();
+            }
+        }
+
+        let changed: Vec<IVec3> = previous.iter().map(|&(pos, _)| pos).collect();
+        let mut sections = relight_world_blocks(&self.loaded_chunks, &self.registry, &changed);
+        for &pos in &changed {
+            // faces of the neighbors touching the block change as well
+            let touching = Direction::ALL.map(|direction| pos + direction.offset());
+            sections.extend(touching.into_iter().chain([pos]).map(world_edit::section_of));
+        }
+        for (chunk_pos, section_y) in sections {
+            self.remesh_section(chunk_pos, section_y);
+        }
+
+        previous
+    }
+
+    /// Blows up the blocks around `center`. Rays cast in every direction lose power with distance
+    /// and with the blast resistance of the blocks they pass, destroying blocks while they still
+    /// have power left. Returns the destroyed positions and queues an [`Explosion`] message, which
+    /// holds the blocks they had.
+    pub fn explode(&mut self, center: Vec3, power: f32) -> Vec<IVec3> {
+        let mut destroyed = vec![];
+        let mut seen = HashSet::new();
+
+        let last = EXPLOSION_RAYS - 1;
+        for (ray, _) in BlockVolume::new(IVec3::splat(EXPLOSION_RAYS)).iter() {
+            // rays through the surface of a cube around the center
+            if !ray.cmpeq(IVec3::ZERO).any() && !ray.cmpeq(IVec3::splat(last)).any() {
+                continue;
+            }
+            let direction = (ray.as_vec3() / last as f32 * 2.0 - 1.0).normalize();
+
+            let mut intensity = power;
+            let mut pos = center;
+            while intensity > 0.0 {
+                let block_pos = pos.floor().as_ivec3();
+                let Some(block) = self.get_block(block_pos) else {
+                    break;
+                };
+                if block.is_solid() {
+                    intensity -= (self.registry.get(block).blast_resistance + 0.3) * EXPLOSION_STEP;
+                    if intensity > 0.0 && seen.insert(block_pos) {
+                        destroyed.push((block_pos, block));
+                    }
+                }
+
+                pos += direction * EXPLOSION_STEP;
+                intensity -= EXPLOSION_STEP * 0.75;
+            }
+        }
+
+        let positions: Vec<_> = destroyed.iter().map(|&(pos, _)| pos).collect();
+        let mut edit = WorldEdit::new();
+        for &pos in &positions {
+            edit.set(pos, Block(0));
+        }
+        self.write_edit(&edit);
+        self.explosions.push(Explosion { center, power, destroyed });
+        positions
+    }
+
+    /// Blocks of the box with its minimum corner at `min`, blocks which aren't loaded read as air.
+    pub fn read_volume(&self, min: IVec3, size: IVec3) -> BlockVolume {
+        let mut volume = BlockVolume::new(size);
+        for (pos, _) in BlockVolume::new(size).iter() {
+            if let Some(block) = self.get_block(min + pos) {
+                volume.set(pos, block);
+            }
+        }
+
+        volume
+    }
+
+    /// Blocks of the box between two corners, both included, like [`World::read_volume`].
+    pub fn read_box(&self, a: IVec3, b: IVec3) -> BlockVolume {
+        let min = a.min(b);
+        self.read_volume(min, a.max(b) - min + 1)
+    }
+
+    /// Places the blocks of a volume with its minimum corner at `origin`, air included. Blocks
+    /// in chunks which aren't loaded are skipped.
+    pub fn write_volume(&mut self, origin: IVec3, volume: &BlockVolume) {
+        self.set_blocks(volume.iter().map(|(pos, block)| (origin + pos, block)));
+    }
+
+    /// First solid block along a ray, see [`World::raycast_filtered`].
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RaycastHit> {
+        self.raycast_filtered(origin, direction, max_distance, |block, _| block.is_solid())
+    }
+
+    /// First block along a ray which `filter` accepts, walking the blocks it crosses in order
+    /// (Amanatides & Woo). Stops with `None` at chunks which aren't loaded or past
+    /// `max_distance`. A ray starting inside an accepted block hits it at distance 0.
+    pub fn raycast_filtered(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        max_distance: f32,
+        filter: impl Fn(Block, &BlockDefinition) -> bool,
+    ) -> Option<RaycastHit> {
+        let direction = direction.try_normalize()?;
+        let mut pos = origin.floor().as_ivec3();
+        let step = IVec3::from_array(direction.to_array().map(|axis| if axis < 0.0 { -1 } else { 1 }));
+        let t_delta = direction.recip().abs();
+        // distance along the ray to the first block boundary of every axis
+        let mut t_max = Vec3::from_array(std::array::from_fn(|axis| {
+            if direction[axis] == 0.0 {
+                return f32::INFINITY;
+            }
+            let boundary = pos[axis] as f32 + if step[axis] > 0 { 1.0 } else { 0.0 };
+            (boundary - origin[axis]) / direction[axis]
+        }));
+
+        let face = |axis: usize| Direction::ALL.into_iter().find(|face| face.offset()[axis] == -step[axis]).unwrap();
+        let mut normal = face(direction.abs().max_position());
+        let mut distance = 0.0;
+        loop {
+            let chunk = self.loaded_chunks.get(&ChunkPos::of_block(pos))?;
+            let in_height = (0..chunk.height()).contains(&pos.y);
+            if !in_height && (pos.y < 0) == (step.y < 0) {
+                // leaving the world vertically, nothing left to hit
+                return None;
+            }
+            if in_height
+                && let Some(block) = chunk.get(local_block_pos(pos))
+                && filter(block, self.registry.get(block))
+            {
+                return Some(RaycastHit {
+                    pos,
+                    block,
+                    normal,
+                    point: origin + direction * distance,
+                    distance,
+                });
+            }
+
+            let axis = t_max.min_position();
+            distance = t_max[axis];
+            if distance > max_distance {
+                return None;
+            }
+            pos[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+            normal = face(axis);
+        }
+    }
+
+    /// Boxes entities collide with at a block position. Blocks of chunks which aren't loaded are
+    /// full cubes, so nothing falls into them, and there's nothing above or below the world.
+    pub fn collision_boxes(&self, pos: IVec3) -> Vec<Aabb> {
+        let Some(chunk) = self.loaded_chunks.get(&ChunkPos::of_block(pos)) else {
+            return vec![Aabb::new(pos.as_vec3(), pos.as_vec3() + 1.0)];
+        };
+        if pos.y < 0 || pos.y >= chunk.height() {
+            return vec![];
+        }
+
+        let block = chunk.get(local_block_pos(pos)).unwrap_or_default();
+        self.registry
+            .collision_boxes(block)
+            .into_iter()
+            .map(|model_box| Aabb::of_model_box(model_box, pos))
+            .collect()
+    }
+
+    /// Moves a box as far as it gets towards `motion` without entering solid blocks, resolving
+    /// one axis at a time, see [`collision::sweep`].
+    pub fn sweep_aabb(&self, aabb: Aabb, motion: Vec3) -> Sweep {
+        collision::sweep(aabb, motion, |pos| self.collision_boxes(pos))
+    }
+
+    /// Whether the box overlaps the collision box of a block, see [`World::collision_boxes`].
+    pub fn overlaps_solid(&self, aabb: Aabb) -> bool {
+        let (min, max) = aabb.block_range();
+        block_positions(min, max).any(|pos| self.collision_boxes(pos).iter().any(|other| other.intersects(&aabb)))
+    }
+
+    /// Positions and blocks of the loaded blocks the box overlaps.
+    pub fn blocks_in_aabb(&self, aabb: Aabb) -> impl Iterator<Item = (IVec3, Block)> + '_ {
+        let (min, max) = aabb.block_range();
+        block_positions(min, max).filter_map(|pos| Some((pos, self.get_block(pos)?)))
+    }
+
+    /// Whether something solid is right below the box, so it stands on it.
+    pub fn is_supported(&self, aabb: Aabb) -> bool {
+        let below = Aabb::new(aabb.min - Vec3::Y * SUPPORT_DEPTH, aabb.max.with_y(aabb.min.y));
+        self.overlaps_solid(below)
+    }
+
+    /// Searches a path between two cells on the [`AsyncComputeTaskPool`], over the chunks loaded
+    /// now. See [`pathfinding::find_path`].
+    pub fn find_path(&self, start: IVec3, goal: IVec3, settings: PathSettings) -> Task<Result<Vec<IVec3>, PathError>> {
+        let grid = PathGrid::new(self.loaded_chunks.clone(), self.registry.clone());
+        AsyncComputeTaskPool::get().spawn(async move { pathfinding::find_path(&grid, start, goal, &settings) })
+    }
+
+    /// Section mesh entity drawing the block at a world position, if it's meshed.
+    pub fn section_entity(&self, pos: IVec3) -> Option<Entity> {
+        let block = self.get_block(pos)?;
+        let pass = if self.registry.get(block).translucent {
+            MeshPass::Translucent
+        } else if self.registry.has_model(block) {
+            MeshPass::Models
+        } else {
+            MeshPass::Opaque
+        };
+
+        let chunk_pos = ChunkPos::of_block(pos);
+        let section_y = if pass == MeshPass::Opaque && self.column_chunks.contains(&chunk_pos) {
+            0
+        } else {
+            pos.y.div_euclid(CHUNK_SIZE)
+        };
+        self.section_entities.get(&(chunk_pos, section_y, pass)).copied()
+    }
+
+    pub fn lod_of(&self, position: ChunkPos) -> LodLevel {
+        self.chunk_lods.get(&position).copied().unwrap_or(LodLevel::FULL)
+    }
+
     pub fn load_chunk(&mut self, position: ChunkPos) {
         if self.loaded_chunks.contains_key(&position)
             || self.chunks_data_to_load.contains(&position)
@@ -49,6 +403,96 @@ impl World {
         }
         self.chunks_data_to_unload.push(position);
     }
+
+    /// Queues every section of a loaded chunk to be meshed again.
+    pub fn remesh_chunk(&mut self, position: ChunkPos) {
+        if !self.loaded_chunks.contains_key(&position) {
+            return;
+        }
+
+        self.dirty_sections.remove(&position);
+        if !self.chunks_mesh_to_load.contains(&position) {
+            self.chunks_mesh_to_load.push(position);
+        }
+    }
+
+    /// Queues a single section of a loaded chunk to be meshed again.
+    pub fn remesh_section(&mut self, position: ChunkPos, section_y: i32) {
+        let Some(chunk) = self.loaded_chunks.get(&position) else {
+            return;
+        };
+        if section_y < 0 || section_y >= chunk.sections.len() as i32 {
+            return;
+        }
+
+        if !self.chunks_mesh_to_load.contains(&position) {
+            self.chunks_mesh_to_load.push(position);
+            self.dirty_sections.insert(position, HashSet::from([section_y]));
+        } else if let Some(dirty) = self.dirty_sections.get_mut(&position) {
+            dirty.insert(section_y);
+        }
+    }
+
+    /// Makes a loaded chunk part of the world.
+    fn insert_chunk(&mut self, position: ChunkPos, chunk: Arc<Chunk>) {
+        self.loaded_chunks.insert(position, chunk);
+        self.remesh_chunk(position);
+
+        // light flows in from and out to the neighbors loaded before
+        let relit = stitch_chunk_light(&self.loaded_chunks, &self.registry, position);
+        for (relit_pos, section_y) in relit {
+            self.remesh_section(relit_pos, section_y);
+        }
+    }
+
+    /// Writes the chunks on the [`bevy::tasks::IoTaskPool`], keeping them around until it's done.
+    fn save_chunks(&mut self, storage: &RegionStorage, chunks: Vec<(ChunkPos, Arc<Chunk>)>) {
+        if chunks.is_empty() {
+            return;
+        }
+
+        let save = self.next_save;
+        self.next_save += 1;
+        for (chunk_pos, chunk) in &chunks {
+            self.saving_chunks.insert(*chunk_pos, (chunk.clone(), save));
+        }
+        self.save_tasks.insert(save, storage.spawn_save(chunks));
+    }
+
+    /// Modified chunks, loaded or waiting to be saved, with their dirty flag cleared.
+    fn take_dirty_chunks(&self) -> Vec<(ChunkPos, Arc<Chunk>)> {
+        let loaded = self.loaded_chunks.iter();
+        let saving = self.saving_chunks.iter().map(|(pos, (chunk, _))| (pos, chunk));
+
+        let mut dirty: HashMap<ChunkPos, Arc<Chunk>> = HashMap::new();
+        for (&chunk_pos, chunk) in loaded.chain(saving) {
+            if chunk.take_dirty() {
+                dirty.insert(chunk_pos, chunk.clone());
+            }
+        }
+
+        dirty.into_iter().collect()
+    }
+
+    /// Neighbors of a section as seen by a mesher at the given LOD.
+    fn section_neighbors(&self, position: ChunkPos, section_y: usize, lod: LodLevel) -> SectionNeighbors {
+        let mut section = SectionNeighbors::new(&self.loaded_chunks, position, section_y);
+        // neighbors at another LOD don't match our border, keep the faces against them
+        if self.lod_of(ChunkPos(position.0 + IVec2::Y)) != lod {
+            section.north = None;
+        }
+        if self.lod_of(ChunkPos(position.0 + IVec2::NEG_Y)) != lod {
+            section.south = None;
+        }
+        if self.lod_of(ChunkPos(position.0 + IVec2::X)) != lod {
+            section.east = None;
+        }
+        if self.lod_of(ChunkPos(position.0 + IVec2::NEG_X)) != lod {
+            section.west = None;
+        }
+
+        section
+    }
 }
 
 pub struct WorldPlugin;
@@ -56,43 +500,197 @@ pub struct WorldPlugin;
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(World::default())
-            .add_systems(Startup, Self::setup)
-            .add_systems(PostUpdate, (Self::start_data_tasks, Self::start_mesh_tasks))
+            .init_resource::<ChunkMesher>()
+            .init_resource::<MeshLayout>()
+            .init_resource::<RegionStorage>()
+            .add_message::<Explosion>()
+            .add_plugins(ChunkSchedulerPlugin)
+            .add_systems(Startup, (Self::setup, Self::open_level))
+            .add_systems(PostUpdate, (Self::start_data_tasks, Self::start_mesh_tasks, Self::send_explosions))
             .add_systems(
                 Update,
                 (
+                    Self::remesh_on_mesher_change,
                     (Self::join_data_tasks, Self::join_mesh_tanks),
+                    Self::sort_translucent_faces,
                     Self::unload_meshes,
                     Self::unload_data,
+                    Self::join_save_tasks,
+                    Self::autosave,
                 )
                     .chain(),
-            );
+            )
+            .add_systems(Last, Self::save_on_exit);
     }
 }
 
 #[derive(Resource)]
-struct GlobalChunkMaterial(Handle<ChunkMaterial>);
+struct GlobalChunkMaterial {
+    opaque: Handle<ChunkMaterial>,
+    translucent: Handle<ChunkMaterial>,
+}
+
+impl GlobalChunkMaterial {
+    fn of(&self, pass: MeshPass) -> Handle<ChunkMaterial> {
+        match pass {
+            MeshPass::Opaque | MeshPass::Models => self.opaque.clone(),
+            MeshPass::Translucent => self.translucent.clone(),
+        }
+    }
+}
 
 impl WorldPlugin {
     pub fn setup(mut commands: Commands, mut materials: ResMut<Assets<ChunkMaterial>>) {
-        let material = materials.add(ChunkMaterial {
-            color: Vec4::new(0.3, 0.5, 0.3, 1.0),
+        let opaque = materials.add(ChunkMaterial {
+            color: Vec4::new(0.4, 0.5, 0.4, 1.0),
+            alpha_mode: AlphaMode::Opaque,
+        });
+        let translucent = materials.add(ChunkMaterial {
+            color: Vec4::new(0.5, 0.7, 0.9, 0.5),
+            alpha_mode: AlphaMode::Blend,
         });
 
-        commands.insert_resource(GlobalChunkMaterial(material));
+        commands.insert_resource(GlobalChunkMaterial { opaque, translucent });
+    }
+
+    /// Opens the saved world before any chunk gets loaded, closing the app when it can't be read.
+    pub fn open_level(
+        mut commands: Commands,
+        world: Res<World>,
+        storage: Res<RegionStorage>,
+        mut exit: MessageWriter<AppExit>,
+    ) {
+        match Level::open(&storage, &world.registry) {
+            Ok(level) => {
+                info!("Opened world {} with seed {}", storage.dir().display(), level.seed);
+                commands.insert_resource(level);
+            }
+            Err(err) => {
+                error!("Can't open world {}: {err}", storage.dir().display());
+                exit.write(AppExit::error());
+            }
+        }
+    }
+
+    /// Meshes every loaded chunk again once another mesher or layout gets selected.
+    pub fn remesh_on_mesher_change(
+        mesher: Res<ChunkMesher>,
+        layout: Res<MeshLayout>,
+        mut world: ResMut<World>,
+    ) {
+        let mesher_changed = mesher.is_changed() && !mesher.is_added();
+        let layout_changed = layout.is_changed() && !layout.is_added();
+        if !mesher_changed && !layout_changed {
+            return;
+        }
+
+        let loaded: Vec<_> = world.loaded_chunks.keys().copied().collect();
+        for chunk_pos in loaded {
+            world.remesh_chunk(chunk_pos);
+        }
     }
 
-    pub fn unload_data(mut world: ResMut<World>) {
+    pub fn unload_data(mut world: ResMut<World>, storage: Res<RegionStorage>) {
         let chunks_to_unload: Vec<_> = world.chunks_data_to_unload.drain(..).collect();
+        let mut chunks_to_save = vec![];
 
         for chunk_pos in chunks_to_unload {
             let chunk = world.loaded_chunks.remove(&chunk_pos);
+            world.chunk_lods.remove(&chunk_pos);
+
+            // forget pending work, dropping a task cancels it
+            world.chunks_data_to_load.retain(|&pos| pos != chunk_pos);
+            world.chunks_mesh_to_load.retain(|&pos| pos != chunk_pos);
+            world.data_tasks.remove(&chunk_pos);
+            world.mesh_tasks.retain(|&(pos, _), _| pos != chunk_pos);
+            world.column_tasks.remove(&chunk_pos);
+            world.column_chunks.remove(&chunk_pos);
+            world.dirty_sections.remove(&chunk_pos);
+            world.section_quads.retain(|&(pos, _), _| pos != chunk_pos);
+
             if let Some(chunk) = chunk {
                 world
                     .chunks_mesh_to_unload
                     .push((chunk_pos, chunk.sections.len()));
+
+                // generated chunks nobody changed are generated again instead
+                if chunk.take_dirty() {
+                    chunks_to_save.push((chunk_pos, chunk));
+                }
             }
         }
+
+        world.save_chunks(&storage, chunks_to_save);
+    }
+
+    fn join_save_tasks(mut world: ResMut<World>, storage: Res<RegionStorage>) {
+        let mut completed = vec![];
+        world.save_tasks.retain(|&save, task| {
+            let status = block_on(poll_once(task));
+            let retain = status.is_none();
+            if let Some(result) = status {
+                completed.push((save, result));
+            }
+            retain
+        });
+
+        for (save, result) in completed {
+            if let Err(err) = result {
+                // saved again by the next autosave, until then they stay in memory
+                error!("Failed to save chunks to {}: {err}", storage.dir().display());
+                for (chunk, chunk_save) in world.saving_chunks.values() {
+                    if *chunk_save == save {
+                        chunk.mark_dirty();
+                    }
+                }
+                continue;
+            }
+
+            world
+                .saving_chunks
+                .retain(|_, (chunk, chunk_save)| *chunk_save != save || chunk.is_dirty());
+        }
+    }
+
+    fn send_explosions(mut world: ResMut<World>, mut explosions: MessageWriter<Explosion>) {
+        explosions.write_batch(world.explosions.drain(..));
+    }
+
+    /// Saves modified chunks every [`RegionStorage::autosave_interval`].
+    fn autosave(
+        mut world: ResMut<World>,
+        storage: Res<RegionStorage>,
+        time: Res<Time>,
+        mut since_save: Local<Duration>,
+    ) {
+        *since_save += time.delta();
+        if *since_save < storage.autosave_interval {
+            return;
+        }
+        *since_save = Duration::ZERO;
+
+        let dirty = world.take_dirty_chunks();
+        world.save_chunks(&storage, dirty);
+    }
+
+    /// Waits for the saves in flight and writes every modified chunk before the app closes.
+    fn save_on_exit(mut exit: MessageReader<AppExit>, mut world: ResMut<World>, storage: Res<RegionStorage>) {
+        if exit.read().last().is_none() {
+            return;
+        }
+
+        for (_, task) in world.save_tasks.drain() {
+            if let Err(err) = block_on(task) {
+                error!("Failed to save chunks to {}: {err}", storage.dir().display());
+            }
+        }
+
+        let dirty = world.take_dirty_chunks();
+        match storage.save_chunks(&dirty) {
+            Ok(()) => info!("Saved {} chunks to {}", dirty.len(), storage.dir().display()),
+            Err(err) => error!("Failed to save {} chunks to {}: {err}", dirty.len(), storage.dir().display()),
+        }
+        world.saving_chunks.clear();
     }
 
     pub fn unload_meshes(mut commands: Commands, mut world: ResMut<World>) {
@@ -100,28 +698,49 @@ impl WorldPlugin {
 
         for (chunk_pos, sections_len) in chunks_to_unload {
             for i in 0..sections_len {
-                let Some(chunk_id) = world.section_entities.remove(&(chunk_pos, i as i32)) else {
-                    continue;
-                };
+                for pass in MeshPass::ALL {
+                    let Some(chunk_id) = world.section_entities.remove(&(chunk_pos, i as i32, pass)) else {
+                        continue;
+                    };
 
-                if let Ok(mut entity) = commands.get_entity(chunk_id) {
-                    entity.despawn();
+                    if let Ok(mut entity) = commands.get_entity(chunk_id) {
+                        entity.despawn();
+                    }
                 }
             }
         }
     }
 
-    fn start_data_tasks(mut world: ResMut<World>) {
+    pub(crate) fn start_data_tasks(
+        mut world: ResMut<World>,
+        settings: Res<ChunkSchedulerSettings>,
+        priorities: Res<ChunkPriorities>,
+        storage: Res<RegionStorage>,
+    ) {
         let task_pool = AsyncComputeTaskPool::get();
-        let chunks_to_load: Vec<_> = world.chunks_data_to_load.drain(..).collect();
+        let mut chunks_to_load = std::mem::take(&mut world.chunks_data_to_load);
+        chunks_to_load.retain(|chunk_pos| {
+            !world.loaded_chunks.contains_key(chunk_pos) && !world.data_tasks.contains_key(chunk_pos)
+        });
+        priorities.sort(&mut chunks_to_load);
+
+        let budget = settings.max_data_tasks.saturating_sub(world.data_tasks.len());
+        world.chunks_data_to_load = chunks_to_load.split_off(budget.min(chunks_to_load.len()));
+
         for chunk_pos in chunks_to_load {
-            if world.loaded_chunks.contains_key(&chunk_pos)
-                || world.data_tasks.contains_key(&chunk_pos) {
+            // still waiting to be saved, the region file may not have it yet
+            if let Some((chunk, _)) = world.saving_chunks.get(&chunk_pos) {
+                let chunk = chunk.clone();
+                world.insert_chunk(chunk_pos, chunk);
                 continue;
             }
 
+            let registry = world.registry.clone();
+            let storage = storage.clone();
             let task = task_pool.spawn::<Chunk>(async move {
-                Self::generate_chunk_at(chunk_pos)
+                let chunk = Self::load_or_generate_chunk_at(&storage, chunk_pos);
+                light_chunk(&chunk, &registry);
+                chunk
             });
             world.data_tasks.insert(chunk_pos, task);
         }
@@ -140,24 +759,78 @@ impl WorldPlugin {
         });
 
         for (chunk_pos, chunk) in completed_chunks {
-            world.loaded_chunks.insert(chunk_pos, Arc::new(chunk));
-            world.chunks_mesh_to_load.push(chunk_pos);
+            world.insert_chunk(chunk_pos, Arc::new(chunk));
         }
     }
 
-    fn start_mesh_tasks(mut world: ResMut<World>) {
+    pub(crate) fn start_mesh_tasks(
+        mut world: ResMut<World>,
+        settings: Res<ChunkSchedulerSettings>,
+        priorities: Res<ChunkPriorities>,
+        mesher: Res<ChunkMesher>,
+        layout: Res<MeshLayout>,
+    ) {
         let task_pool = AsyncComputeTaskPool::get();
-        let chunks_to_mesh: Vec<_> = world.chunks_mesh_to_load.drain(..).collect();
+        let mut chunks_to_mesh = std::mem::take(&mut world.chunks_mesh_to_load);
+        priorities.sort(&mut chunks_to_mesh);
+
         for chunk_pos in chunks_to_mesh {
-            let chunk = Arc::clone(&world.loaded_chunks[&chunk_pos]);
+            let Some(chunk) = world.loaded_chunks.get(&chunk_pos).cloned() else {
+                continue;
+            };
+            if world.mesh_tasks.len() + world.column_tasks.len() >= settings.max_mesh_tasks {
+                world.chunks_mesh_to_load.push(chunk_pos);
+                continue;
+            }
+            let lod = world.lod_of(chunk_pos);
+            // no entry means every section needs meshing
+            let dirty = world.dirty_sections.remove(&chunk_pos);
+            let is_dirty = |section_y: i32| dirty.as_ref().is_none_or(|dirty| dirty.contains(&section_y));
+
+            if *layout == MeshLayout::Columns && lod.is_full() {
+                let sections = (0..chunk.sections.len() as i32)
+                    .map(|section_y| {
+                        let cached = world.section_quads.get(&(chunk_pos, section_y));
+                        let section = match cached {
+                            Some(quads) if !is_dirty(section_y) => ColumnSection::Cached(Arc::clone(quads)),
+                            _ => ColumnSection::Dirty(world.section_neighbors(chunk_pos, section_y as usize, lod)),
+                        };
+                        (section_y, section)
+                    })
+                    .collect();
+
+                let registry = world.registry.clone();
+                let task = task_pool.spawn::<ColumnMesh>(async move { mesh_column(sections, &registry) });
+                world.column_tasks.insert(chunk_pos, task);
+                world.mesh_tasks.retain(|&(pos, _), _| pos != chunk_pos);
+                continue;
+            }
+
+            world.column_tasks.remove(&chunk_pos);
+            let mut postponed = HashSet::new();
             for section_y in 0..chunk.sections.len() {
-                let section = SectionNeighbors::new(&world.loaded_chunks, chunk_pos, section_y);
+                if !is_dirty(section_y as i32) {
+                    continue;
+                }
+                if world.mesh_tasks.len() + world.column_tasks.len() >= settings.max_mesh_tasks {
+                    postponed.insert(section_y as i32);
+                    continue;
+                }
 
-                let task = task_pool.spawn::<Option<ChunkSectionMesh>>(async move {
-                    generate_section_mesh(section)
+                let section = world.section_neighbors(chunk_pos, section_y, lod);
+                let mesher = mesher.mesher();
+                let registry = world.registry.clone();
+                let task = task_pool.spawn::<SectionMeshes>(async move {
+                    SectionMeshes::build(mesher.as_ref(), &section, lod, &registry)
                 });
                 world.mesh_tasks.insert((chunk_pos, section_y as i32), task);
             }
+
+            // sections over the task limit wait for the next frame
+            if !postponed.is_empty() {
+                world.dirty_sections.entry(chunk_pos).or_default().extend(postponed);
+                world.chunks_mesh_to_load.push(chunk_pos);
+            }
         }
     }
 
@@ -166,52 +839,147 @@ impl WorldPlugin {
         mut world: ResMut<World>,
         mut meshes: ResMut<Assets<Mesh>>,
         material: Res<GlobalChunkMaterial>,
+        settings: Res<ChunkSchedulerSettings>,
+        priorities: Res<ChunkPriorities>,
     ) {
         let mut completed_sections = vec![];
 
         world.mesh_tasks.retain(|&(chunk_pos, section_y), task| {
             let status = block_on(poll_once(task));
             let retain = status.is_none();
-            if let Some(section) = status {
-                if section.is_none() {
-                    // section is empty!
-                    return false;
+            if let Some(meshes) = status {
+                for (pass, mesh) in meshes.into_passes() {
+                    completed_sections.push((chunk_pos, section_y, pass, mesh));
                 }
-                let section_mesh = section.unwrap();
-                completed_sections.push((chunk_pos, section_y, section_mesh));
             }
             retain
         });
 
-        for (chunk_pos, section_y, section_mesh) in completed_sections {
-            let mut mesh = Mesh::new(
-                PrimitiveTopology::TriangleList,
-                RenderAssetUsages::RENDER_WORLD,
-            );
+        let mut completed_columns = vec![];
+        world.column_tasks.retain(|&chunk_pos, task| {
+            let status = block_on(poll_once(task));
+            let retain = status.is_none();
+            if let Some(column) = status {
+                completed_columns.push((chunk_pos, column));
+            }
+            retain
+        });
+
+        for &(chunk_pos, ..) in &completed_sections {
+            world.column_chunks.remove(&chunk_pos);
+        }
+        for (chunk_pos, column) in completed_columns {
+            // the column mesh lives in the entity of the bottom section, the others are cleared
+            world.column_chunks.insert(chunk_pos);
+            for (section_y, quads) in column.sections {
+                world.section_quads.insert((chunk_pos, section_y), quads);
+                if section_y != 0 {
+                    completed_sections.push((chunk_pos, section_y, MeshPass::Opaque, None));
+                }
+            }
+            completed_sections.push((chunk_pos, 0, MeshPass::Opaque, column.mesh));
+            for (section_y, meshes) in column.dirty {
+                for (pass, mesh) in meshes.into_passes() {
+                    if pass != MeshPass::Opaque {
+                        completed_sections.push((chunk_pos, section_y, pass, mesh));
+                    }
+                }
+            }
+        }
+
+        let mut uploads = std::mem::take(&mut world.mesh_uploads);
+        uploads.extend(completed_sections);
+        // chunks can get unloaded while their meshes wait for an upload slot
+        uploads.retain(|(chunk_pos, _, _, _)| world.loaded_chunks.contains_key(chunk_pos));
+        uploads.sort_by(|a, b| priorities.priority(a.0).total_cmp(&priorities.priority(b.0)));
+        world.mesh_uploads = uploads.split_off(settings.max_mesh_uploads.min(uploads.len()));
+
+        for (chunk_pos, section_y, pass, section_mesh) in uploads {
+            if let Some(entity) = world.section_entities.remove(&(chunk_pos, section_y, pass)) {
+                commands.entity(entity).despawn();
+            }
+
+            let Some(section_mesh) = section_mesh else {
+                // section is empty!
+                continue;
+            };
+
+            // translucent meshes stay in the main world to get their faces sorted
+            let usages = match pass {
+                MeshPass::Translucent => RenderAssetUsages::all(),
+                _ => RenderAssetUsages::RENDER_WORLD,
+            };
+            let translucent_faces = (pass == MeshPass::Translucent).then(|| TranslucentFaces {
+                centers: quad_centers(&section_mesh.vertices),
+            });
+
+            let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, usages);
             mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, section_mesh.vertices);
             mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, section_mesh.normals);
+            mesh.insert_attribute(ATTRIBUTE_VOXEL_LIGHT, section_mesh.lights);
             mesh.insert_indices(Indices::U32(section_mesh.indices));
 
-            if let Some(&entity) = world.section_entities.get(&(chunk_pos, section_y)) {
-                commands.entity(entity).despawn();
+            let mut entity = commands.spawn((
+                Mesh3d(meshes.add(mesh)),
+                MeshMaterial3d(material.of(pass)),
+                Transform::from_xyz(
+                    chunk_pos.0.x as f32 * CHUNK_SIZE as f32,
+                    section_y as f32 * CHUNK_SIZE as f32,
+                    chunk_pos.0.y as f32 * CHUNK_SIZE as f32,
+                ),
+                chunk_pos,
+            ));
+            if let Some(translucent_faces) = translucent_faces {
+                entity.insert(translucent_faces);
             }
 
-            let entity = commands
-                .spawn((
-                    Mesh3d(meshes.add(mesh)),
-                    MeshMaterial3d(material.0.clone()),
-                    Transform::from_xyz(
-                        chunk_pos.0.x as f32 * CHUNK_SIZE as f32,
-                        section_y as f32 * CHUNK_SIZE as f32,
-                        chunk_pos.0.y as f32 * CHUNK_SIZE as f32,
-                    ),
-                    chunk_pos,
-                ))
-                .id();
-
             world
                 .section_entities
-                .insert((chunk_pos, section_y), entity);
+                .insert((chunk_pos, section_y, pass), entity.id());
+        }
+    }
+
+    /// Sorts the faces of translucent sections back to front, for new sections and for every
+    /// section once the camera moved into another block.
+    fn sort_translucent_faces(
+        cameras: Query<&GlobalTransform, With<Camera3d>>,
+        sections: Query<(&Mesh3d, &TranslucentFaces, &GlobalTransform)>,
+        added: Query<Entity, Added<TranslucentFaces>>,
+        mut meshes: ResMut<Assets<Mesh>>,
+        mut last_eye_block: Local<Option<IVec3>>,
+    ) {
+        let Some(camera) = cameras.iter().next() else {
+            return;
+        };
+        let eye = camera.translation();
+        let eye_block = eye.floor().as_ivec3();
+
+        let moved = *last_eye_block != Some(eye_block);
+        *last_eye_block = Some(eye_block);
+
+        let mut sort = |(mesh, faces, transform): (&Mesh3d, &TranslucentFaces, &GlobalTransform)| {
+            let local_eye = eye - transform.translation();
+            if let Some(mesh) = meshes.get_mut(&mesh.0) {
+                mesh.insert_indices(Indices::U32(sort_quads_back_to_front(&faces.centers, local_eye)));
+            }
+        };
+
+        if moved {
+            sections.iter().for_each(&mut sort);
+        } else {
+            sections.iter_many(&added).for_each(&mut sort);
+        }
+    }
+
+    /// Chunk as saved in its region file, generated when it was never saved or can't be read.
+    pub fn load_or_generate_chunk_at(storage: &RegionStorage, coord: ChunkPos) -> Chunk {
+        match storage.load_chunk(coord) {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => Self::generate_chunk_at(coord),
+            Err(err) => {
+                warn!("Failed to load chunk {:?}, generating it instead: {err}", coord.0);
+                Self::generate_chunk_at(coord)
+            }
         }
     }
 
@@ -221,3 +989,385 @@ impl WorldPlugin {
         chunk
     }
 }
+
+/// Every position of a box, `min` and `max` inclusive.
+fn block_positions(min: IVec3, max: IVec3) -> impl Iterator<Item = IVec3> {
+    (min.z..=max.z).flat_map(move |z| (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z))))
+}
+
+/// Position of a world block inside its chunk.
+fn local_block_pos(pos: IVec3) -> IVec3 {
+    IVec3::new(pos.x.rem_euclid(CHUNK_SIZE), pos.y, pos.z.rem_euclid(CHUNK_SIZE))
+}
+
+#[cfg(test)]
+impl World {
+    /// World of empty chunks two sections high around the origin, from `-radius` to `radius`
+    /// chunks exclusive, holding `blocks`. Light and meshes are left out.
+    pub(crate) fn with_blocks(radius: i32, blocks: impl IntoIterator<Item = (IVec3, Block)>) -> Self {
+        use crate::chunk::ChunkSection;
+        use std::sync::RwLock;
+
+        let mut world = World::default();
+        for x in -radius..radius {
+            for z in -radius..radius {
+                let mut chunk = Chunk::new();
+                chunk.sections = (0..2).map(|_| Arc::new(RwLock::new(ChunkSection::new()))).collect();
+                world.loaded_chunks.insert(ChunkPos(IVec2::new(x, z)), Arc::new(chunk));
+            }
+        }
+        for (pos, block) in blocks {
+            world.loaded_chunks[&ChunkPos::of_block(pos)].set(local_block_pos(pos), block);
+        }
+
+        world
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Orientation;
+    use bevy::math::BVec3;
+
+    fn block(name: &str) -> Block {
+        BlockRegistry::default().by_name(name).unwrap()
+    }
+
+    fn bottom_slab() -> Block {
+        block("voxel:stone_slab").with_facing(Orientation::Down)
+    }
+
+    /// Stone floor at y 0 with the given blocks on top.
+    fn floor_with(blocks: impl IntoIterator<Item = (IVec3, Block)>) -> World {
+        let floor = block_positions(IVec3::new(-16, 0, -16), IVec3::new(15, 0, 15)).map(|pos| (pos, block("voxel:stone")));
+        World::with_blocks(1, floor.chain(blocks))
+    }
+
+    /// Box of a player 0.6 wide and 1.8 high standing at `feet`.
+    fn player_at(feet: Vec3) -> Aabb {
+        Aabb::new(feet - Vec3::new(0.3, 0.0, 0.3), feet + Vec3::new(0.3, 1.8, 0.3))
+    }
+
+    fn assert_moved(sweep: Sweep, motion: Vec3, blocked: BVec3) {
+        assert!(sweep.motion.abs_diff_eq(motion, 1e-4), "moved {} instead of {motion}", sweep.motion);
+        assert_eq!(sweep.blocked, blocked);
+    }
+
+    #[test]
+    fn sweeping_past_a_corner_stops_only_the_blocked_axis() {
+        let world = floor_with([(IVec3::new(3, 1, 3), block("voxel:stone"))]);
+
+        // x goes first and passes the block, then z runs into its side
+        let sweep = world.sweep_aabb(player_at(Vec3::new(2.0, 1.0, 2.0)), Vec3::new(1.0, 0.0, 1.0));
+        assert_moved(sweep, Vec3::new(1.0, 0.0, 0.7), BVec3::new(false, false, true));
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(2.0, 1.0, 3.5)), Vec3::new(1.0, 0.0, 1.0));
+        assert_moved(sweep, Vec3::new(0.7, 0.0, 1.0), BVec3::new(true, false, false));
+
+        // boxes only touching the corner edge pass by
+        let sweep = world.sweep_aabb(player_at(Vec3::new(2.7, 1.0, 2.0)), Vec3::new(0.0, 0.0, 3.0));
+        assert_moved(sweep, Vec3::new(0.0, 0.0, 3.0), BVec3::FALSE);
+    }
+
+    #[test]
+    fn sweeping_into_a_wall_slides_along_it() {
+        let wall = (-5..=5).flat_map(|z| [IVec3::new(3, 1, z), IVec3::new(3, 2, z)]);
+        let world = floor_with(wall.map(|pos| (pos, block("voxel:stone"))));
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(2.0, 1.0, 0.0)), Vec3::new(2.0, -0.5, 1.5));
+        assert_moved(sweep, Vec3::new(0.7, 0.0, 1.5), BVec3::new(true, true, false));
+    }
+
+    #[test]
+    fn fast_boxes_dont_tunnel_through_thin_blocks() {
+        let world = floor_with([(IVec3::new(5, 1, 0), block("voxel:stone")), (IVec3::new(5, 2, 0), block("voxel:stone"))]);
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(0.5, 20.0, 0.5)), Vec3::new(0.0, -100.0, 0.0));
+        assert_moved(sweep, Vec3::new(0.0, -19.0, 0.0), BVec3::new(false, true, false));
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(0.5, 1.0, 0.5)), Vec3::new(50.0, 0.0, 0.0));
+        assert_moved(sweep, Vec3::new(4.2, 0.0, 0.0), BVec3::new(true, false, false));
+    }
+
+    #[test]
+    fn slabs_stop_boxes_at_their_top() {
+        let world = floor_with([(IVec3::new(2, 1, 0), bottom_slab())]);
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(2.5, 4.0, 0.5)), Vec3::new(0.0, -5.0, 0.0));
+        assert_moved(sweep, Vec3::new(0.0, -2.5, 0.0), BVec3::new(false, true, false));
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(0.5, 1.0, 0.5)), Vec3::new(3.0, 0.0, 0.0));
+        assert_moved(sweep, Vec3::new(1.2, 0.0, 0.0), BVec3::new(true, false, false));
+
+        let sweep = world.sweep_aabb(player_at(Vec3::new(0.5, 1.5, 0.5)), Vec3::new(3.0, 0.0, 0.0));
+        assert_moved(sweep, Vec3::new(3.0, 0.0, 0.0), BVec3::FALSE);
+    }
+
+    #[test]
+    fn overlapping_needs_more_than_touching() {
+        let world = floor_with([(IVec3::new(2, 1, 0), bottom_slab()), (IVec3::new(4, 1, 0), block("voxel:tall_grass"))]);
+
+        assert!(!world.overlaps_solid(player_at(Vec3::new(0.5, 1.0, 0.5))));
+        assert!(world.overlaps_solid(player_at(Vec3::new(0.5, 0.9, 0.5))));
+        assert!(!world.overlaps_solid(player_at(Vec3::new(1.7, 1.0, 0.5))));
+        assert!(world.overlaps_solid(player_at(Vec3::new(1.8, 1.0, 0.5))));
+        assert!(!world.overlaps_solid(player_at(Vec3::new(2.5, 1.5, 0.5))));
+        assert!(!world.overlaps_solid(player_at(Vec3::new(4.5, 1.0, 0.5))));
+
+        // unloaded chunks are solid, above the world is empty
+        assert!(world.overlaps_solid(player_at(Vec3::new(40.0, 1.0, 0.5))));
+        assert!(!world.overlaps_solid(player_at(Vec3::new(0.5, 40.0, 0.5))));
+    }
+
+    #[test]
+    fn supported_boxes_stand_on_something() {
+        let world = floor_with([(IVec3::new(2, 1, 0), bottom_slab()), (IVec3::new(6, 1, 6), block("voxel:stone"))]);
+
+        assert!(world.is_supported(player_at(Vec3::new(0.5, 1.0, 0.5))));
+        assert!(!world.is_supported(player_at(Vec3::new(0.5, 1.05, 0.5))));
+        assert!(world.is_supported(player_at(Vec3::new(2.5, 1.5, 0.5))));
+
+        // standing over the edge of a pillar
+        assert!(world.is_supported(player_at(Vec3::new(7.2, 2.0, 6.5))));
+        assert!(!world.is_supported(player_at(Vec3::new(7.4, 2.0, 6.5))));
+    }
+
+    #[test]
+    fn blocks_in_boxes_come_from_every_loaded_chunk() {
+        let world = floor_with([(IVec3::new(-1, 1, 0), block("voxel:glass")), (IVec3::new(0, 1, 0), block("voxel:stone"))]);
+
+        // across the border between the chunks at x -1 and 0
+        let aabb = Aabb::new(Vec3::new(-1.5, 0.5, 0.5), Vec3::new(1.5, 1.5, 1.5));
+        let blocks: HashMap<_, _> = world.blocks_in_aabb(aabb).collect();
+        assert_eq!(blocks.len(), 4 * 2 * 2);
+        assert_eq!(blocks[&IVec3::new(-1, 1, 0)], block("voxel:glass"));
+        assert_eq!(blocks[&IVec3::new(0, 1, 0)], block("voxel:stone"));
+        assert_eq!(blocks[&IVec3::new(1, 1, 1)], Block(0));
+        assert_eq!(blocks[&IVec3::new(-2, 0, 1)], block("voxel:stone"));
+
+        // touching faces don't count
+        let touching = Aabb::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 2.0, 1.0));
+        assert_eq!(world.blocks_in_aabb(touching).map(|(pos, _)| pos).collect::<Vec<_>>(), [IVec3::new(0, 1, 0)]);
+
+        // half over the chunk at x 16, which isn't loaded
+        let aabb = Aabb::new(Vec3::new(15.5, 0.5, 0.5), Vec3::new(16.5, 0.9, 0.9));
+        let positions: Vec<_> = world.blocks_in_aabb(aabb).map(|(pos, _)| pos).collect();
+        assert_eq!(positions, [IVec3::new(15, 0, 0)]);
+        assert_eq!(world.blocks_in_aabb(aabb.translated(Vec3::X * 10.0)).count(), 0);
+    }
+
+    #[test]
+    fn sections_at_another_lod_keep_skirts_over_their_border() {
+        use crate::greedy_chunk_render_plugin::{greedy_face_quads, padded_section};
+        use crate::quad::Direction;
+
+        // stone from y 0 to 8 over the border between the chunks at x -16 and 0
+        let stone = block("voxel:stone");
+        let blocks = (0..8).flat_map(|y| (-16..16).flat_map(move |x| (-16..16).map(move |z| (IVec3::new(x, y, z), stone))));
+        let mut world = World::with_blocks(1, blocks);
+        let (near, far) = (ChunkPos(IVec2::ZERO), ChunkPos(IVec2::NEG_X));
+        // area in blocks of the faces a section shows towards its neighbor
+        let border_area = |world: &World, chunk_pos: ChunkPos, towards: Direction| {
+            let lod = world.lod_of(chunk_pos);
+            let padded = padded_section(&world.section_neighbors(chunk_pos, 0, lod), lod, &world.registry).unwrap();
+            greedy_face_quads(&padded)
+                .iter()
+                .filter(|face| face.direction == towards)
+                .map(|face| face.quad.w * face.quad.h * (lod.scale() * lod.scale()) as u32)
+                .sum::<u32>()
+        };
+
+        assert_eq!(border_area(&world, near, Direction::Left), 0);
+        assert_eq!(border_area(&world, far, Direction::Right), 0);
+
+        // both sides close the border on their own, so no crack shows between the levels
+        for lod in 1..=LodLevel::MAX.0 {
+            world.chunk_lods.insert(far, LodLevel(lod));
+            assert_eq!(border_area(&world, near, Direction::Left), 8 * 16, "LOD {lod}");
+            assert_eq!(border_area(&world, far, Direction::Right), 8 * 16, "LOD {lod}");
+        }
+    }
+
+    #[test]
+    fn column_meshes_are_found_from_every_section() {
+        let (stone, glass) = (block("voxel:stone"), block("voxel:glass"));
+        let (top_stone, top_glass) = (IVec3::new(1, 20, 1), IVec3::new(1, 21, 1));
+        let mut world = World::with_blocks(1, [(top_stone, stone), (top_glass, glass)]);
+        let chunk_pos = ChunkPos(IVec2::ZERO);
+        let [bottom, top, translucent] = [1, 2, 3].map(|index| Entity::from_raw_u32(index).unwrap());
+        world.section_entities.insert((chunk_pos, 0, MeshPass::Opaque), bottom);
+        world.section_entities.insert((chunk_pos, 1, MeshPass::Opaque), top);
+        world.section_entities.insert((chunk_pos, 1, MeshPass::Translucent), translucent);
+        assert_eq!(world.section_entity(top_stone), Some(top));
+
+        // the column mesh replaces the opaque meshes of the upper sections
+        world.section_entities.remove(&(chunk_pos, 1, MeshPass::Opaque));
+        world.column_chunks.insert(chunk_pos);
+        assert_eq!(world.section_entity(top_stone), Some(bottom));
+        assert_eq!(world.section_entity(top_glass), Some(translucent));
+        assert_eq!(world.section_entity(IVec3::new(17, 20, 1)), None);
+    }
+
+    #[test]
+    fn mesh_tasks_stop_at_the_limit_within_a_chunk() {
+        use bevy::ecs::system::RunSystemOnce;
+        use bevy::tasks::TaskPool;
+
+        AsyncComputeTaskPool::get_or_init(TaskPool::new);
+        let mut world = World::with_blocks(1, []);
+        world.chunks_mesh_to_load = world.loaded_chunks.keys().copied().collect();
+        let settings = ChunkSchedulerSettings {
+            max_mesh_tasks: 3,
+            ..ChunkSchedulerSettings::default()
+        };
+
+        let mut app = App::new();
+        app.insert_resource(world)
+            .insert_resource(settings)
+            .init_resource::<ChunkPriorities>()
+            .init_resource::<ChunkMesher>()
+            .init_resource::<MeshLayout>();
+        app.world_mut().run_system_once(WorldPlugin::start_mesh_tasks).unwrap();
+
+        // 4 chunks of 2 sections, the one split by the limit keeps its other section dirty
+        let world = app.world().resource::<World>();
+        assert_eq!(world.mesh_tasks.len(), 3);
+        assert_eq!(world.chunks_mesh_to_load.len(), 3);
+        let split = world.chunks_mesh_to_load.iter().find(|pos| world.dirty_sections.contains_key(pos)).unwrap();
+        assert_eq!(world.dirty_sections[split], HashSet::from([1]));
+        assert!(world.mesh_tasks.contains_key(&(*split, 0)));
+    }
+
+    fn assert_hit(hit: Option<RaycastHit>, pos: IVec3, normal: Direction, point: Vec3) {
+        let hit = hit.unwrap_or_else(|| panic!("missed {pos}"));
+        assert_eq!((hit.pos, hit.normal), (pos, normal));
+        assert!(hit.point.abs_diff_eq(point, 1e-4), "hit at {} instead of {point}", hit.point);
+    }
+
+    #[test]
+    fn rays_hit_the_face_they_enter_through() {
+        let stone = block("voxel:stone");
+        let world = floor_with([(IVec3::new(4, 5, 0), stone), (IVec3::new(-3, 5, 0), stone), (IVec3::new(0, 5, 4), stone)]);
+        let origin = Vec3::new(0.5, 5.5, 0.5);
+
+        assert_hit(world.raycast(origin, Vec3::X, 10.0), IVec3::new(4, 5, 0), Direction::Left, Vec3::new(4.0, 5.5, 0.5));
+        assert_hit(world.raycast(origin, Vec3::NEG_X, 10.0), IVec3::new(-3, 5, 0), Direction::Right, Vec3::new(-2.0, 5.5, 0.5));
+        assert_hit(world.raycast(origin, Vec3::Z * 3.0, 10.0), IVec3::new(0, 5, 4), Direction::Forward, Vec3::new(0.5, 5.5, 4.0));
+        assert_hit(world.raycast(origin, Vec3::NEG_Y, 10.0), IVec3::new(0, 0, 0), Direction::Up, Vec3::new(0.5, 1.0, 0.5));
+        assert_eq!(world.raycast(origin, Vec3::X, 10.0).unwrap().distance, 3.5);
+        assert_eq!(world.raycast(origin, Vec3::ZERO, 10.0), None);
+        // nothing above the floor
+        assert_eq!(world.raycast(origin, Vec3::Y, 100.0), None);
+    }
+
+    #[test]
+    fn diagonal_rays_walk_every_block_they_cross() {
+        let stone = block("voxel:stone");
+        let wall = (0..16).map(|y| (IVec3::new(3, y, 0), stone));
+        let world = floor_with(wall);
+        let origin = Vec3::new(0.5, 5.5, 0.5);
+
+        let direction = Vec3::new(1.0, 0.5, 0.0);
+        let hit = world.raycast(origin, direction, 10.0).unwrap();
+        assert_hit(Some(hit), IVec3::new(3, 6, 0), Direction::Left, Vec3::new(3.0, 6.75, 0.5));
+        assert!((hit.distance - 2.5 * direction.length()).abs() < 1e-4);
+
+        // down and forward, into the side of a block and just past its corner onto the floor
+        let world = floor_with([(IVec3::new(2, 3, 0), stone)]);
+        let hit = world.raycast(Vec3::new(0.5, 4.5, 0.5), Vec3::new(1.0, -0.5, 0.0), 10.0);
+        assert_hit(hit, IVec3::new(2, 3, 0), Direction::Left, Vec3::new(2.0, 3.75, 0.5));
+        let hit = world.raycast(Vec3::new(0.5, 4.4, 0.5), Vec3::new(1.0, -1.0, 0.0), 10.0);
+        assert_hit(hit, IVec3::new(3, 0, 0), Direction::Up, Vec3::new(3.9, 1.0, 0.5));
+    }
+
+    #[test]
+    fn rays_stop_at_their_distance_and_the_loaded_chunks() {
+        let stone = block("voxel:stone");
+        let world = floor_with([(IVec3::new(4, 5, 0), stone), (IVec3::new(2, 5, 3), stone)]);
+        let origin = Vec3::new(0.5, 5.5, 0.5);
+
+        assert_eq!(world.raycast(origin, Vec3::X, 3.4), None);
+        assert_eq!(world.raycast(origin, Vec3::X, 3.5).unwrap().pos, IVec3::new(4, 5, 0));
+
+        // starting inside a block hits it right away
+        let inside = Vec3::new(4.25, 5.5, 0.75);
+        let hit = world.raycast(inside, Vec3::NEG_X, 10.0).unwrap();
+        assert_eq!((hit.pos, hit.distance, hit.point), (IVec3::new(4, 5, 0), 0.0, inside));
+
+        // across the border between the chunks at x -16 and 0, and into the chunk at 16 which
+        // isn't loaded
+        assert_hit(world.raycast(Vec3::new(-9.5, 5.5, 3.5), Vec3::X, 20.0), IVec3::new(2, 5, 3), Direction::Left, Vec3::new(2.0, 5.5, 3.5));
+        assert_eq!(world.raycast(Vec3::new(5.5, 5.5, 0.5), Vec3::X, 100.0), None);
+    }
+
+    #[test]
+    fn filtered_rays_go_through_rejected_blocks() {
+        let (glass, stone) = (block("voxel:glass"), block("voxel:stone"));
+        let world = floor_with([(IVec3::new(2, 5, 0), glass), (IVec3::new(4, 5, 0), stone)]);
+        let origin = Vec3::new(0.5, 5.5, 0.5);
+
+        assert_eq!(world.raycast(origin, Vec3::X, 10.0).unwrap().pos, IVec3::new(2, 5, 0));
+        let opaque = world.raycast_filtered(origin, Vec3::X, 10.0, |block, definition| block.is_solid() && !definition.translucent);
+        assert_hit(opaque, IVec3::new(4, 5, 0), Direction::Left, Vec3::new(4.0, 5.5, 0.5));
+        let air = world.raycast_filtered(Vec3::new(2.5, 5.5, 0.5), Vec3::X, 10.0, |block, _| block == Block(0));
+        assert_eq!(air.map(|hit| (hit.pos, hit.distance)), Some((IVec3::new(3, 5, 0), 0.5)));
+    }
+
+    /// World with a hollow cube of `block` three blocks away from the center of the block at
+    /// (8, 8, 8), in the middle of the bottom section of chunk (0, 0).
+    fn shelter(block: Block) -> World {
+        let (min, max) = (IVec3::splat(5), IVec3::splat(11));
+        let shell = block_positions(min, max).filter(|pos| pos.cmpeq(min).any() || pos.cmpeq(max).any());
+        World::with_blocks(2, shell.map(|pos| (pos, block)))
+    }
+
+    #[test]
+    fn explosions_break_weak_blocks_only() {
+        let center = Vec3::splat(8.5);
+        let mut stone = shelter(block("voxel:stone"));
+        assert!(stone.explode(center, 3.0).is_empty());
+        assert_eq!(stone.read_volume(IVec3::splat(5), IVec3::splat(7)), shelter(block("voxel:stone")).read_volume(IVec3::splat(5), IVec3::splat(7)));
+
+        let glass = block("voxel:glass");
+        let mut world = shelter(glass);
+        let before = world.read_volume(IVec3::ZERO, IVec3::splat(16));
+        let destroyed = world.explode(center, 3.0);
+
+        let positions: HashSet<_> = destroyed.iter().copied().collect();
+        assert_eq!(positions.len(), destroyed.len(), "positions are reported once");
+        let changed: HashSet<_> = before
+            .iter()
+            .filter(|&(pos, block)| world.get_block(pos).unwrap() != block)
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(positions, changed);
+        // the middle of every wall is straight in the blast
+        for pos in [IVec3::new(5, 8, 8), IVec3::new(11, 8, 8), IVec3::new(8, 5, 8), IVec3::new(8, 11, 8), IVec3::new(8, 8, 5), IVec3::new(8, 8, 11)] {
+            assert!(positions.contains(&pos), "{pos} survived");
+        }
+        assert!(positions.iter().all(|&pos| world.get_block(pos) == Some(Block(0))));
+    }
+
+    #[test]
+    fn explosions_send_one_message_and_remesh_each_section_once() {
+        use bevy::ecs::message::Messages;
+        use bevy::ecs::system::RunSystemOnce;
+
+        let glass = block("voxel:glass");
+        let mut world = shelter(glass);
+        let destroyed = world.explode(Vec3::splat(8.5), 4.0);
+
+        assert_eq!(world.chunks_mesh_to_load, [ChunkPos(IVec2::ZERO)]);
+        assert_eq!(world.dirty_sections[&ChunkPos(IVec2::ZERO)], HashSet::from([0]));
+
+        let mut app = App::new();
+        app.add_message::<Explosion>().insert_resource(world);
+        app.world_mut().run_system_once(WorldPlugin::send_explosions).unwrap();
+        let explosions: Vec<_> = app.world().resource::<Messages<Explosion>>().iter_current_update_messages().cloned().collect();
+        assert_eq!(explosions.len(), 1);
+        assert_eq!(explosions[0].center, Vec3::splat(8.5));
+        assert_eq!(explosions[0].destroyed, destroyed.iter().map(|&pos| (pos, glass)).collect::<Vec<_>>());
+        assert!(app.world().resource::<World>().explosions.is_empty());
+    }
+}
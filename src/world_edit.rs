@@ -0,0 +1,227 @@
+use crate::block::Block;
+use crate::block_volume::BlockVolume;
+use crate::chunk::{CHUNK_SIZE, ChunkPos};
+use crate::world::World;
+use bevy::math::{IVec3, Vec3};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fmt;
+
+/// Batch of block changes applied to the world at once by [`World::apply_edit`]. Later
+/// operations overwrite earlier ones where they overlap, blocks above or below the world are
+/// left out when applying.
+///
+/// Boxes are given by two corners, both included, in any order.
+#[derive(Debug, Clone, Default)]
+pub struct WorldEdit {
+    blocks: HashMap<IVec3, Block>,
+}
+
+impl WorldEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Changes of the edit, in no particular order.
+    pub fn blocks(&self) -> impl Iterator<Item = (IVec3, Block)> + '_ {
+        self.blocks.iter().map(|(&pos, &block)| (pos, block))
+    }
+
+    pub fn set(&mut self, pos: IVec3, block: Block) -> &mut Self {
+        self.blocks.insert(pos, block);
+        self
+    }
+
+    pub fn fill(&mut self, a: IVec3, b: IVec3, block: Block) -> &mut Self {
+        for pos in box_positions(a, b) {
+            self.blocks.insert(pos, block);
+        }
+        self
+    }
+
+    /// Walls, floor and ceiling of the box made of `block` with air inside.
+    pub fn hollow(&mut self, a: IVec3, b: IVec3, block: Block) -> &mut Self {
+        let (min, max) = (a.min(b), a.max(b));
+        for pos in box_positions(min, max) {
+            let on_border = pos.cmpeq(min).any() || pos.cmpeq(max).any();
+            self.blocks.insert(pos, if on_border { block } else { Block(0) });
+        }
+        self
+    }
+
+    /// Blocks whose center is within `radius` of the center of the `center` block.
+    pub fn sphere(&mut self, center: IVec3, radius: f32, block: Block) -> &mut Self {
+        let reach = IVec3::splat(radius.ceil() as i32);
+        for pos in box_positions(center - reach, center + reach) {
+            if (pos - center).as_vec3().length_squared() <= radius * radius {
+                self.blocks.insert(pos, block);
+            }
+        }
+        self
+    }
+
+    /// Upright cylinder standing on the `base` block, `height` blocks high.
+    pub fn cylinder(&mut self, base: IVec3, radius: f32, height: i32, block: Block) -> &mut Self {
+        let reach = radius.ceil() as i32;
+        let top = base + IVec3::new(reach, height - 1, reach);
+        for pos in box_positions(base - IVec3::new(reach, 0, reach), top) {
+            let offset = (pos - base).as_vec3() * Vec3::new(1.0, 0.0, 1.0);
+            if offset.length_squared() <= radius * radius {
+                self.blocks.insert(pos, block);
+            }
+        }
+        self
+    }
+
+    /// Replaces the loaded blocks of the box `matches` accepts.
+    pub fn replace(&mut self, world: &World, a: IVec3, b: IVec3, matches: impl Fn(Block) -> bool, block: Block) -> &mut Self {
+        for pos in box_positions(a, b) {
+            if world.get_block(pos).is_some_and(&matches) {
+                self.blocks.insert(pos, block);
+            }
+        }
+        self
+    }
+
+    /// Copies the blocks of a box as they are in the world now, with the minimum corner of the box
+    /// going to `to`. Blocks which aren't loaded are copied as air.
+    pub fn copy(&mut self, world: &World, a: IVec3, b: IVec3, to: IVec3) -> &mut Self {
+        self.paste(&world.read_box(a, b), to, false)
+    }
+
+    /// Places the blocks of a volume with its minimum corner at `to`, leaving out air when
+    /// `skip_air` is set.
+    pub fn paste(&mut self, volume: &BlockVolume, to: IVec3, skip_air: bool) -> &mut Self {
+        for (pos, block) in volume.iter() {
+            if !skip_air || block.is_solid() {
+                self.blocks.insert(to + pos, block);
+            }
+        }
+        self
+    }
+
+    /// Changes grouped by the chunk section they're in.
+    pub(crate) fn by_section(&self) -> HashMap<(ChunkPos, i32), Vec<(IVec3, Block)>> {
+        let mut sections: HashMap<_, Vec<_>> = HashMap::new();
+        for (&pos, &block) in &self.blocks {
+            sections.entry(section_of(pos)).or_default().push((pos, block));
+        }
+        sections
+    }
+}
+
+/// Chunk and section a world position is in.
+pub(crate) fn section_of(pos: IVec3) -> (ChunkPos, i32) {
+    (ChunkPos::of_block(pos), pos.y.div_euclid(CHUNK_SIZE))
+}
+
+/// Every position of a box, both corners included.
+fn box_positions(a: IVec3, b: IVec3) -> impl Iterator<Item = IVec3> {
+    let (min, max) = (a.min(b), a.max(b));
+    (min.y..=max.y).flat_map(move |y| (min.z..=max.z).flat_map(move |z| (min.x..=max.x).map(move |x| IVec3::new(x, y, z))))
+}
+
+/// Reason an edit wasn't applied.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EditError {
+    /// The edit touches a chunk which isn't loaded.
+    NotLoaded(ChunkPos),
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::NotLoaded(chunk_pos) => write!(f, "chunk {} isn't loaded", chunk_pos.0),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_registry::BlockRegistry;
+    use bevy::math::IVec2;
+
+    fn block(name: &str) -> Block {
+        BlockRegistry::default().by_name(name).unwrap()
+    }
+
+    /// How many blocks of every loaded chunk are `block`.
+    fn count(world: &World, block: Block) -> usize {
+        let volume = world.read_volume(IVec3::new(-16, 0, -16), IVec3::splat(32));
+        volume.iter().filter(|&(_, found)| found == block).count()
+    }
+
+    fn applied(edit: &WorldEdit) -> World {
+        let mut world = World::with_blocks(1, []);
+        world.apply_edit(edit).unwrap();
+        world
+    }
+
+    #[test]
+    fn shapes_place_the_expected_blocks() {
+        let stone = block("voxel:stone");
+
+        let world = applied(WorldEdit::new().fill(IVec3::new(3, 2, 4), IVec3::new(0, 1, 0), stone));
+        assert_eq!(count(&world, stone), 4 * 2 * 5);
+
+        // the air inside overwrites what was there
+        let world = applied(
+            WorldEdit::new()
+                .fill(IVec3::new(-1, 2, -1), IVec3::new(1, 4, 1), block("voxel:glass"))
+                .hollow(IVec3::new(-2, 1, -2), IVec3::new(2, 5, 2), stone),
+        );
+        assert_eq!(count(&world, stone), 5 * 5 * 5 - 3 * 3 * 3);
+        assert_eq!(count(&world, block("voxel:glass")), 0);
+
+        // 1 center, 6 at distance 1, 12 at sqrt 2, 8 at sqrt 3 and 6 at 2
+        let world = applied(WorldEdit::new().sphere(IVec3::new(0, 8, 0), 2.0, stone));
+        assert_eq!(count(&world, stone), 33);
+        assert_eq!(world.get_block(IVec3::new(0, 10, 0)), Some(stone));
+        assert_eq!(world.get_block(IVec3::new(1, 10, 0)), Some(Block(0)));
+
+        let world = applied(WorldEdit::new().cylinder(IVec3::new(0, 1, 0), 1.5, 3, stone));
+        assert_eq!(count(&world, stone), 9 * 3);
+        assert_eq!(world.get_block(IVec3::new(1, 3, -1)), Some(stone));
+        assert_eq!(world.get_block(IVec3::new(0, 4, 0)), Some(Block(0)));
+
+        // blocks above the world are left out
+        let world = applied(WorldEdit::new().fill(IVec3::new(0, 30, 0), IVec3::new(0, 40, 0), stone));
+        assert_eq!(count(&world, stone), 2);
+    }
+
+    #[test]
+    fn replacing_only_touches_matching_blocks() {
+        let (stone, glass, water) = (block("voxel:stone"), block("voxel:glass"), block("voxel:water"));
+        let mut edit = WorldEdit::new();
+        for x in 0..8 {
+            edit.set(IVec3::new(x, 1, 0), if x % 2 == 0 { stone } else { glass });
+        }
+        let mut world = applied(&edit);
+
+        let mut replace = WorldEdit::new();
+        replace.replace(&world, IVec3::new(-4, 0, -4), IVec3::new(5, 4, 4), |block| block == glass, water);
+        assert_eq!(replace.blocks().count(), 3);
+        world.apply_edit(&replace).unwrap();
+        assert_eq!((count(&world, stone), count(&world, glass), count(&world, water)), (4, 1, 3));
+    }
+
+    #[test]
+    fn edits_touching_unloaded_chunks_change_nothing() {
+        let stone = block("voxel:stone");
+        let mut world = World::with_blocks(1, [(IVec3::new(10, 1, 0), stone)]);
+
+        let mut edit = WorldEdit::new();
+        edit.fill(IVec3::new(0, 1, 0), IVec3::new(20, 1, 0), Block(0));
+        assert_eq!(world.apply_edit(&edit), Err(EditError::NotLoaded(ChunkPos(IVec2::new(1, 0)))));
+        assert_eq!(world.get_block(IVec3::new(10, 1, 0)), Some(stone));
+        assert_eq!(count(&world, stone), 1);
+
+        // unloaded blocks aren't replaced either
+        let mut replace = WorldEdit::new();
+        replace.replace(&world, IVec3::new(0, 1, 0), IVec3::new(20, 1, 0), |_| true, stone);
+        assert_eq!(replace.blocks().count(), 16);
+    }
+}